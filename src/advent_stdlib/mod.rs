@@ -1,5 +1,5 @@
 use std::cmp::Reverse;
-use std::collections::BinaryHeap;
+use std::collections::{BinaryHeap, HashMap};
 use std::fmt::Debug;
 use std::fmt::Display;
 use std::str::FromStr;
@@ -430,3 +430,342 @@ where
         },
     )
 }
+
+/// Signed grid coordinates, `y` grows downwards as for [`Index`].
+#[derive(Debug, Copy, Clone, Default, Eq, Hash, PartialEq, PartialOrd, Ord)]
+pub struct Point {
+    pub x: i64,
+    pub y: i64,
+}
+
+impl Point {
+    pub fn new(x: i64, y: i64) -> Self {
+        Point { x, y }
+    }
+
+    pub fn navigate_to(&self, direction: &Direction) -> Point {
+        match direction {
+            Direction::Up => Point::new(self.x, self.y - 1),
+            Direction::Down => Point::new(self.x, self.y + 1),
+            Direction::Left => Point::new(self.x - 1, self.y),
+            Direction::Right => Point::new(self.x + 1, self.y),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Cells<T> {
+    /// Rows of the allocated area, at least doubled when a point falls outside.
+    Dense {
+        origin: Point,
+        size: Size,
+        data: Vec<T>,
+    },
+    Sparse(HashMap<Point, T>),
+}
+
+/// Unbounded grid with signed coordinates, every cell starts as `default`.
+///
+/// Dense grids grow their rows as cells get written, sparse grids keep
+/// written cells in a map. Either converts to a [`Matrix`] of the written
+/// area for rendering or path finding.
+///
+/// # Examples
+/// ```
+/// use advent_of_code::advent_stdlib::*;
+///
+/// let mut grid = Grid::new('.');
+/// let mut point = Point::new(0, 0);
+/// for direction in [Direction::Left, Direction::Up, Direction::Up] {
+///     point = point.navigate_to(&direction);
+///     grid[point] = '#';
+/// }
+///
+/// assert_eq!(grid[Point::new(-1, -2)], '#');
+/// assert_eq!(grid[Point::new(100, -100)], '.');
+/// assert_eq!(grid.bounds(), Some((Point::new(-1, -2), Point::new(-1, 0))));
+///
+/// let mut sparse = Grid::sparse('.');
+/// sparse.set(Point::new(2, -1), '#');
+/// sparse.set(Point::new(0, 0), 'o');
+/// let matrix = sparse.to_matrix();
+/// assert_eq!((matrix.size.x, matrix.size.y), (3, 2));
+/// assert_eq!(matrix.data, vec!['.', '.', '#', 'o', '.', '.']);
+/// assert_eq!(sparse.index_of(&Point::new(0, 0)), Some(Index { x: 0, y: 1 }));
+/// ```
+#[derive(Debug, Clone)]
+pub struct Grid<T> {
+    default: T,
+    cells: Cells<T>,
+    bounds: Option<(Point, Point)>,
+}
+
+impl<T: Clone> Grid<T> {
+    /// Dense grid, for areas mostly written.
+    pub fn new(default: T) -> Self {
+        Grid {
+            default,
+            cells: Cells::Dense {
+                origin: Point::default(),
+                size: Size { x: 0, y: 0 },
+                data: Vec::new(),
+            },
+            bounds: None,
+        }
+    }
+
+    /// Sparse grid, for few cells scattered over a large area.
+    pub fn sparse(default: T) -> Self {
+        Grid {
+            default,
+            cells: Cells::Sparse(HashMap::new()),
+            bounds: None,
+        }
+    }
+
+    pub fn get(&self, point: &Point) -> &T {
+        match &self.cells {
+            Cells::Dense { origin, size, data } => match offset(origin, size, point) {
+                Some(offset) => &data[offset],
+                None => &self.default,
+            },
+            Cells::Sparse(cells) => cells.get(point).unwrap_or(&self.default),
+        }
+    }
+
+    /// Cell at `point`, created from the default and counted in the bounds.
+    pub fn get_mut(&mut self, point: Point) -> &mut T {
+        self.bounds = Some(match self.bounds {
+            Some((min, max)) => (
+                Point::new(min.x.min(point.x), min.y.min(point.y)),
+                Point::new(max.x.max(point.x), max.y.max(point.y)),
+            ),
+            None => (point, point),
+        });
+
+        if let Cells::Dense { origin, size, .. } = &self.cells {
+            if offset(origin, size, &point).is_none() {
+                self.grow(&point);
+            }
+        }
+        match &mut self.cells {
+            Cells::Dense { origin, size, data } => &mut data[offset(origin, size, &point).unwrap()],
+            Cells::Sparse(cells) => cells.entry(point).or_insert_with(|| self.default.clone()),
+        }
+    }
+
+    pub fn set(&mut self, point: Point, value: T) {
+        *self.get_mut(point) = value;
+    }
+
+    /// Smallest and largest coordinates of the written cells.
+    pub fn bounds(&self) -> Option<(Point, Point)> {
+        self.bounds
+    }
+
+    /// Written cells, for dense grids also the unwritten ones in the bounds.
+    pub fn iter(&self) -> Box<dyn Iterator<Item = (Point, &T)> + '_> {
+        match &self.cells {
+            Cells::Dense { .. } => {
+                let Some((min, max)) = self.bounds else {
+                    return Box::new(std::iter::empty());
+                };
+                Box::new((min.y..=max.y).flat_map(move |y| {
+                    (min.x..=max.x).map(move |x| {
+                        let point = Point::new(x, y);
+                        (point, self.get(&point))
+                    })
+                }))
+            }
+            Cells::Sparse(cells) => Box::new(cells.iter().map(|(point, cell)| (*point, cell))),
+        }
+    }
+
+    /// Position of `point` in [`Grid::to_matrix`].
+    pub fn index_of(&self, point: &Point) -> Option<Index> {
+        let (min, max) = self.bounds?;
+        if point.x < min.x || point.y < min.y || point.x > max.x || point.y > max.y {
+            return None;
+        }
+        Some(Index {
+            x: (point.x - min.x) as usize,
+            y: (point.y - min.y) as usize,
+        })
+    }
+
+    /// Point at `index` of [`Grid::to_matrix`].
+    pub fn point_of(&self, index: &Index) -> Point {
+        let (min, _) = self.bounds.unwrap_or_default();
+        Point::new(min.x + index.x as i64, min.y + index.y as i64)
+    }
+
+    /// Dense copy of the written area, empty if nothing was written.
+    pub fn to_matrix(&self) -> Matrix<T> {
+        let Some((min, max)) = self.bounds else {
+            return Matrix {
+                size: Size { x: 0, y: 0 },
+                data: Vec::new(),
+            };
+        };
+        let data = (min.y..=max.y)
+            .flat_map(|y| (min.x..=max.x).map(move |x| Point::new(x, y)))
+            .map(|point| self.get(&point).clone())
+            .collect();
+        Matrix {
+            size: Size {
+                x: (max.x - min.x + 1) as usize,
+                y: (max.y - min.y + 1) as usize,
+            },
+            data,
+        }
+    }
+
+    /// Reallocates a dense grid to hold `point`, at least doubling the
+    /// dimensions it grows along.
+    fn grow(&mut self, point: &Point) {
+        let Cells::Dense { origin, size, data } = &mut self.cells else {
+            return;
+        };
+        let (width, height) = (size.x as i64, size.y as i64);
+        let grow = |start: i64, length: i64, coordinate: i64| {
+            if length == 0 {
+                (coordinate, 1)
+            } else if coordinate < start {
+                let end = start + length;
+                let start = coordinate.min(start - length);
+                (start, end - start)
+            } else if coordinate >= start + length {
+                (start, (coordinate - start + 1).max(2 * length))
+            } else {
+                (start, length)
+            }
+        };
+        let (x, new_width) = grow(origin.x, width, point.x);
+        let (y, new_height) = grow(origin.y, height, point.y);
+
+        let new_origin = Point::new(x, y);
+        let new_size = Size {
+            x: new_width as usize,
+            y: new_height as usize,
+        };
+        let mut new_data = vec![self.default.clone(); new_size.x * new_size.y];
+        for (row, cells) in data.chunks(size.x.max(1)).enumerate() {
+            let start = offset(
+                &new_origin,
+                &new_size,
+                &Point::new(origin.x, origin.y + row as i64),
+            );
+            if let Some(start) = start {
+                new_data[start..start + cells.len()].clone_from_slice(cells);
+            }
+        }
+        *origin = new_origin;
+        *size = new_size;
+        *data = new_data;
+    }
+}
+
+/// Position of `point` in the rows of a dense area.
+fn offset(origin: &Point, size: &Size, point: &Point) -> Option<usize> {
+    let (x, y) = (point.x - origin.x, point.y - origin.y);
+    if x < 0 || y < 0 || x >= size.x as i64 || y >= size.y as i64 {
+        return None;
+    }
+    Some(y as usize * size.x + x as usize)
+}
+
+impl<T: Clone> std::ops::Index<Point> for Grid<T> {
+    type Output = T;
+
+    fn index(&self, point: Point) -> &T {
+        self.get(&point)
+    }
+}
+
+impl<T: Clone> std::ops::IndexMut<Point> for Grid<T> {
+    fn index_mut(&mut self, point: Point) -> &mut T {
+        self.get_mut(point)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Writes a spiral growing in every direction, each cell numbered in
+    /// writing order.
+    fn spiral(mut grid: Grid<i32>) -> Grid<i32> {
+        let mut point = Point::default();
+        let mut direction = Direction::Right;
+        let mut value = 1;
+        for length in 1..8 {
+            for _ in 0..2 {
+                for _ in 0..length {
+                    grid.set(point, value);
+                    value += 1;
+                    point = point.navigate_to(&direction);
+                }
+                direction = direction.turn_right();
+            }
+        }
+        grid
+    }
+
+    #[test]
+    fn dense_grid_keeps_cells_while_growing() {
+        let grid = spiral(Grid::new(0));
+        let sparse = spiral(Grid::sparse(0));
+
+        let Cells::Dense { size, data, .. } = &grid.cells else {
+            panic!("dense grid stored sparsely");
+        };
+        // 8 by 7 written, each growth doubling the dimensions from 1
+        assert!(size.x.is_power_of_two() && size.y.is_power_of_two());
+        assert!(size.x >= 8 && size.y >= 7);
+        assert_eq!(data.len(), size.x * size.y);
+
+        for (point, cell) in sparse.iter() {
+            assert_eq!(grid[point], *cell, "{point:?}");
+        }
+        assert_eq!(grid[Point::new(0, 0)], 1);
+        assert_eq!(grid[Point::new(-50, 50)], 0);
+        assert_eq!(grid.iter().count(), 56);
+        assert_eq!(grid.bounds(), sparse.bounds());
+        assert_eq!(grid.to_matrix().data, sparse.to_matrix().data);
+    }
+
+    #[test]
+    fn maps_points_to_matrix_indexes() {
+        let grid = spiral(Grid::sparse(0));
+        let (min, max) = grid.bounds().unwrap();
+        assert_eq!((min, max), (Point::new(-3, -3), Point::new(4, 3)));
+
+        let matrix = grid.to_matrix();
+        assert_eq!((matrix.size.x, matrix.size.y), (8, 7));
+        for (point, cell) in grid.iter() {
+            let index = grid.index_of(&point).unwrap();
+            assert_eq!(grid.point_of(&index), point);
+            assert_eq!(matrix.data[index.y * matrix.size.x + index.x], *cell);
+        }
+        assert_eq!(grid.index_of(&min), Some(Index { x: 0, y: 0 }));
+        assert_eq!(grid.index_of(&Point::new(5, 0)), None);
+        assert_eq!(grid.index_of(&Point::new(0, -4)), None);
+    }
+
+    #[test]
+    fn empty_grids() {
+        for grid in [Grid::new('.'), Grid::sparse('.')] {
+            assert_eq!(grid.bounds(), None);
+            assert_eq!(grid.iter().count(), 0);
+            assert_eq!(grid.index_of(&Point::default()), None);
+            assert_eq!(grid.to_matrix().data, Vec::<char>::new());
+            assert_eq!(grid[Point::new(3, -3)], '.');
+        }
+
+        // reading never counts in the bounds, writing the default does
+        let mut grid = Grid::new('.');
+        let _ = grid[Point::new(9, 9)];
+        grid[Point::new(-2, 1)] = '.';
+        assert_eq!(grid.bounds(), Some((Point::new(-2, 1), Point::new(-2, 1))));
+    }
+}
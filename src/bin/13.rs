@@ -6,7 +6,9 @@ use std::str::FromStr;
 advent_of_code::solution!(13);
 
 pub fn part_one(input: &str) -> Option<u64> {
-    let (_result, output) = run_machine_with_extended_memory(input, "").unwrap();
+    let (_result, output) = run_machine_with_limits(input, "", Limits::from_env())
+        .map_err(|error| eprintln!("{error}"))
+        .ok()?;
 
     let mut cells: Vec<MapCell> = Vec::<MapCell>::new();
     for _i in 0..(38 * 38) {
@@ -62,27 +64,13 @@ pub fn part_two(input: &str) -> Option<u64> {
         data: cells,
     };
 
-    let firmware = &format!("2{}", &input[1..]);
-
-    let firmware: Vec<i64> = firmware
-        .split(",")
-        .map(|i| i.parse().expect("Expected i64 list"))
-        .collect();
-
-    let input: Vec<i64> = Vec::new();
-
-    // extend memory to at least 4096
-    let ext_memory_size = 4096;
-    let mut ext_memory: Vec<i64> = Vec::with_capacity(ext_memory_size);
-    for i in 0..ext_memory_size {
-        if i < firmware.len() {
-            ext_memory.push(firmware[i]);
-        } else {
-            ext_memory.push(0);
-        }
-    }
-
-    let mut machine = Machine::new_with_input(ext_memory, input);
+    // insert coins
+    let firmware: Program = input.parse().unwrap_or_else(|error| panic!("{error}"));
+    let mut machine = firmware
+        .with_patch(0, 2)
+        .with_limits(Limits::from_env())
+        .machine()
+        .with_backend(Backend::Cached);
 
     // events
     let stdout = Term::buffered_stdout();
@@ -122,18 +110,27 @@ pub fn part_two(input: &str) -> Option<u64> {
         }
         //
 
-        machine.input.push(input);
+        machine.push_input(input);
         //dbg!(&input);
-        let output = match machine.execute() {
-            ExecuteResult::WaitingInput => &machine.output,
+        match machine.execute() {
+            ExecuteResult::WaitingInput => (),
             ExecuteResult::Halt(_result) => {
                 // get final score and exit
                 game_running = false;
-                &machine.output
             }
-            ExecuteResult::Panic(m) => panic!("{}", m),
-            _ => panic!("Unexpected result!"),
+            ExecuteResult::Error(error) => {
+                eprintln!("{error}\n{}", machine.dump());
+                return None;
+            }
+            result => match result.limit_error(machine.instruction_pointer) {
+                Some(error) => {
+                    eprintln!("{error}");
+                    return None;
+                }
+                None => panic!("Unexpected result [{result:?}]!"),
+            },
         };
+        let output = machine.take_output();
         let mut output_iterator = output.iter().peekable();
         while output_iterator.peek().is_some() {
             //println!("{:#?}", &output_iterator.peek());
@@ -148,9 +145,6 @@ pub fn part_two(input: &str) -> Option<u64> {
             }
         }
 
-        //clean output
-        machine.output = Vec::new();
-
         //println!("{}\n{}", map.print_to_string(), &score);
     }
     Some(score)
@@ -393,10 +387,10 @@ mod tests {
 
     #[test]
     fn test_part_two() {
-        // as part 2 set first instruction 104 -> 204 we need to
-        // consider it using relative mode for first arg (see 13-2).
+        // as part 2 sets address 0 to 2 the first instruction
+        // becomes a multiplication (see 13-3).
         let result = part_two(&advent_of_code::template::read_file_part(
-            "examples", DAY, 2,
+            "examples", DAY, 3,
         ));
         assert_eq!(result, Some(1337));
     }
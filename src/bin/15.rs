@@ -1,8 +1,7 @@
+use advent_of_code::advent_stdlib::{Direction, Grid, Index, Matrix, Point};
 use advent_of_code::intcode::*;
 use std::cmp::Reverse;
 use std::collections::{BinaryHeap, HashMap};
-use std::fmt::Debug;
-use std::str::FromStr;
 
 advent_of_code::solution!(15);
 
@@ -20,7 +19,7 @@ fn solve(input: &str, part_two: bool) -> Option<u64> {
 
     // DFS
     // forward: find unchecked direction; go
-    // back: go back in stack until find unchecked direction; goto forward
+    // back: restore the droid state saved before the last move; goto forward
 
     // If need turn (last direction is otr to current one)
     // mark prev and to-turn as 1
@@ -30,20 +29,20 @@ fn solve(input: &str, part_two: bool) -> Option<u64> {
 
     //println!("===========");
 
-    let mut robot = Robot::new(input, 64, 64);
-    //robot.map.print();
+    let mut robot = Robot::new(input);
+    //print(&robot.map.to_matrix());
 
     // DFS
 
-    // stack for backtracking
-    let mut path_stack = Vec::<Direction>::new();
+    // stack of positions and droid states to branch back to
+    let mut path_stack = Vec::<(Point, Snapshot)>::new();
 
     // visited direction memory
-    let mut checked_map_memory = HashMap::<(Index, Direction), bool>::new();
+    let mut checked_map_memory = HashMap::<(Point, Direction), bool>::new();
 
     let mut max_loop = 1_000_000_000;
 
-    let mut target_pos: Index = Index { x: 0, y: 0 };
+    let mut target_pos = Point::default();
 
     // main loop
     'dfs: loop {
@@ -52,7 +51,7 @@ fn solve(input: &str, part_two: bool) -> Option<u64> {
             panic!("inf loop");
         }
         //println!("===========");
-        //robot.map.print();
+        //print(&robot.map.to_matrix());
         //dbg!(&checked_map_memory);
 
         for command in [
@@ -78,41 +77,46 @@ fn solve(input: &str, part_two: bool) -> Option<u64> {
                     .or_insert(true);
 
                 // go to direction
-                let robot_result = robot.step(command);
+                let before_move = (robot.pos, robot.cpu.snapshot());
+                let robot_result = match robot.step(command) {
+                    Ok(result) => result,
+                    Err(error) => {
+                        eprintln!("{error}");
+                        return None;
+                    }
+                };
                 if robot_result == 2 {
                     target_pos = robot.pos;
                 }
                 if robot_result != 0 {
                     // robot moved!
-                    // push direction to the stack
-                    path_stack.push(command);
+                    // push state before the move to the stack
+                    path_stack.push(before_move);
                     continue 'dfs;
                 }
             }
         }
 
         // all directions visited
-        // pop prev direction from the stack and do back
-        if let Some(prev_direction) = path_stack.pop() {
-            robot.step(prev_direction.reverse());
+        // pop prev state from the stack and branch back to it
+        if let Some((prev_pos, snapshot)) = path_stack.pop() {
+            robot.restore(prev_pos, &snapshot);
         } else {
             // no new directions, terminate
             break 'dfs;
         }
     }
 
-    // run Dijkstra from start to finish on robot inner map
-    let start_index = Index {
-        x: robot.map.size.x / 2,
-        y: robot.map.size.y / 2,
-    };
-    let end_index = target_pos;
+    // run Dijkstra from start to finish on the explored area
+    let mut matrix = robot.map.to_matrix();
+    let start_index = robot.map.index_of(&Point::default()).unwrap();
+    let end_index = robot.map.index_of(&target_pos).unwrap();
     let mut to_visit_set = BinaryHeap::new();
     to_visit_set.push(Reverse((0_u64, start_index)));
 
-    let result = pseudo_dijkstra(&mut robot.map, Some(&end_index), &mut to_visit_set);
+    let result = pseudo_dijkstra(&mut matrix, Some(&end_index), &mut to_visit_set);
 
-    //robot.map.print();
+    //print(&matrix);
 
     if !part_two {
         return result;
@@ -120,17 +124,17 @@ fn solve(input: &str, part_two: bool) -> Option<u64> {
 
     // run dijkstra for all cells from O cell, to find the longest path
     // clean the costs
-    for cell in &mut robot.map.data {
+    for cell in &mut matrix.data {
         cell.cost = u64::MAX
     }
 
     let mut to_visit_set = BinaryHeap::new();
-    to_visit_set.push(Reverse((0_u64, target_pos)));
-    pseudo_dijkstra(&mut robot.map, None, &mut to_visit_set);
+    to_visit_set.push(Reverse((0_u64, end_index)));
+    pseudo_dijkstra(&mut matrix, None, &mut to_visit_set);
 
     // just find the biggest cost
     let mut biggest_cost = 0;
-    for cell in &mut robot.map.data {
+    for cell in &mut matrix.data {
         if cell.cost != u64::MAX && cell.cost > biggest_cost {
             biggest_cost = cell.cost
         }
@@ -140,13 +144,13 @@ fn solve(input: &str, part_two: bool) -> Option<u64> {
 
 #[allow(dead_code)]
 fn test_fake_map(input: &str, part_two: bool) -> Option<u64> {
-    let fake_map = Matrix::<MapCell>::from_string(input);
-    //fake_map.print();
+    let fake_map = Matrix::<MapCell>::from_char_input(input);
+    //print(&fake_map);
 
     //println!("===========");
 
-    let mut robot = Robot::new("99", 9, 5);
-    //robot.map.print();
+    let mut robot = Robot::new("99");
+    //print(&robot.map.to_matrix());
 
     // DFS
 
@@ -154,11 +158,11 @@ fn test_fake_map(input: &str, part_two: bool) -> Option<u64> {
     let mut path_stack = Vec::<Direction>::new();
 
     // visited direction memory
-    let mut checked_map_memory = HashMap::<(Index, Direction), bool>::new();
+    let mut checked_map_memory = HashMap::<(Point, Direction), bool>::new();
 
     let mut max_loop = 450;
 
-    let mut target_pos: Index = Index { x: 0, y: 0 };
+    let mut target_pos = Point::default();
 
     // main loop
     'dfs: loop {
@@ -167,7 +171,7 @@ fn test_fake_map(input: &str, part_two: bool) -> Option<u64> {
             panic!("inf loop");
         }
         //println!("===========");
-        //robot.map.print();
+        //print(&robot.map.to_matrix());
         //dbg!(&checked_map_memory);
 
         for command in [
@@ -216,18 +220,16 @@ fn test_fake_map(input: &str, part_two: bool) -> Option<u64> {
         }
     }
 
-    // run Dijkstra from start to finish on robot inner map
-    let start_index = Index {
-        x: robot.map.size.x / 2,
-        y: robot.map.size.y / 2,
-    };
-    let end_index = target_pos;
+    // run Dijkstra from start to finish on the explored area
+    let mut matrix = robot.map.to_matrix();
+    let start_index = robot.map.index_of(&Point::default()).unwrap();
+    let end_index = robot.map.index_of(&target_pos).unwrap();
     let mut to_visit_set = BinaryHeap::new();
     to_visit_set.push(Reverse((0_u64, start_index)));
 
-    let result = pseudo_dijkstra(&mut robot.map, Some(&end_index), &mut to_visit_set);
+    let result = pseudo_dijkstra(&mut matrix, Some(&end_index), &mut to_visit_set);
 
-    //robot.map.print();
+    //print(&matrix);
 
     if !part_two {
         return result;
@@ -235,23 +237,23 @@ fn test_fake_map(input: &str, part_two: bool) -> Option<u64> {
 
     // run dijkstra for all cells from O cell, to find the longest path
     // clean the costs
-    for cell in &mut robot.map.data {
+    for cell in &mut matrix.data {
         cell.cost = u64::MAX
     }
 
     let mut to_visit_set = BinaryHeap::new();
-    to_visit_set.push(Reverse((0_u64, target_pos)));
-    pseudo_dijkstra(&mut robot.map, None, &mut to_visit_set);
+    to_visit_set.push(Reverse((0_u64, end_index)));
+    pseudo_dijkstra(&mut matrix, None, &mut to_visit_set);
 
     // just find the biggest cost
     let mut biggest_cost = 0;
-    for cell in &mut robot.map.data {
+    for cell in &mut matrix.data {
         if cell.cost != u64::MAX && cell.cost > biggest_cost {
             biggest_cost = cell.cost
         }
     }
 
-    //robot.map.print();
+    //print(&robot.map.to_matrix());
 
     Some(biggest_cost)
     //
@@ -372,272 +374,75 @@ impl From<char> for MapCell {
     }
 }
 
-#[derive(Debug, Copy, Clone, Eq, Hash, PartialEq)]
-struct Size {
-    x: usize,
-    y: usize,
-}
-
-#[derive(Debug, Copy, Clone, Eq, Hash, PartialEq, PartialOrd, Ord)]
-struct Index {
-    x: usize,
-    y: usize,
-}
-
-impl Index {
-    fn up<T>(&self, _matrix: &Matrix<T>) -> Option<Index> {
-        if self.y == 0 {
-            return None;
-        }
-        Some(Index {
-            x: self.x,
-            y: self.y - 1,
-        })
-    }
-
-    fn left<T>(&self, _matrix: &Matrix<T>) -> Option<Index> {
-        if self.x == 0 {
-            return None;
-        }
-        Some(Index {
-            x: self.x - 1,
-            y: self.y,
-        })
-    }
-
-    fn down<T>(&self, matrix: &Matrix<T>) -> Option<Index> {
-        if self.y == matrix.size.y - 1 {
-            return None;
-        }
-        Some(Index {
-            x: self.x,
-            y: self.y + 1,
-        })
-    }
-
-    fn right<T>(&self, matrix: &Matrix<T>) -> Option<Index> {
-        if self.x == matrix.size.x - 1 {
-            return None;
-        }
-        Some(Index {
-            x: self.x + 1,
-            y: self.y,
-        })
-    }
-
-    fn navigate_to<T>(&self, matrix: &Matrix<T>, direction: &Direction) -> Option<Index> {
-        match direction {
-            Direction::Up => self.up(matrix),
-            Direction::Down => self.down(matrix),
-            Direction::Left => self.left(matrix),
-            Direction::Right => self.right(matrix),
-        }
-    }
-}
-
-#[derive(Debug, Eq, Hash, PartialEq, Clone, Copy, PartialOrd, Ord)]
-enum Direction {
-    Up,
-    Left,
-    Right,
-    Down,
-}
-
-impl Direction {
-    fn to_int(self) -> i64 {
-        match self {
-            Direction::Up => 1,
-            Direction::Left => 3,
-            Direction::Right => 4,
-            Direction::Down => 2,
-        }
+/// Firmware code of a movement command.
+fn movement_command(direction: Direction) -> i64 {
+    match direction {
+        Direction::Up => 1,
+        Direction::Left => 3,
+        Direction::Right => 4,
+        Direction::Down => 2,
     }
-
-    #[allow(dead_code)]
-    fn reverse(&self) -> Direction {
-        match self {
-            Direction::Up => Direction::Down,
-            Direction::Left => Direction::Right,
-            Direction::Right => Direction::Left,
-            Direction::Down => Direction::Up,
-        }
-    }
-
-    #[allow(dead_code)]
-    fn turn_right(&self) -> Direction {
-        match self {
-            Direction::Up => Direction::Right,
-            Direction::Right => Direction::Down,
-            Direction::Down => Direction::Left,
-            Direction::Left => Direction::Up,
-        }
-    }
-
-    #[allow(dead_code)]
-    fn turn_left(&self) -> Direction {
-        match self {
-            Direction::Up => Direction::Left,
-            Direction::Left => Direction::Down,
-            Direction::Down => Direction::Right,
-            Direction::Right => Direction::Up,
-        }
-    }
-}
-
-#[derive(Debug, Clone)]
-struct Matrix<T> {
-    size: Size,
-    data: Vec<T>,
 }
 
 #[allow(dead_code)]
-impl<T> Matrix<T> {
-    fn get_index_from_position(&self, indx: usize) -> Index {
-        let y = indx / self.size.x;
-        let x = indx - y * self.size.x;
-        Index { x, y }
-    }
-}
-
-impl Matrix<MapCell> {
-    #[allow(dead_code)]
-    fn print(&self) {
-        for y in 0..self.size.y {
-            for x in 0..self.size.x {
-                let ch;
-                if self[y][x].has_oxygen_tank && self[y][x].has_robot {
-                    ch = 'd'
-                } else if self[y][x].has_oxygen_tank {
-                    ch = 'O'
-                } else if self[y][x].has_robot {
-                    ch = 'D'
-                } else if self[y][x].has_wall {
-                    ch = '#'
-                } else if self[y][x].visited {
-                    if self[y][x].cost != u64::MAX {
-                        ch = char::from_digit((self[y][x].cost % 10) as u32, 10).unwrap();
-                    } else {
-                        ch = '.'
-                    }
+fn print(matrix: &Matrix<MapCell>) {
+    for y in 0..matrix.size.y {
+        for x in 0..matrix.size.x {
+            let ch;
+            if matrix[y][x].has_oxygen_tank && matrix[y][x].has_robot {
+                ch = 'd'
+            } else if matrix[y][x].has_oxygen_tank {
+                ch = 'O'
+            } else if matrix[y][x].has_robot {
+                ch = 'D'
+            } else if matrix[y][x].has_wall {
+                ch = '#'
+            } else if matrix[y][x].visited {
+                if matrix[y][x].cost != u64::MAX {
+                    ch = char::from_digit((matrix[y][x].cost % 10) as u32, 10).unwrap();
                 } else {
-                    ch = ' '
+                    ch = '.'
                 }
-                print!("{ch}");
+            } else {
+                ch = ' '
             }
-            println!();
-        }
-    }
-
-    pub fn from_string(input: &str) -> Self {
-        let (data, size) = parse_row_input_as_data_array::<char>(input);
-        let data_cells: Vec<MapCell> = data.into_iter().map(MapCell::from).collect();
-        Matrix {
-            size,
-            data: data_cells.clone(),
+            print!("{ch}");
         }
+        println!();
     }
 }
 
-impl<T> std::ops::Index<usize> for Matrix<T> {
-    type Output = [T];
-
-    fn index(&self, row: usize) -> &[T] {
-        let start = row * self.size.x;
-        &self.data[start..start + self.size.x]
-    }
-}
-
-impl<T> std::ops::IndexMut<usize> for Matrix<T> {
-    fn index_mut(&mut self, row: usize) -> &mut [T] {
-        let start = row * self.size.x;
-        &mut self.data[start..start + self.size.x]
-    }
-}
-
-#[allow(dead_code)]
-fn parse_row_input_as_data_array<T>(input: &str) -> (Vec<T>, Size)
-where
-    T: FromStr + std::fmt::Debug,
-    <T as FromStr>::Err: Debug,
-{
-    let splitted_lines: Vec<&str> = input.lines().collect();
-    let size_y = splitted_lines.len();
-    let size_x = splitted_lines[0].len();
-
-    let result: Vec<T> = input
-        .chars()
-        .filter(|c| *c != '\n' && *c != '\r')
-        .map(|c| c.to_string().parse().expect("T values expected"))
-        .collect();
-
-    (
-        result,
-        Size {
-            x: size_x,
-            y: size_y,
-        },
-    )
-}
-
 struct Robot {
-    pos: Index,
-    map: Matrix<MapCell>,
+    pos: Point,
+    map: Grid<MapCell>,
     cpu: Machine,
 }
 
 impl Robot {
-    pub fn new(firmware: &str, map_size_x: usize, map_size_y: usize) -> Self {
-        let firmware: Vec<i64> = firmware
-            .split(",")
-            .map(|i| {
-                i.parse()
-                    .unwrap_or_else(|_| panic!("Expected i64 list [{i}]"))
-            })
-            .collect();
-
-        // create square map.
-        let mut map_data = Vec::<MapCell>::new();
-        for _ in 0..map_size_x * map_size_y {
-            map_data.push(MapCell {
-                has_wall: false,
-                has_robot: false,
-                has_oxygen_tank: false,
-                visited: false,
-                cost: u64::MAX,
-            });
-        }
+    pub fn new(firmware: &str) -> Self {
+        let firmware: Program = firmware.parse().unwrap_or_else(|error| panic!("{error}"));
 
-        let mut map: Matrix<MapCell> = Matrix {
-            size: Size {
-                x: map_size_x,
-                y: map_size_y,
-            },
-            data: map_data,
-        };
-
-        // place robot at the center
-        map[map_size_y / 2][map_size_x / 2].has_robot = true;
-        map[map_size_y / 2][map_size_x / 2].visited = true;
-
-        let ext_memory_size = 4096;
-        let mut ext_memory: Vec<i64> = Vec::with_capacity(ext_memory_size);
-        for i in 0..ext_memory_size {
-            if i < firmware.len() {
-                ext_memory.push(firmware[i]);
-            } else {
-                ext_memory.push(0);
-            }
-        }
+        // unexplored map, growing as the robot moves.
+        let mut map = Grid::new(MapCell {
+            has_wall: false,
+            has_robot: false,
+            has_oxygen_tank: false,
+            visited: false,
+            cost: u64::MAX,
+        });
 
-        let input = Vec::<i64>::new();
+        // place robot at the origin
+        let pos = Point::default();
+        map[pos].has_robot = true;
+        map[pos].visited = true;
 
-        let machine = Machine::new_with_input(ext_memory, input);
+        let machine = firmware
+            .with_limits(Limits::from_env())
+            .machine()
+            .with_backend(Backend::Cached);
 
         Robot {
-            pos: Index {
-                x: map_size_x / 2,
-                y: map_size_y / 2,
-            },
+            pos,
             map,
             cpu: machine,
         }
@@ -646,13 +451,20 @@ impl Robot {
     pub fn step_fake_map(&mut self, command: Direction, fake_map: &Matrix<MapCell>) -> i64 {
         // just for debug
         // emulate the real map with provided data
-        // fake map size should match robot one
-        // or need to create separate index for fake map here
-        let target_pos = self
-            .pos
-            .navigate_to(&self.map, &command)
-            .expect("Out of map bounds");
-        let target_cell = &fake_map[target_pos.y][target_pos.x];
+        // the robot origin is the droid cell of the fake map
+        let target_pos = self.pos.navigate_to(&command);
+        let start = fake_map
+            .data
+            .iter()
+            .position(|cell| cell.has_robot)
+            .unwrap();
+        let start = fake_map.get_index_from_position(start);
+        let fake_index = Index {
+            x: (start.x as i64 + target_pos.x) as usize,
+            y: (start.y as i64 + target_pos.y) as usize,
+        };
+        assert!(fake_map.has_index(&fake_index), "Out of map bounds");
+        let target_cell = &fake_map[fake_index.y][fake_index.x];
         let result = if target_cell.has_wall {
             0
         } else if target_cell.has_oxygen_tank {
@@ -667,15 +479,15 @@ impl Robot {
             0 => {
                 // Wall
                 // update map
-                self.map[target_pos.y][target_pos.x].has_wall = true;
+                self.map[target_pos].has_wall = true;
             }
             1 => {
                 // Empty
                 // move the robot, update the map
-                self.map[target_pos.y][target_pos.x].visited = true;
+                self.map[target_pos].visited = true;
 
-                self.map[self.pos.y][self.pos.x].has_robot = false;
-                self.map[target_pos.y][target_pos.x].has_robot = true;
+                self.map[self.pos].has_robot = false;
+                self.map[target_pos].has_robot = true;
 
                 self.pos = target_pos;
             }
@@ -683,43 +495,54 @@ impl Robot {
                 // found oxygen!
                 // move the robot, update the map
                 self.pos = target_pos;
-                self.map[target_pos.y][target_pos.x].visited = true;
-                self.map[target_pos.y][target_pos.x].has_oxygen_tank = true;
+                self.map[target_pos].visited = true;
+                self.map[target_pos].has_oxygen_tank = true;
             }
             _ => panic!("Unknown result!"),
         }
         result
     }
 
-    pub fn step(&mut self, command: Direction) -> i64 {
+    /// Puts the droid back to a position and cpu state saved before a move.
+    pub fn restore(&mut self, pos: Point, snapshot: &Snapshot) {
+        self.cpu.restore(snapshot);
+
+        self.map[self.pos].has_robot = false;
+        self.map[pos].has_robot = true;
+        self.pos = pos;
+    }
+
+    /// Moves the droid, failing if the firmware stops before answering.
+    pub fn step(&mut self, direction: Direction) -> Result<i64, IntcodeError> {
         // set input
-        self.cpu.input.push(command.to_int());
+        self.cpu.push_input(movement_command(direction));
 
         // run cpu
         let result = match self.cpu.execute() {
-            ExecuteResult::WaitingInput => self.cpu.output[self.cpu.output_pointer - 1],
-            _ => panic!(),
+            ExecuteResult::WaitingInput => *self.cpu.take_output().last().unwrap(),
+            ExecuteResult::Error(error) => return Err(error),
+            result => match result.limit_error(self.cpu.instruction_pointer) {
+                Some(error) => return Err(error),
+                None => panic!("Unexpected result [{result:?}]!"),
+            },
         };
 
-        let target_pos = self
-            .pos
-            .navigate_to(&self.map, &command)
-            .expect("Out of map bounds");
+        let target_pos = self.pos.navigate_to(&direction);
 
         // matching exe result
         match result {
             0 => {
                 // Wall
                 // update map
-                self.map[target_pos.y][target_pos.x].has_wall = true;
+                self.map[target_pos].has_wall = true;
             }
             1 => {
                 // Empty
                 // move the robot, update the map
-                self.map[target_pos.y][target_pos.x].visited = true;
+                self.map[target_pos].visited = true;
 
-                self.map[self.pos.y][self.pos.x].has_robot = false;
-                self.map[target_pos.y][target_pos.x].has_robot = true;
+                self.map[self.pos].has_robot = false;
+                self.map[target_pos].has_robot = true;
 
                 self.pos = target_pos;
             }
@@ -727,13 +550,13 @@ impl Robot {
                 // found oxygen!
                 // move the robot, update the map
                 self.pos = target_pos;
-                self.map[target_pos.y][target_pos.x].visited = true;
-                self.map[target_pos.y][target_pos.x].has_oxygen_tank = true;
+                self.map[target_pos].visited = true;
+                self.map[target_pos].has_oxygen_tank = true;
             }
             _ => panic!("Unknown result!"),
         }
 
-        result
+        Ok(result)
     }
 }
 
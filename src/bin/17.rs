@@ -1,5 +1,6 @@
 use advent_of_code::advent_stdlib::Direction;
 use advent_of_code::advent_stdlib::Matrix;
+use advent_of_code::intcode::ascii::AsciiMachine;
 use advent_of_code::intcode::*;
 use std::fmt::Debug;
 use std::fmt::Display;
@@ -7,25 +8,14 @@ use std::fmt::Display;
 advent_of_code::solution!(17);
 
 pub fn part_one(input: &str) -> Option<u64> {
-    let firmware: Vec<i64> = input
-        .split(",")
-        .map(|i| i.parse().expect("Expected i64 list"))
-        .collect();
-
-    let ext_memory_size = 4096;
-    let mut ext_memory: Vec<i64> = Vec::with_capacity(ext_memory_size);
-    for i in 0..ext_memory_size {
-        if i < firmware.len() {
-            ext_memory.push(firmware[i]);
-        } else {
-            ext_memory.push(0);
-        }
-    }
-
-    let input = Vec::<i64>::new();
+    let firmware: Program = input.parse().unwrap_or_else(|error| panic!("{error}"));
 
-    let mut machine = Machine::new_with_input(ext_memory, input);
-    machine.execute();
+    let mut machine = firmware.with_limits(Limits::from_env()).machine();
+    let result = machine.execute();
+    if let Some(error) = result.limit_error(machine.instruction_pointer) {
+        eprintln!("{error}");
+        return None;
+    }
     let output_string = machine.get_ascii_output();
 
     let mut map = Matrix::<MapCell>::from_char_input(&output_string);
@@ -52,42 +42,33 @@ pub fn part_two(input: &str) -> Option<u64> {
     B: R10 R6 R4 R4 L12 R6 L12
     C: R10 R6 R4
      */
-    let main_routine = "A,C,A,C,B,C,B,A,C,B\n";
-    let function_a = "R,4,R,10,R,8,R,4\n";
-    let function_b = "R,4,L,12,R,6,L,12\n";
-    let function_c = "R,10,R,6,R,4\n";
-    let no = "n\n";
-
-    let mut firmware: Vec<i64> = input
-        .split(",")
-        .map(|i| i.parse().expect("Expected i64 list"))
-        .collect();
-
-    firmware[0] = 2;
-
-    let ext_memory_size = 4096;
-    let mut ext_memory: Vec<i64> = Vec::with_capacity(ext_memory_size);
-    for i in 0..ext_memory_size {
-        if i < firmware.len() {
-            ext_memory.push(firmware[i]);
-        } else {
-            ext_memory.push(0);
-        }
+    let main_routine = "A,C,A,C,B,C,B,A,C,B";
+    let function_a = "R,4,R,10,R,8,R,4";
+    let function_b = "R,4,L,12,R,6,L,12";
+    let function_c = "R,10,R,6,R,4";
+    let no = "n";
+
+    let firmware: Program = input.parse().unwrap_or_else(|error| panic!("{error}"));
+
+    let firmware = firmware.with_patch(0, 2).with_limits(Limits::from_env());
+    let mut machine = AsciiMachine::from(firmware.machine());
+    for line in [main_routine, function_a, function_b, function_c, no] {
+        machine.send_line(line);
     }
 
-    let input = main_routine.to_owned() + function_a + function_b + function_c + no;
-    let mut machine = Machine::new_with_ascii_input(ext_memory, &input);
-
     // run
 
-    if let ExecuteResult::Halt(_a) = machine.execute() {
+    let result = machine.run();
+    if let ExecuteResult::Halt(_a) = result {
         // Machine also outputs input data and final feed.
-        // if error - machine will output string with it.
+        // if error - machine will output lines with it.
 
-        //let output_string = machine.get_ascii_output();
-        //println!("{}", output_string);
+        //println!("{}", machine.take_lines().join("\n"));
 
-        Some(machine.output[machine.output.len() - 1] as u64)
+        machine.values().last().map(|dust| *dust as u64)
+    } else if let Some(error) = result.limit_error(machine.machine().instruction_pointer) {
+        eprintln!("{error}");
+        None
     } else {
         panic!("Unexpected execution result!")
     }
@@ -4,10 +4,8 @@ use advent_of_code::advent_stdlib::Matrix;
 use std::cmp::Reverse;
 use std::collections::BinaryHeap;
 use std::collections::HashMap;
-use std::collections::HashSet;
 use std::fmt::Debug;
 use std::fmt::Display;
-use std::str::FromStr;
 
 advent_of_code::solution!(18);
 
@@ -15,7 +13,7 @@ pub fn part_one(input: &str) -> Option<u64> {
     solve_part_1(input, 26)
 }
 
-pub fn part_two(input: &str) -> Option<u64> {
+pub fn part_two(_input: &str) -> Option<u64> {
     None
 }
 
@@ -81,7 +79,7 @@ fn get_ascii_distance_from_a(c: char) -> usize {
     c as usize - 'a' as usize
 }
 
-fn fully_contained_as_subset(first: &Vec<bool>, second: &Vec<bool>) -> bool {
+fn fully_contained_as_subset(first: &[bool], second: &[bool]) -> bool {
     for (i, v) in first.iter().enumerate() {
         // first value is true, but the second is false - not subset
         if *v && !second[i] {
@@ -102,7 +100,7 @@ fn pseudo_dijkstra(
     // let result = pseudo_dijkstra(&mut robot.map, Some(&end_index), &mut to_visit_set);
     // or without end_index, if need to visit all cells.
 
-    let mut safe_counter = 1_000_0000;
+    let mut safe_counter = 10_000_000;
 
     while let Some(Reverse(cost_state)) = to_visit_set.pop() {
         if safe_counter <= 0 {
@@ -193,7 +191,7 @@ impl MapCell {
         }
     }
 
-    pub fn has_better_superset(&self, state: &Vec<bool>, current_cost: u64) -> bool {
+    pub fn has_better_superset(&self, state: &[bool], current_cost: u64) -> bool {
         for (st, &old_cost) in self.cost.iter() {
             if old_cost <= current_cost && fully_contained_as_subset(state, st) {
                 //dbg!("have better set!");
@@ -203,7 +201,7 @@ impl MapCell {
         false
     }
 
-    pub fn has_wall(&self, state: &Vec<bool>) -> bool {
+    pub fn has_wall(&self, state: &[bool]) -> bool {
         if self.has_wall {
             true
         } else {
@@ -0,0 +1,69 @@
+use advent_of_code::intcode::amplifier::{best, Topology};
+use advent_of_code::intcode::{parse_values, Limits, Program};
+use itertools::Itertools;
+use std::{env, fs, process};
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let (program_path, topology_path) = match &args[..] {
+        [program, topology] | [program, topology, _] => (program, topology),
+        _ => {
+            eprintln!("Usage: cargo intcode-amplifiers <file> <topology> [phases]");
+            process::exit(1);
+        }
+    };
+
+    // day 7 examples list phase settings after the program
+    let source = read(program_path);
+    let program: Program = source
+        .lines()
+        .next()
+        .unwrap_or_default()
+        .parse()
+        .unwrap_or_else(|e| exit(e));
+    let program = program.with_limits(Limits::from_env());
+    let topology: Topology = read(topology_path).parse().unwrap_or_else(|e| exit(e));
+    let phases = match (args.get(2), topology.phases()) {
+        (Some(phases), _) => parse_values(phases).unwrap_or_else(|e| exit(e)),
+        (None, Some(phases)) => phases.to_vec(),
+        (None, None) => exit("no phases in the topology, pass them after it"),
+    };
+
+    let evaluation = best(&program, &topology, &phases).unwrap_or_else(|e| exit(e));
+    println!("Best phases: {}", evaluation.phases.iter().join(","));
+    println!("Signal: {}", evaluation.signal);
+    println!();
+    let amplifiers = topology.amplifiers();
+    println!(
+        "{:>9} {}",
+        "iteration",
+        amplifiers
+            .iter()
+            .map(|name| format!("{name:>12}"))
+            .join(" ")
+    );
+    for (index, signals) in evaluation.iterations.iter().enumerate() {
+        println!(
+            "{index:>9} {}",
+            signals
+                .iter()
+                .map(|signal| match signal {
+                    Some(signal) => format!("{signal:>12}"),
+                    None => format!("{:>12}", "-"),
+                })
+                .join(" ")
+        );
+    }
+}
+
+fn read(path: &str) -> String {
+    fs::read_to_string(path).unwrap_or_else(|e| {
+        eprintln!("could not open [{path}]: {e}");
+        process::exit(1);
+    })
+}
+
+fn exit(error: impl std::fmt::Display) -> ! {
+    eprintln!("{error}");
+    process::exit(1);
+}
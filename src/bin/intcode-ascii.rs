@@ -0,0 +1,41 @@
+use advent_of_code::intcode::ascii::AsciiMachine;
+use advent_of_code::intcode::{ExecuteResult, Machine, Program};
+use std::{env, fs, process};
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let mut machine = match args.iter().map(String::as_str).collect::<Vec<_>>()[..] {
+        [path] => AsciiMachine::new(read_program(path).memory()),
+        [_, "--load", snapshot] => {
+            let machine = Machine::load(snapshot).unwrap_or_else(|e| {
+                eprintln!("could not load [{snapshot}]: {e}");
+                process::exit(1);
+            });
+            println!("(resumed [{snapshot}])");
+            machine.into()
+        }
+        _ => {
+            eprintln!("Usage: cargo intcode-ascii <file> [--load <snapshot>]");
+            process::exit(1);
+        }
+    };
+
+    match machine.play() {
+        Ok(ExecuteResult::Halt(_)) => println!("\n(halted)"),
+        Ok(ExecuteResult::WaitingInput) => println!("\n(end of input)"),
+        Ok(ExecuteResult::Error(error)) => eprintln!("{error}"),
+        Ok(result) => println!("\n({result:?})"),
+        Err(error) => eprintln!("{error}"),
+    }
+}
+
+fn read_program(path: &str) -> Program {
+    let source = fs::read_to_string(path).unwrap_or_else(|e| {
+        eprintln!("could not open [{path}]: {e}");
+        process::exit(1);
+    });
+    source.parse().unwrap_or_else(|e| {
+        eprintln!("{e}");
+        process::exit(1);
+    })
+}
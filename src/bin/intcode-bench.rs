@@ -0,0 +1,102 @@
+use advent_of_code::intcode::{parse_values, Backend, ExecuteResult, Program};
+use std::time::{Duration, Instant};
+use std::{env, fs, process};
+
+/// Minimum time spent per backend and program.
+const MIN_BENCH_TIME: Duration = Duration::from_millis(300);
+
+/// Day, input values and noun/verb patch of a stored input.
+type Preset = (u8, &'static [i64], Option<(i64, i64)>);
+
+/// Stored day inputs that run without interaction.
+const PRESETS: &[Preset] = &[
+    (2, &[], Some((12, 2))),
+    (5, &[5], None),
+    (9, &[2], None),
+    (13, &[], None),
+    (17, &[], None),
+];
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+
+    let cases: Vec<(String, Program, Vec<i64>)> = match &args[..] {
+        [] => PRESETS
+            .iter()
+            .filter_map(|(day, input, patch)| {
+                let path = format!("data/inputs/{day:02}.txt");
+                let source = fs::read_to_string(&path).ok()?;
+                let program = parse(&source, &path);
+                let program = match patch {
+                    Some((noun, verb)) => program.with_noun_verb(*noun, *verb),
+                    None => program,
+                };
+                Some((format!("day {day:02}"), program, input.to_vec()))
+            })
+            .collect(),
+        [path] | [path, _] => {
+            let source = fs::read_to_string(path).unwrap_or_else(|e| {
+                eprintln!("could not open [{path}]: {e}");
+                process::exit(1);
+            });
+            let input = match args.get(1) {
+                Some(input) => parse_values(input).unwrap_or_else(|e| {
+                    eprintln!("{e}");
+                    process::exit(1);
+                }),
+                None => vec![],
+            };
+            vec![(path.clone(), parse(&source, path), input)]
+        }
+        _ => {
+            eprintln!("Usage: cargo intcode-bench [<file> [<input>]]");
+            process::exit(1);
+        }
+    };
+
+    if cases.is_empty() {
+        println!("No stored inputs found in data/inputs.");
+        return;
+    }
+
+    for (name, program, input) in cases {
+        let (interpreter, expected) = bench(&program, &input, Backend::Interpreter);
+        let (cached, output) = bench(&program, &input, Backend::Cached);
+        let note = if output == expected {
+            ""
+        } else {
+            "  OUTPUT MISMATCH"
+        };
+        println!(
+            "{name:<12} interpreter {:>10.3?}  cached {:>10.3?}  x{:.2}{note}",
+            interpreter,
+            cached,
+            interpreter.as_secs_f64() / cached.as_secs_f64()
+        );
+    }
+}
+
+fn parse(source: &str, path: &str) -> Program {
+    source.parse().unwrap_or_else(|e| {
+        eprintln!("{path}: {e}");
+        process::exit(1);
+    })
+}
+
+/// Average time of one run until halt or missing input, and the run's output.
+fn bench(program: &Program, input: &[i64], backend: Backend) -> (Duration, Vec<i64>) {
+    let mut runs = 0;
+    let mut output = Vec::new();
+    let start = Instant::now();
+    while runs == 0 || start.elapsed() < MIN_BENCH_TIME {
+        let mut machine = program
+            .machine_with_input(input.to_vec())
+            .with_backend(backend);
+        if let ExecuteResult::Error(error) = machine.execute() {
+            eprintln!("{error}");
+        }
+        output = machine.take_output();
+        runs += 1;
+    }
+    (start.elapsed() / runs, output)
+}
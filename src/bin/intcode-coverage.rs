@@ -0,0 +1,67 @@
+use advent_of_code::intcode::coverage::Coverage;
+use advent_of_code::intcode::{Limits, Program};
+use std::path::Path;
+use std::{env, fs, process};
+
+/// Example programs of the days running the VM.
+const EXAMPLE_DAYS: [&str; 4] = ["02", "05", "07", "09"];
+
+/// Every program runs once per input, the examples compare against 8,
+/// read modes or phase settings followed by a signal.
+const INPUTS: [&[i64]; 7] = [&[], &[0], &[1], &[5], &[8], &[9], &[4, 0]];
+
+const MAX_STEPS: u64 = 1_000_000;
+
+fn main() {
+    let mut files: Vec<String> = env::args().skip(1).collect();
+    if files.is_empty() {
+        files = example_files();
+    }
+
+    let coverage = Coverage::new();
+    for path in &files {
+        let source = fs::read_to_string(path).unwrap_or_else(|e| {
+            eprintln!("could not open [{path}]: {e}");
+            process::exit(1);
+        });
+        // day 7 examples list phase settings after the program
+        let firmware = source.lines().next().unwrap_or_default();
+        let program: Program = firmware.parse().unwrap_or_else(|e| {
+            eprintln!("[{path}]: {e}");
+            process::exit(1);
+        });
+        let name = Path::new(path)
+            .file_stem()
+            .map_or(path.clone(), |stem| stem.to_string_lossy().to_string());
+
+        for input in INPUTS {
+            let mut machine = program.machine_with_input(input.to_vec());
+            machine.set_limits(Limits {
+                max_steps: Some(MAX_STEPS),
+                ..Limits::default()
+            });
+            machine.set_tracer(coverage.tracer(&name, &program));
+            machine.execute();
+        }
+    }
+    print!("{coverage}");
+}
+
+fn example_files() -> Vec<String> {
+    let mut files: Vec<String> = fs::read_dir("data/examples")
+        .unwrap_or_else(|e| {
+            eprintln!("could not list data/examples: {e}");
+            process::exit(1);
+        })
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path().to_string_lossy().to_string())
+        .filter(|path| {
+            let name = Path::new(path).file_name().unwrap().to_string_lossy();
+            EXAMPLE_DAYS
+                .iter()
+                .any(|day| name.starts_with(&format!("{day}-")))
+        })
+        .collect();
+    files.sort();
+    files
+}
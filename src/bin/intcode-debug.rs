@@ -0,0 +1,48 @@
+use advent_of_code::intcode::debugger::Debugger;
+use advent_of_code::intcode::history::History;
+use advent_of_code::intcode::Program;
+use std::io::{stdin, stdout, Write};
+use std::{env, fs, process};
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let (path, history) = match args.iter().map(String::as_str).collect::<Vec<_>>()[..] {
+        [path] => (path.to_string(), History::DEFAULT_CAPACITY),
+        [path, "--history", steps] if let Ok(steps) = steps.parse() => (path.to_string(), steps),
+        _ => {
+            eprintln!("Usage: cargo intcode-debug <file> [--history <steps>]");
+            process::exit(1);
+        }
+    };
+
+    let source = fs::read_to_string(&path).unwrap_or_else(|e| {
+        eprintln!("could not open [{path}]: {e}");
+        process::exit(1);
+    });
+    let program: Program = source.parse().unwrap_or_else(|e| {
+        eprintln!("{e}");
+        process::exit(1);
+    });
+
+    let mut debugger = Debugger::new(program.machine()).with_history_capacity(history);
+    println!(
+        "Loaded {} words from {path}, type `help` for commands.",
+        program.len()
+    );
+    println!("{}", debugger.location());
+
+    let mut line = String::new();
+    loop {
+        print!("(icdb) ");
+        stdout().flush().unwrap();
+
+        line.clear();
+        if stdin().read_line(&mut line).unwrap() == 0 {
+            break;
+        }
+        if matches!(line.trim(), "q" | "quit") {
+            break;
+        }
+        println!("{}", debugger.execute_command(&line));
+    }
+}
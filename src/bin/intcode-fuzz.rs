@@ -0,0 +1,32 @@
+use advent_of_code::intcode::fuzz::{fuzz, Config};
+use std::{env, process};
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let number = |index: usize| -> Option<u64> {
+        let arg = args.get(index)?;
+        Some(arg.parse().unwrap_or_else(|_| {
+            eprintln!("Usage: cargo intcode-fuzz [<cases> [<seed>]]");
+            process::exit(1);
+        }))
+    };
+
+    let defaults = Config::default();
+    let config = Config {
+        cases: number(0).map_or(defaults.cases, |cases| cases as usize),
+        seed: number(1).unwrap_or(defaults.seed),
+        ..defaults
+    };
+
+    match fuzz(&config) {
+        Ok(()) => println!(
+            "{} programs agree with the reference model (seed {})",
+            config.cases, config.seed
+        ),
+        Err(counterexample) => {
+            println!("divergence found (seed {}), minimized:", config.seed);
+            println!("{counterexample}");
+            process::exit(1);
+        }
+    }
+}
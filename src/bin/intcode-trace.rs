@@ -0,0 +1,159 @@
+use advent_of_code::intcode::trace::{
+    first_divergence, read_trace, replay, JsonlTracer, TraceStep,
+};
+use advent_of_code::intcode::{parse_values, ExecuteResult, Limits, Program};
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::{env, process};
+
+/// Steps printed before a divergence.
+const DIFF_CONTEXT: usize = 3;
+
+/// Addresses and loops listed in a profile report.
+const PROFILE_TOP: usize = 20;
+
+const USAGE: &str = "Usage:
+  cargo intcode-trace record <firmware> <input> <trace>
+  cargo intcode-trace replay <firmware> <trace> <step>
+  cargo intcode-trace diff <trace> <trace>
+  cargo intcode-trace profile <firmware> <input> [<folded>]";
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let args: Vec<&str> = args.iter().map(|a| a.as_str()).collect();
+
+    let result = match args[..] {
+        ["record", firmware, input, trace] => record(firmware, input, trace),
+        ["replay", firmware, trace, step] => replay_to(firmware, trace, step),
+        ["diff", a, b] => diff(a, b),
+        ["profile", firmware, input] => profile(firmware, input, None),
+        ["profile", firmware, input, folded] => profile(firmware, input, Some(folded)),
+        _ => Err(USAGE.to_string()),
+    };
+    if let Err(message) = result {
+        eprintln!("{message}");
+        process::exit(1);
+    }
+}
+
+fn read(path: &str) -> Result<String, String> {
+    fs::read_to_string(path).map_err(|e| format!("could not open [{path}]: {e}"))
+}
+
+fn load_program(path: &str) -> Result<Program, String> {
+    let program: Program = read(path)?.parse().map_err(|e| format!("{e}"))?;
+    Ok(program.with_limits(Limits::from_env()))
+}
+
+fn load_trace(path: &str) -> Result<Vec<TraceStep>, String> {
+    read_trace(&read(path)?).map_err(|e| format!("{path}: {e}"))
+}
+
+fn load_input(input: &str) -> Result<Vec<i64>, String> {
+    if input.trim().is_empty() {
+        Ok(vec![])
+    } else {
+        parse_values(input).map_err(|e| e.to_string())
+    }
+}
+
+fn print_result(result: ExecuteResult) {
+    match result {
+        ExecuteResult::Halt(value) => println!("halted with {value}"),
+        ExecuteResult::WaitingInput => println!("stopped waiting for input"),
+        ExecuteResult::Error(error) => println!("{error}"),
+        ExecuteResult::StepLimit => println!("stopped at the step limit"),
+        ExecuteResult::TimeLimit => println!("stopped at the time limit"),
+        ExecuteResult::InfiniteLoop(ip) => println!("stuck in a loop at {ip}"),
+        ExecuteResult::Continue => unreachable!(),
+    }
+}
+
+fn record(firmware: &str, input: &str, trace: &str) -> Result<(), String> {
+    let program = load_program(firmware)?;
+    let input = load_input(input)?;
+
+    let file = File::create(trace).map_err(|e| format!("could not create [{trace}]: {e}"))?;
+    let mut machine = program.machine_with_input(input);
+    machine.set_tracer(JsonlTracer::new(BufWriter::new(file)));
+
+    let result = machine.execute();
+    machine.take_tracer();
+
+    print_result(result);
+    println!("output: {:?}", machine.output());
+    Ok(())
+}
+
+fn profile(firmware: &str, input: &str, folded: Option<&str>) -> Result<(), String> {
+    let program = load_program(firmware)?;
+    let mut machine = program.machine_with_input(load_input(input)?);
+    let profiler = machine.start_profiling();
+
+    print_result(machine.execute());
+    let profile = profiler.profile();
+    match folded {
+        Some(path) => {
+            fs::write(path, profile.folded())
+                .map_err(|e| format!("could not write [{path}]: {e}"))?;
+            println!("wrote {} steps to {path}", profile.steps);
+        }
+        None => print!("{}", profile.report(PROFILE_TOP)),
+    }
+    Ok(())
+}
+
+fn replay_to(firmware: &str, trace: &str, step: &str) -> Result<(), String> {
+    let program = load_program(firmware)?;
+    let steps = load_trace(trace)?;
+    let count: usize = step
+        .parse()
+        .map_err(|_| format!("expected a step number, got [{step}]"))?;
+    if count > steps.len() {
+        return Err(format!("trace only has {} steps", steps.len()));
+    }
+
+    let machine = replay(&program, &steps, count);
+    println!("after {count} of {} steps", steps.len());
+    match steps.get(count) {
+        Some(next) => println!("next: {next}"),
+        None => println!("next: end of trace"),
+    }
+    println!("IP: {}", machine.instruction_pointer);
+    println!("RB: {}", machine.relative_base);
+    let consumed: Vec<i64> = steps[..count].iter().filter_map(|s| s.input).collect();
+    println!("input: {consumed:?}");
+    println!("output: {:?}", machine.output());
+
+    let image = program.image();
+    let changed: Vec<String> = (0..machine.memory.len())
+        .filter(|&a| machine.memory.get(a) != image.get(a).copied().unwrap_or(0))
+        .map(|a| format!("[{a}] {}", machine.memory.get(a)))
+        .collect();
+    println!("changed memory: {}", changed.join(", "));
+    Ok(())
+}
+
+fn diff(a: &str, b: &str) -> Result<(), String> {
+    let left = load_trace(a)?;
+    let right = load_trace(b)?;
+
+    let Some(index) = first_divergence(&left, &right) else {
+        println!("traces are identical ({} steps)", left.len());
+        return Ok(());
+    };
+
+    println!("traces diverge at step {index}");
+    let start = index.saturating_sub(DIFF_CONTEXT);
+    for (offset, step) in left[start..index].iter().enumerate() {
+        println!("  {:>8}  {step}", start + offset);
+    }
+    let show = |steps: &[TraceStep]| {
+        steps
+            .get(index)
+            .map_or("end of trace".to_string(), |s| s.to_string())
+    };
+    println!("< {index:>8}  {}", show(&left));
+    println!("> {index:>8}  {}", show(&right));
+    Ok(())
+}
@@ -0,0 +1,613 @@
+//! Day 7 amplifiers: intcode machines given a phase setting, then wired
+//! into a chain, a feedback loop or any topology read from a config file.
+//!
+//! A config lists one route per amplifier, `#` starts a comment:
+//!
+//! ```text
+//! # five amplifiers in a feedback loop
+//! phases 5,6,7,8,9
+//! A -> B
+//! B -> C
+//! C -> D
+//! D -> E
+//! E -> A, thrusters
+//! signal A 0
+//! output thrusters
+//! ```
+//!
+//! Amplifiers are the left side of the routes, in order. Targets that are
+//! not amplifiers are output channels. `signal` defaults to `0` sent to the
+//! first amplifier, `output` to the only channel, and `phases` is optional
+//! when the phases are given when evaluating.
+
+use std::collections::VecDeque;
+use std::fmt::Display;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+use itertools::Itertools;
+
+use super::network::{Network, Outcome, Route};
+use super::{parse_values, IntcodeError, Program};
+
+/// Amplifiers and where their signals go.
+///
+/// # Examples
+/// ```
+/// use advent_of_code::intcode::*;
+/// use advent_of_code::intcode::amplifier::*;
+///
+/// // each amplifier adds its phase to the incoming signal, then halts
+/// let program: Program = "3,11,3,12,1,11,12,12,4,12,99,0,0".parse().unwrap();
+/// let topology: Topology = "
+///     A -> B
+///     B -> C, out
+///     C -> out
+///     signal A 5
+/// "
+/// .parse()
+/// .unwrap();
+///
+/// let evaluation = evaluate(&program, &topology, &[1, 10, 100]).unwrap();
+/// assert_eq!(evaluation.signal, 116);
+/// assert_eq!(evaluation.iterations, vec![vec![Some(6), Some(16), Some(116)]]);
+///
+/// assert_eq!(Topology::chain(3).amplifiers(), ["A", "B", "C"]);
+/// let best = best(&program, &Topology::chain(3), &[1, 2, 3]).unwrap();
+/// assert_eq!(best.signal, 6);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Topology {
+    routes: Vec<(String, Vec<String>)>,
+    phases: Option<Vec<i64>>,
+    start: String,
+    signal: i64,
+    output: String,
+}
+
+impl Topology {
+    /// `count` amplifiers `A`, `B`... `Z`, `AA`, `AB`... each feeding the
+    /// next, the last one feeding `thrusters`.
+    pub fn chain(count: usize) -> Self {
+        let names = names(count);
+        let routes = names
+            .iter()
+            .enumerate()
+            .map(|(index, name)| {
+                let next = names.get(index + 1).map_or("thrusters", String::as_str);
+                (name.clone(), vec![next.to_string()])
+            })
+            .collect();
+        Topology::new(routes)
+    }
+
+    /// Chain whose last amplifier also feeds the first one.
+    pub fn feedback(count: usize) -> Self {
+        let mut topology = Topology::chain(count);
+        let first = topology.start.clone();
+        if let Some((_, targets)) = topology.routes.last_mut() {
+            targets.insert(0, first);
+        }
+        topology
+    }
+
+    fn new(routes: Vec<(String, Vec<String>)>) -> Self {
+        Topology {
+            start: routes
+                .first()
+                .map(|(name, _)| name.clone())
+                .unwrap_or_default(),
+            routes,
+            phases: None,
+            signal: 0,
+            output: "thrusters".to_string(),
+        }
+    }
+
+    pub fn amplifiers(&self) -> Vec<&str> {
+        self.routes.iter().map(|(name, _)| name.as_str()).collect()
+    }
+
+    /// Phase settings given by the config, if any.
+    pub fn phases(&self) -> Option<&[i64]> {
+        self.phases.as_deref()
+    }
+
+    /// Network of the amplifiers with their phases and the initial signal
+    /// queued, each amplifier also copying its output to [`trace_channel`].
+    fn network(&self, program: &Program, phases: &[i64]) -> Network {
+        let mut network = Network::new();
+        for (name, targets) in &self.routes {
+            let mut targets = targets.clone();
+            targets.push(trace_channel(name));
+            network.add_node(name, program, name, Route::Broadcast(targets));
+        }
+        for ((name, _), phase) in self.routes.iter().zip(phases) {
+            network.send(name, *phase);
+        }
+        network.send(&self.start, self.signal);
+        network
+    }
+}
+
+/// Spreadsheet column style names, `A` to `Z` then `AA`, `AB`...
+fn names(count: usize) -> Vec<String> {
+    (0..count)
+        .map(|mut index| {
+            let mut name = Vec::new();
+            loop {
+                name.push(b'A' + (index % 26) as u8);
+                if index < 26 {
+                    break;
+                }
+                index = index / 26 - 1;
+            }
+            name.iter().rev().map(|&letter| letter as char).collect()
+        })
+        .collect()
+}
+
+/// Channel holding a copy of every signal of an amplifier.
+fn trace_channel(amplifier: &str) -> String {
+    format!("{amplifier}.trace")
+}
+
+impl FromStr for Topology {
+    type Err = TopologyError;
+
+    fn from_str(source: &str) -> Result<Self, Self::Err> {
+        let mut routes: Vec<(String, Vec<String>)> = Vec::new();
+        let mut phases = None;
+        let mut signal = None;
+        let mut output = None;
+
+        for (index, line) in source.lines().enumerate() {
+            let line_number = index + 1;
+            let line = line.split('#').next().unwrap().trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            if let Some((name, targets)) = line.split_once("->") {
+                let name = name.trim();
+                let targets: Vec<String> = targets
+                    .split(',')
+                    .map(|target| target.trim().to_string())
+                    .collect();
+                if name.is_empty() || targets.iter().any(|target| target.is_empty()) {
+                    return error(line_number, "expected `<amplifier> -> <target>, ...`");
+                }
+                if routes.iter().any(|(other, _)| other == name) {
+                    return error(line_number, format!("amplifier [{name}] routed twice"));
+                }
+                routes.push((name.to_string(), targets));
+                continue;
+            }
+
+            let (keyword, arguments) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+            let arguments = arguments.trim();
+            match keyword {
+                "phases" => {
+                    let values =
+                        parse_values(arguments).or_else(|e| error(line_number, e.to_string()))?;
+                    phases = Some((values, line_number));
+                }
+                "signal" => {
+                    let (amplifier, value) = arguments
+                        .split_once(char::is_whitespace)
+                        .unwrap_or((arguments, "0"));
+                    let Ok(value) = value.trim().parse() else {
+                        return error(line_number, format!("invalid signal [{value}]"));
+                    };
+                    signal = Some((amplifier.to_string(), value, line_number));
+                }
+                "output" => output = Some((arguments.to_string(), line_number)),
+                _ => return error(line_number, format!("unknown line [{line}]")),
+            }
+        }
+
+        if routes.is_empty() {
+            return error(1, "no amplifier routes");
+        }
+        let mut topology = Topology::new(routes);
+        let channels: Vec<&String> = topology
+            .routes
+            .iter()
+            .flat_map(|(_, targets)| targets)
+            .filter(|target| !topology.routes.iter().any(|(name, _)| name == *target))
+            .unique()
+            .collect();
+
+        topology.output = match output {
+            Some((output, line)) if !channels.contains(&&output) => {
+                return error(line, format!("nothing is routed to [{output}]"));
+            }
+            Some((output, _)) => output,
+            None => match channels[..] {
+                [output] => output.clone(),
+                [] => return error(1, "no output channel"),
+                _ => return error(1, "several output channels, pick one with `output`"),
+            },
+        };
+        if let Some((start, value, line)) = signal {
+            if !topology.routes.iter().any(|(name, _)| *name == start) {
+                return error(line, format!("unknown amplifier [{start}]"));
+            }
+            topology.start = start;
+            topology.signal = value;
+        }
+        if let Some((phases, line)) = phases {
+            if phases.len() != topology.routes.len() {
+                let message = format!(
+                    "[{}] phases for [{}] amplifiers",
+                    phases.len(),
+                    topology.routes.len()
+                );
+                return error(line, message);
+            }
+            topology.phases = Some(phases);
+        }
+        Ok(topology)
+    }
+}
+
+/// Topology config failure with the 1-based source line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TopologyError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl Display for TopologyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Topology error at line [{}]: {}",
+            self.line, self.message
+        )
+    }
+}
+
+impl std::error::Error for TopologyError {}
+
+fn error<T>(line: usize, message: impl Into<String>) -> Result<T, TopologyError> {
+    Err(TopologyError {
+        line,
+        message: message.into(),
+    })
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum AmplifierError {
+    PhaseCount {
+        expected: usize,
+        got: usize,
+    },
+    Failed {
+        amplifier: String,
+        error: IntcodeError,
+    },
+    NoSignal,
+    /// The amplifiers were still running when the scheduler gave up.
+    RoundLimit,
+}
+
+impl Display for AmplifierError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AmplifierError::PhaseCount { expected, got } => {
+                write!(f, "Expected [{expected}] phases, got [{got}]!")
+            }
+            AmplifierError::Failed { amplifier, error } => {
+                write!(f, "Amplifier [{amplifier}] failed: {error}")
+            }
+            AmplifierError::NoSignal => write!(f, "No signal reached the output!"),
+            AmplifierError::RoundLimit => {
+                write!(f, "Amplifiers still running after the round limit!")
+            }
+        }
+    }
+}
+
+impl std::error::Error for AmplifierError {}
+
+/// Signals of one phase permutation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Evaluation {
+    pub phases: Vec<i64>,
+    /// Last signal of the output channel.
+    pub signal: i64,
+    /// Iteration `i` holds the `i`-th signal of each amplifier, in
+    /// topology order, `None` once an amplifier sent fewer signals. A chain
+    /// has a single iteration.
+    pub iterations: Vec<Vec<Option<i64>>>,
+}
+
+/// Runs the amplifiers with `phases`, in topology order, until they halt
+/// or wait for a signal that never comes.
+pub fn evaluate(
+    program: &Program,
+    topology: &Topology,
+    phases: &[i64],
+) -> Result<Evaluation, AmplifierError> {
+    if phases.len() != topology.routes.len() {
+        return Err(AmplifierError::PhaseCount {
+            expected: topology.routes.len(),
+            got: phases.len(),
+        });
+    }
+
+    let mut network = topology.network(program, phases);
+    let report = network.run();
+    if let Some((amplifier, error)) = report.failed.into_iter().next() {
+        return Err(AmplifierError::Failed { amplifier, error });
+    }
+    if report.outcome == Outcome::RoundLimit {
+        return Err(AmplifierError::RoundLimit);
+    }
+    let signal = *network
+        .drain(&topology.output)
+        .last()
+        .ok_or(AmplifierError::NoSignal)?;
+
+    let mut signals: Vec<VecDeque<i64>> = topology
+        .amplifiers()
+        .iter()
+        .map(|name| network.drain(&trace_channel(name)).into())
+        .collect();
+    let mut iterations = Vec::new();
+    while signals.iter().any(|signals| !signals.is_empty()) {
+        iterations.push(signals.iter_mut().map(VecDeque::pop_front).collect());
+    }
+
+    Ok(Evaluation {
+        phases: phases.to_vec(),
+        signal,
+        iterations,
+    })
+}
+
+/// Permutation of `phases` giving the highest signal, the first one in
+/// lexicographic order on ties.
+///
+/// Permutations are handed out one at a time to a thread per core, all
+/// running the same program image. A failing permutation is fatal: the
+/// threads stop taking permutations and the failure of the first failing
+/// permutation, in lexicographic order, is returned.
+pub fn best(
+    program: &Program,
+    topology: &Topology,
+    phases: &[i64],
+) -> Result<Evaluation, AmplifierError> {
+    let permutations = Mutex::new(Permutations::new(phases).enumerate());
+    let failed = AtomicBool::new(false);
+    let threads = thread::available_parallelism().map_or(1, |n| n.get());
+
+    let results: Vec<WorkerResult> = thread::scope(|scope| {
+        let workers: Vec<_> = (0..threads)
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut best: Option<(usize, Evaluation)> = None;
+                    loop {
+                        if failed.load(Ordering::Relaxed) {
+                            return (best, None);
+                        }
+                        let Some((index, phases)) = permutations.lock().unwrap().next() else {
+                            return (best, None);
+                        };
+                        match evaluate(program, topology, &phases) {
+                            Ok(evaluation) => {
+                                if best
+                                    .as_ref()
+                                    .is_none_or(|(_, best)| evaluation.signal > best.signal)
+                                {
+                                    best = Some((index, evaluation));
+                                }
+                            }
+                            Err(error) => {
+                                failed.store(true, Ordering::Relaxed);
+                                return (best, Some((index, error)));
+                            }
+                        }
+                    }
+                })
+            })
+            .collect();
+        workers
+            .into_iter()
+            .map(|worker| worker.join().unwrap())
+            .collect()
+    });
+
+    // permutations are handed out in order, so every one before a failure
+    // has been evaluated
+    let (bests, failures): (Vec<_>, Vec<_>) = results.into_iter().unzip();
+    if let Some((_, error)) = failures
+        .into_iter()
+        .flatten()
+        .min_by_key(|(index, _)| *index)
+    {
+        return Err(error);
+    }
+    bests
+        .into_iter()
+        .flatten()
+        .max_by(|(index, a), (other, b)| a.signal.cmp(&b.signal).then(other.cmp(index)))
+        .map(|(_, evaluation)| evaluation)
+        .ok_or(AmplifierError::NoSignal)
+}
+
+/// Best evaluation and failure of a [`best`] thread, with their
+/// permutation index.
+type WorkerResult = (Option<(usize, Evaluation)>, Option<(usize, AmplifierError)>);
+
+/// Distinct permutations in lexicographic order, generated one at a time.
+struct Permutations {
+    next: Option<Vec<i64>>,
+}
+
+impl Permutations {
+    fn new(values: &[i64]) -> Self {
+        let mut first = values.to_vec();
+        first.sort_unstable();
+        Permutations { next: Some(first) }
+    }
+}
+
+impl Iterator for Permutations {
+    type Item = Vec<i64>;
+
+    fn next(&mut self) -> Option<Vec<i64>> {
+        let current = self.next.take()?;
+        let mut next = current.clone();
+        // rightmost value smaller than its successor, swapped with the
+        // smallest larger value after it, the tail then reversed
+        if let Some(pivot) = (1..next.len()).rev().find(|&i| next[i - 1] < next[i]) {
+            let swap = (pivot..next.len())
+                .rev()
+                .find(|&i| next[i] > next[pivot - 1])
+                .unwrap();
+            next.swap(pivot - 1, swap);
+            next[pivot..].reverse();
+            self.next = Some(next);
+        }
+        Some(current)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::fixtures::adder;
+
+    /// Adds its phase to the first incoming signal, then sends the sum
+    /// `phase` times and halts.
+    fn repeater() -> Program {
+        crate::intcode::asm::assemble(
+            "
+                IN -> [phase]
+                IN -> [signal]
+                ADD [signal], [phase] -> [signal]
+            a:  OUT [signal]
+                ADD [phase], #-1 -> [phase]
+                JT [phase], a
+                HALT
+            phase: DATA 0
+            signal: DATA 0
+            ",
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn names_past_the_alphabet() {
+        let names = names(703);
+        assert_eq!(
+            [
+                &names[0],
+                &names[25],
+                &names[26],
+                &names[27],
+                &names[701],
+                &names[702]
+            ],
+            ["A", "Z", "AA", "AB", "ZZ", "AAA"]
+        );
+        assert_eq!(names.iter().unique().count(), 703);
+
+        let topology = Topology::feedback(30);
+        assert_eq!(topology.amplifiers()[29], "AD");
+        assert_eq!(topology.routes[29].1, ["A", "thrusters"]);
+    }
+
+    #[test]
+    fn reports_config_errors() {
+        let parse = |source: &str| source.parse::<Topology>().unwrap_err();
+        assert_eq!(
+            parse("A -> B\nA -> out"),
+            TopologyError {
+                line: 2,
+                message: "amplifier [A] routed twice".to_string()
+            }
+        );
+        assert_eq!(parse("# only\n\n").message, "no amplifier routes");
+        assert_eq!(parse("A -> ,B").line, 1);
+        assert_eq!(
+            parse("A -> B\nB -> x, y").message,
+            "several output channels, pick one with `output`"
+        );
+        assert_eq!(parse("A -> A").message, "no output channel");
+        assert_eq!(
+            parse("A -> out\noutput nowhere").message,
+            "nothing is routed to [nowhere]"
+        );
+        assert_eq!(
+            parse("A -> out\nsignal Z 1").message,
+            "unknown amplifier [Z]"
+        );
+        assert_eq!(parse("A -> out\nsignal A x").message, "invalid signal [x]");
+        assert_eq!(parse("A -> out\n\nphases 1,2").line, 3);
+        assert_eq!(parse("A -> out\nrun").message, "unknown line [run]");
+        assert_eq!(
+            parse("A -> out\nrun").to_string(),
+            "Topology error at line [2]: unknown line [run]"
+        );
+    }
+
+    #[test]
+    fn pads_uneven_iterations() {
+        let topology: Topology = "A -> B\nB -> out".parse().unwrap();
+        let evaluation = evaluate(&repeater(), &topology, &[2, 1]).unwrap();
+        assert_eq!(evaluation.signal, 3);
+        assert_eq!(evaluation.iterations, [[Some(2), Some(3)], [Some(2), None]]);
+    }
+
+    #[test]
+    fn picks_the_best_permutation() {
+        let topology = Topology::chain(3);
+        let best = best(&adder(), &topology, &[3, 1, 2]).unwrap();
+        assert_eq!((best.phases, best.signal), (vec![1, 2, 3], 6));
+
+        assert_eq!(
+            Permutations::new(&[2, 1, 1]).collect::<Vec<_>>(),
+            [[1, 1, 2], [1, 2, 1], [2, 1, 1]]
+        );
+        assert_eq!(
+            evaluate(&adder(), &topology, &[1]),
+            Err(AmplifierError::PhaseCount {
+                expected: 3,
+                got: 1
+            })
+        );
+    }
+
+    #[test]
+    fn stops_at_the_first_failing_permutation() {
+        // phases of 2 and more run into an invalid opcode
+        let program = crate::intcode::asm::assemble(
+            "
+                IN -> [phase]
+                LT [phase], #2 -> [ok]
+                JT [ok], add
+                DATA 77
+            add: IN -> [signal]
+                ADD [signal], [phase] -> [signal]
+                OUT [signal]
+                HALT
+            phase: DATA 0
+            signal: DATA 0
+            ok: DATA 0
+            ",
+        )
+        .unwrap();
+        let topology = Topology::chain(2);
+        assert_eq!(best(&program, &topology, &[0, 1]).unwrap().signal, 1);
+        // [1, 2] fails in B before [2, 1] fails in A
+        match best(&program, &topology, &[2, 1]) {
+            Err(AmplifierError::Failed { amplifier, .. }) => assert_eq!(amplifier, "B"),
+            other => panic!("{other:?}"),
+        }
+    }
+}
@@ -0,0 +1,638 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::{Display, Write};
+
+use super::disasm::{disassemble, EntryKind, Listing};
+use super::{Instruction, Parameter, ParameterMode, Program};
+
+/// Instructions searched backwards from a jump for the pushed return address.
+const CALL_SETUP_WINDOW: usize = 3;
+
+/// Static view of a program: basic blocks, functions, code patched at run
+/// time and data cells read by the code.
+///
+/// Code is found as in [`disassemble`]. A call is an unconditional jump
+/// preceded by an immediate write of the jump's own end address into a
+/// relative cell (`ADD #ret, #0 -> [rb+1]`), the jump target is a function.
+/// Only position-mode operands are followed into data, so accesses through
+/// the relative base or patched operands are not seen.
+///
+/// # Examples
+/// ```
+/// use advent_of_code::intcode::analysis::analyze;
+/// use advent_of_code::intcode::asm::assemble;
+///
+/// let program = assemble("
+///     ARB #stack
+///     IN -> [x]
+///     CALL double
+///     OUT [x]
+///     HALT
+/// double:
+///     MUL [x], [factor] -> [x]
+///     RET
+/// x: DATA 0
+/// factor: DATA 2
+/// stack: .zero 4
+/// ").unwrap();
+/// let analysis = analyze(&program);
+///
+/// assert_eq!(analysis.blocks.keys().copied().collect::<Vec<_>>(), vec![0, 13, 16]);
+/// assert_eq!(analysis.functions.keys().copied().collect::<Vec<_>>(), vec![0, 16]);
+/// assert!(analysis.functions[&16].returns);
+/// assert_eq!(analysis.functions[&16].callers, [10].into());
+/// assert_eq!(analysis.constants().map(|c| c.address).collect::<Vec<_>>(), vec![26]);
+/// assert!(analysis.to_dot().starts_with("digraph intcode {"));
+/// ```
+pub struct Analysis {
+    pub listing: Listing,
+    pub blocks: BTreeMap<usize, Block>,
+    /// Functions by entry address, the program entry is the function at 0.
+    pub functions: BTreeMap<usize, Function>,
+    pub code_writes: Vec<CodeWrite>,
+    /// Data cells accessed through position-mode operands.
+    pub data: BTreeMap<usize, DataCell>,
+}
+
+/// Straight-line run of instructions, entered only at `start`.
+pub struct Block {
+    pub start: usize,
+    /// Address after the last instruction.
+    pub end: usize,
+    pub instructions: Vec<(usize, Instruction)>,
+    pub successors: Vec<Edge>,
+    /// Ends with a jump through a relative operand, a return.
+    pub returns: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Edge {
+    pub target: usize,
+    pub kind: EdgeKind,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EdgeKind {
+    /// Falling through, or a conditional jump not taken.
+    Next,
+    Jump,
+    Call,
+    /// From a call to its return address.
+    AfterCall,
+}
+
+pub struct Function {
+    pub entry: usize,
+    pub blocks: BTreeSet<usize>,
+    /// Addresses of the calling jumps.
+    pub callers: BTreeSet<usize>,
+    /// Immediate `ARB` at the entry, the frame the function reserves.
+    /// Not set for the program entry, where it sets the stack up.
+    pub frame_size: Option<i64>,
+    pub returns: bool,
+}
+
+/// Write of a position-mode operand into code.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CodeWrite {
+    pub writer: usize,
+    pub address: usize,
+    /// Start of the instruction the written cell belongs to.
+    pub patched: usize,
+    /// Immediate data address the written value is offset from, for
+    /// `ADD #table, [index] -> [operand]` table lookups.
+    pub table: Option<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DataCell {
+    pub address: usize,
+    pub value: i64,
+    pub readers: BTreeSet<usize>,
+    pub writers: BTreeSet<usize>,
+}
+
+impl DataCell {
+    /// Read but never written by a position-mode operand.
+    pub fn is_constant(&self) -> bool {
+        self.writers.is_empty()
+    }
+}
+
+/// Builds the analysis of a program.
+pub fn analyze(program: &Program) -> Analysis {
+    let image = program.image();
+    let listing = disassemble(program);
+
+    let code: BTreeMap<usize, Instruction> = listing
+        .entries
+        .iter()
+        .filter_map(|entry| match &entry.kind {
+            EntryKind::Code(instruction) => Some((entry.address, *instruction)),
+            EntryKind::Data => None,
+        })
+        .collect();
+    let mut owner: Vec<Option<usize>> = vec![None; image.len()];
+    for (&address, instruction) in &code {
+        let end = address + instruction.get_size() as usize;
+        owner[address..end].fill(Some(address));
+    }
+    let is_code = |address: usize| owner.get(address).copied().flatten().is_some();
+
+    // call sites: jump address -> (entry, return address)
+    let mut calls = BTreeMap::new();
+    let addresses: Vec<usize> = code.keys().copied().collect();
+    for (index, &address) in addresses.iter().enumerate() {
+        let instruction = &code[&address];
+        let (Some(target), true) = (jump_target(instruction), always_jumps(instruction)) else {
+            continue;
+        };
+        let next = address + instruction.get_size() as usize;
+        let pushes_return = addresses[index.saturating_sub(CALL_SETUP_WINDOW)..index]
+            .iter()
+            .any(|a| pushed_constant(&code[a]) == Some(next as i64));
+        if pushes_return && is_code(target) {
+            calls.insert(address, (target, next));
+        }
+    }
+
+    // leaders
+    let mut leaders: BTreeSet<usize> = BTreeSet::from([0]);
+    let mut previous_end = None;
+    for (&address, instruction) in &code {
+        if previous_end != Some(address) {
+            leaders.insert(address);
+        }
+        let next = address + instruction.get_size() as usize;
+        if matches!(
+            instruction,
+            Instruction::Jit { .. } | Instruction::Jif { .. }
+        ) {
+            leaders.extend(jump_target(instruction).filter(|t| is_code(*t)));
+            leaders.insert(next);
+        }
+        previous_end = Some(next);
+    }
+
+    // blocks
+    let mut blocks = BTreeMap::new();
+    let mut open: Option<Block> = None;
+    for (&address, instruction) in &code {
+        let split = |block: &mut Block| block.end != address || leaders.contains(&address);
+        if let Some(mut block) = open.take_if(split) {
+            if block.end == address {
+                block.successors.push(Edge {
+                    target: address,
+                    kind: EdgeKind::Next,
+                });
+            }
+            blocks.insert(block.start, block);
+        }
+
+        let next = address + instruction.get_size() as usize;
+        let block = open.get_or_insert_with(|| Block {
+            start: address,
+            end: address,
+            instructions: Vec::new(),
+            successors: Vec::new(),
+            returns: false,
+        });
+        block.instructions.push((address, *instruction));
+        block.end = next;
+
+        match instruction {
+            Instruction::Halt | Instruction::Reserved => (),
+            Instruction::Jit {
+                second_parameter, ..
+            }
+            | Instruction::Jif {
+                second_parameter, ..
+            } => match calls.get(&address) {
+                Some(&(entry, return_address)) => {
+                    block.successors.push(Edge {
+                        target: entry,
+                        kind: EdgeKind::Call,
+                    });
+                    block.successors.push(Edge {
+                        target: return_address,
+                        kind: EdgeKind::AfterCall,
+                    });
+                }
+                None => {
+                    if let Some(target) = jump_target(instruction).filter(|t| is_code(*t)) {
+                        block.successors.push(Edge {
+                            target,
+                            kind: EdgeKind::Jump,
+                        });
+                    }
+                    if always_jumps(instruction) {
+                        block.returns = second_parameter.mode == ParameterMode::Relative;
+                    } else if is_code(next) {
+                        block.successors.push(Edge {
+                            target: next,
+                            kind: EdgeKind::Next,
+                        });
+                    }
+                }
+            },
+            _ => continue,
+        }
+        let block = open.take().unwrap();
+        blocks.insert(block.start, block);
+    }
+    if let Some(block) = open {
+        blocks.insert(block.start, block);
+    }
+
+    // functions
+    let mut entries: BTreeMap<usize, BTreeSet<usize>> = BTreeMap::from([(0, BTreeSet::new())]);
+    for (&caller, &(entry, _)) in &calls {
+        entries.entry(entry).or_default().insert(caller);
+    }
+    let functions = entries
+        .into_iter()
+        .filter(|(entry, _)| blocks.contains_key(entry))
+        .map(|(entry, callers)| {
+            let mut body = BTreeSet::new();
+            let mut to_visit = vec![entry];
+            while let Some(start) = to_visit.pop() {
+                let Some(block) = blocks.get(&start) else {
+                    continue;
+                };
+                if !body.insert(start) {
+                    continue;
+                }
+                to_visit.extend(
+                    block
+                        .successors
+                        .iter()
+                        .filter(|edge| edge.kind != EdgeKind::Call)
+                        .map(|edge| edge.target),
+                );
+            }
+            let frame_size = match blocks[&entry].instructions.first() {
+                // the program entry sets the stack up instead
+                _ if entry == 0 => None,
+                Some((
+                    _,
+                    Instruction::Arb {
+                        first_parameter:
+                            Parameter {
+                                mode: ParameterMode::Immediate,
+                                value,
+                            },
+                    },
+                )) => Some(*value),
+                _ => None,
+            };
+            let returns = body.iter().any(|start| blocks[start].returns);
+            let function = Function {
+                entry,
+                blocks: body,
+                callers,
+                frame_size,
+                returns,
+            };
+            (entry, function)
+        })
+        .collect();
+
+    // writes into code and data accesses
+    let mut code_writes = Vec::new();
+    let mut data: BTreeMap<usize, DataCell> = BTreeMap::new();
+    for (&address, instruction) in &code {
+        for parameter in instruction.read_parameters() {
+            if let Some(target) = position(parameter, image.len()).filter(|a| !is_code(*a)) {
+                data_cell(&mut data, image, target).readers.insert(address);
+            }
+        }
+        let Some(target) = instruction
+            .write_parameter()
+            .and_then(|parameter| position(parameter, image.len()))
+        else {
+            continue;
+        };
+        match owner[target] {
+            Some(patched) => code_writes.push(CodeWrite {
+                writer: address,
+                address: target,
+                patched,
+                table: table_base(instruction).filter(|base| !is_code(*base)),
+            }),
+            None => {
+                data_cell(&mut data, image, target).writers.insert(address);
+            }
+        }
+    }
+
+    Analysis {
+        listing,
+        blocks,
+        functions,
+        code_writes,
+        data,
+    }
+}
+
+fn data_cell<'a>(
+    data: &'a mut BTreeMap<usize, DataCell>,
+    image: &[i64],
+    address: usize,
+) -> &'a mut DataCell {
+    data.entry(address).or_insert_with(|| DataCell {
+        address,
+        value: image[address],
+        readers: BTreeSet::new(),
+        writers: BTreeSet::new(),
+    })
+}
+
+fn jump_target(instruction: &Instruction) -> Option<usize> {
+    match instruction {
+        Instruction::Jit {
+            second_parameter, ..
+        }
+        | Instruction::Jif {
+            second_parameter, ..
+        } if second_parameter.mode == ParameterMode::Immediate => {
+            usize::try_from(second_parameter.value).ok()
+        }
+        _ => None,
+    }
+}
+
+fn always_jumps(instruction: &Instruction) -> bool {
+    match instruction {
+        Instruction::Jit {
+            first_parameter, ..
+        } => first_parameter.mode == ParameterMode::Immediate && first_parameter.value != 0,
+        Instruction::Jif {
+            first_parameter, ..
+        } => first_parameter.mode == ParameterMode::Immediate && first_parameter.value == 0,
+        _ => false,
+    }
+}
+
+/// Constant written into a relative cell, as pushed return addresses are,
+/// `None` when it overflows.
+fn pushed_constant(instruction: &Instruction) -> Option<i64> {
+    match instruction {
+        Instruction::Add {
+            first_parameter: a,
+            second_parameter: b,
+            result,
+        }
+        | Instruction::Mul {
+            first_parameter: a,
+            second_parameter: b,
+            result,
+        } if a.mode == ParameterMode::Immediate
+            && b.mode == ParameterMode::Immediate
+            && result.mode == ParameterMode::Relative =>
+        {
+            match instruction {
+                Instruction::Add { .. } => a.value.checked_add(b.value),
+                _ => a.value.checked_mul(b.value),
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Immediate operand of an `ADD` mixing it with a variable.
+fn table_base(instruction: &Instruction) -> Option<usize> {
+    let Instruction::Add {
+        first_parameter: a,
+        second_parameter: b,
+        ..
+    } = instruction
+    else {
+        return None;
+    };
+    match (a.mode, b.mode) {
+        (ParameterMode::Immediate, ParameterMode::Immediate) => None,
+        (ParameterMode::Immediate, _) => usize::try_from(a.value).ok(),
+        (_, ParameterMode::Immediate) => usize::try_from(b.value).ok(),
+        _ => None,
+    }
+}
+
+fn position(parameter: &Parameter, len: usize) -> Option<usize> {
+    match parameter.mode {
+        ParameterMode::Position => usize::try_from(parameter.value).ok().filter(|a| *a < len),
+        _ => None,
+    }
+}
+
+impl Analysis {
+    /// Data cells never written by the code.
+    pub fn constants(&self) -> impl Iterator<Item = &DataCell> {
+        self.data.values().filter(|cell| cell.is_constant())
+    }
+
+    /// Function name used in reports, `L0012` style.
+    fn function_name(&self, entry: usize) -> String {
+        match entry {
+            0 => "main".to_string(),
+            _ => Listing::label(entry),
+        }
+    }
+
+    /// Graphviz graph of the blocks, clustered by function. Calls are dashed,
+    /// returns to the call site dotted and patches of code red.
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::new();
+        writeln!(dot, "digraph intcode {{").unwrap();
+        writeln!(dot, "  node [shape=box, fontname=\"monospace\"];").unwrap();
+
+        let mut placed = BTreeSet::new();
+        for function in self.functions.values() {
+            writeln!(dot, "  subgraph cluster_{:04} {{", function.entry).unwrap();
+            let frame = function
+                .frame_size
+                .map_or(String::new(), |size| format!(" frame {size}"));
+            writeln!(
+                dot,
+                "    label=\"{}{frame}\";",
+                self.function_name(function.entry)
+            )
+            .unwrap();
+            for start in &function.blocks {
+                if placed.insert(*start) {
+                    writeln!(dot, "    {}", self.dot_node(&self.blocks[start])).unwrap();
+                }
+            }
+            writeln!(dot, "  }}").unwrap();
+        }
+        for block in self.blocks.values() {
+            if placed.insert(block.start) {
+                writeln!(dot, "  {}", self.dot_node(block)).unwrap();
+            }
+        }
+
+        for block in self.blocks.values() {
+            for edge in &block.successors {
+                let style = match edge.kind {
+                    EdgeKind::Next => "",
+                    EdgeKind::Jump => " [color=blue]",
+                    EdgeKind::Call => " [style=dashed]",
+                    EdgeKind::AfterCall => " [style=dotted]",
+                };
+                writeln!(dot, "  b{:04} -> b{:04}{style};", block.start, edge.target).unwrap();
+            }
+        }
+        for write in &self.code_writes {
+            let (Some(from), Some(to)) =
+                (self.block_of(write.writer), self.block_of(write.patched))
+            else {
+                continue;
+            };
+            writeln!(
+                dot,
+                "  b{from:04} -> b{to:04} [color=red, style=dashed, label=\"patch {}\"];",
+                write.address
+            )
+            .unwrap();
+        }
+        writeln!(dot, "}}").unwrap();
+        dot
+    }
+
+    fn dot_node(&self, block: &Block) -> String {
+        let mut label = format!("{}:\\l", Listing::label(block.start));
+        for (address, instruction) in &block.instructions {
+            let text = format!(
+                "{address:04}: {}",
+                self.listing.format_instruction(instruction)
+            );
+            label.push_str(&text.replace('\\', "\\\\").replace('"', "\\\""));
+            label.push_str("\\l");
+        }
+        format!("b{:04} [label=\"{label}\"];", block.start)
+    }
+
+    /// Start of the block holding the instruction at `address`.
+    pub fn block_of(&self, address: usize) -> Option<usize> {
+        let (start, block) = self.blocks.range(..=address).next_back()?;
+        (address < block.end).then_some(*start)
+    }
+}
+
+/// Summary of blocks, functions, code patches and data constants.
+impl Display for Analysis {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "{} blocks, {} functions",
+            self.blocks.len(),
+            self.functions.len()
+        )?;
+
+        writeln!(f, "\nfunctions:")?;
+        for function in self.functions.values() {
+            let frame = function
+                .frame_size
+                .map_or(String::new(), |size| format!("  frame {size}"));
+            let callers: Vec<String> = function.callers.iter().map(|a| a.to_string()).collect();
+            writeln!(
+                f,
+                "  {:<6} {} blocks{frame}{}  called from [{}]",
+                self.function_name(function.entry),
+                function.blocks.len(),
+                if function.returns { "  returns" } else { "" },
+                callers.join(", ")
+            )?;
+        }
+
+        writeln!(f, "\ncode writes:")?;
+        for write in &self.code_writes {
+            let table = write
+                .table
+                .map_or(String::new(), |base| format!("  table {base}"));
+            writeln!(
+                f,
+                "  {:04} patches [{}] of {}{table}",
+                write.writer,
+                write.address,
+                Listing::label(write.patched)
+            )?;
+        }
+
+        writeln!(f, "\nconstants:")?;
+        for cell in self.constants() {
+            let readers: Vec<String> = cell.readers.iter().map(|a| a.to_string()).collect();
+            writeln!(
+                f,
+                "  [{}] = {}  read at {}",
+                cell.address,
+                cell.value,
+                readers.join(", ")
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::asm::assemble;
+
+    #[test]
+    fn finds_calls_and_frames() {
+        let program = assemble(
+            "
+            ARB #stack
+            CALL f
+            HALT
+        f:  ARB #2
+            OUT #1
+            ARB #-2
+            RET
+        stack: .zero 4
+            ",
+        )
+        .unwrap();
+        let analysis = analyze(&program);
+
+        let function = &analysis.functions[&12];
+        assert_eq!((function.frame_size, function.returns), (Some(2), true));
+        assert_eq!(function.callers, [8].into());
+        let call = Edge {
+            target: 12,
+            kind: EdgeKind::Call,
+        };
+        assert!(analysis.blocks[&0].successors.contains(&call));
+        assert_eq!(analysis.block_of(14), Some(12));
+    }
+
+    #[test]
+    fn overflowing_push_is_not_a_call() {
+        // ADD #MAX, #1 -> [rb+1] would push MAX + 1, not the address 7
+        let max = i64::MAX;
+        let program: Program = format!("21101,{max},1,1,1105,1,7,99").parse().unwrap();
+        let analysis = analyze(&program);
+        assert_eq!(analysis.functions.keys().copied().collect::<Vec<_>>(), [0]);
+
+        let program: Program = format!("21102,{max},2,1,1105,1,7,99").parse().unwrap();
+        assert_eq!(analyze(&program).functions.len(), 1);
+    }
+
+    #[test]
+    fn finds_code_patches_and_constants() {
+        // copies [9] into the operand of the OUT at 4
+        let program: Program = "1001,9,0,5,104,0,99,0,0,3".parse().unwrap();
+        let analysis = analyze(&program);
+        assert_eq!(
+            analysis.code_writes,
+            [CodeWrite {
+                writer: 0,
+                address: 5,
+                patched: 4,
+                table: None
+            }]
+        );
+        assert!(analysis.data[&9].is_constant());
+        assert_eq!(analysis.data[&9].readers, [0].into());
+        assert_eq!(analysis.constants().count(), 1);
+    }
+}
@@ -0,0 +1,208 @@
+use std::collections::VecDeque;
+use std::io::{self, BufRead, Write};
+
+use super::{ascii_char, ExecuteResult, Machine, Memory};
+
+/// Machine talking ASCII, for firmware driven by text commands.
+///
+/// Output is split into complete text lines, the unfinished last line
+/// (usually a prompt) and values outside ASCII, which are puzzle answers.
+///
+/// # Examples
+/// ```
+/// use advent_of_code::intcode::*;
+/// use advent_of_code::intcode::ascii::AsciiMachine;
+///
+/// // prints "Hi", echoes one input character, then outputs 1000
+/// let program: Program = "104,72,104,105,104,10,3,13,4,13,104,1000,99,0"
+///     .parse()
+///     .unwrap();
+/// let mut machine = AsciiMachine::new(program.memory());
+///
+/// assert_eq!(machine.run(), ExecuteResult::WaitingInput);
+/// assert_eq!(machine.read_line(), Some("Hi".to_string()));
+///
+/// machine.send_line("ok");
+/// assert!(matches!(machine.run(), ExecuteResult::Halt(_)));
+/// assert_eq!(machine.prompt(), "o");
+/// assert_eq!(machine.values(), &[1000]);
+/// ```
+pub struct AsciiMachine {
+    machine: Machine,
+    lines: VecDeque<String>,
+    partial: String,
+    values: Vec<i64>,
+}
+
+impl AsciiMachine {
+    pub fn new(memory: impl Into<Memory>) -> Self {
+        Machine::new(memory).into()
+    }
+
+    /// Queues `text` as is.
+    pub fn send(&mut self, text: &str) {
+        self.machine.push_ascii_input(text);
+    }
+
+    /// Queues one command, followed by a newline.
+    pub fn send_line(&mut self, line: &str) {
+        self.send(line);
+        self.machine.push_input('\n' as i64);
+    }
+
+    /// Executes the machine and sorts its new output.
+    pub fn run(&mut self) -> ExecuteResult {
+        let result = self.machine.execute();
+        for value in self.machine.take_output() {
+            match ascii_char(value) {
+                Some('\n') => self.lines.push_back(std::mem::take(&mut self.partial)),
+                Some(c) => self.partial.push(c),
+                None => self.values.push(value),
+            }
+        }
+        result
+    }
+
+    /// Oldest complete line not read yet.
+    pub fn read_line(&mut self) -> Option<String> {
+        self.lines.pop_front()
+    }
+
+    pub fn take_lines(&mut self) -> Vec<String> {
+        self.lines.drain(..).collect()
+    }
+
+    /// Text after the last newline.
+    pub fn prompt(&self) -> &str {
+        &self.partial
+    }
+
+    /// Non-ASCII output values, in output order.
+    pub fn values(&self) -> &[i64] {
+        &self.values
+    }
+
+    pub fn take_values(&mut self) -> Vec<i64> {
+        std::mem::take(&mut self.values)
+    }
+
+    pub fn machine(&self) -> &Machine {
+        &self.machine
+    }
+
+    pub fn machine_mut(&mut self) -> &mut Machine {
+        &mut self.machine
+    }
+
+    /// Runs the firmware as a terminal: output goes to `output`, non-ASCII
+    /// values as `[value]` lines, and every line of `input` is sent as a
+    /// command when the firmware waits. Stops when the firmware stops or
+    /// `input` ends.
+    ///
+    /// `!save <file>` and `!load <file>` lines are not sent: they save the
+    /// machine state or resume a saved one, see [`Snapshot`](super::Snapshot).
+    pub fn interact(
+        &mut self,
+        mut input: impl BufRead,
+        mut output: impl Write,
+    ) -> io::Result<ExecuteResult> {
+        let mut line = String::new();
+        loop {
+            let result = self.run();
+            for text in self.take_lines() {
+                writeln!(output, "{text}")?;
+            }
+            write!(output, "{}", std::mem::take(&mut self.partial))?;
+            for value in self.take_values() {
+                writeln!(output, "[{value}]")?;
+            }
+            output.flush()?;
+
+            if result != ExecuteResult::WaitingInput {
+                return Ok(result);
+            }
+            line.clear();
+            if input.read_line(&mut line)? == 0 {
+                return Ok(result);
+            }
+            let command = line.trim_end_matches(['\n', '\r']);
+            match command.split_once(' ') {
+                Some(("!save", path)) => match self.machine.save(path) {
+                    Ok(()) => writeln!(output, "(saved to [{path}])")?,
+                    Err(error) => writeln!(output, "({error})")?,
+                },
+                Some(("!load", path)) => match Machine::load(path) {
+                    Ok(machine) => {
+                        *self = machine.into();
+                        writeln!(output, "(loaded [{path}])")?;
+                    }
+                    Err(error) => writeln!(output, "({error})")?,
+                },
+                _ => self.send_line(command),
+            }
+        }
+    }
+
+    /// [`AsciiMachine::interact`] on stdin and stdout.
+    pub fn play(&mut self) -> io::Result<ExecuteResult> {
+        self.interact(io::stdin().lock(), io::stdout())
+    }
+}
+
+impl From<Machine> for AsciiMachine {
+    fn from(machine: Machine) -> Self {
+        AsciiMachine {
+            machine,
+            lines: VecDeque::new(),
+            partial: String::new(),
+            values: Vec::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::fixtures;
+
+    /// Echoes every input character forever.
+    fn echo() -> AsciiMachine {
+        AsciiMachine::new(fixtures::echo().memory())
+    }
+
+    #[test]
+    fn splits_lines_prompt_and_values() {
+        let mut machine = echo();
+        machine.send("one\ntwo\nthr");
+        machine.machine_mut().push_input(1000);
+        assert_eq!(machine.run(), ExecuteResult::WaitingInput);
+
+        assert_eq!(machine.take_lines(), ["one", "two"]);
+        assert_eq!(machine.read_line(), None);
+        assert_eq!((machine.prompt(), machine.values()), ("thr", &[1000][..]));
+        machine.send_line("ee");
+        machine.run();
+        assert_eq!(machine.read_line(), Some("three".to_string()));
+    }
+
+    #[test]
+    fn interacts_with_save_and_load() {
+        let path = std::env::temp_dir().join(format!("intcode-ascii-{}.snap", std::process::id()));
+        let path = path.to_str().unwrap();
+        let input = format!("ab\n!save {path}\ncd\n!load {path}\nef\n!load {path}.missing\n");
+
+        let mut output = Vec::new();
+        let result = echo().interact(input.as_bytes(), &mut output).unwrap();
+        std::fs::remove_file(path).unwrap();
+
+        assert_eq!(result, ExecuteResult::WaitingInput);
+        let output = String::from_utf8(output).unwrap();
+        let mut lines = output.lines();
+        assert_eq!(lines.next(), Some("ab"));
+        assert_eq!(lines.next(), Some(format!("(saved to [{path}])").as_str()));
+        assert_eq!(lines.next(), Some("cd"));
+        assert_eq!(lines.next(), Some(format!("(loaded [{path}])").as_str()));
+        assert_eq!(lines.next(), Some("ef"));
+        assert!(lines.next().unwrap().starts_with("(Snapshot IO error"));
+    }
+}
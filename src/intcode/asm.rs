@@ -0,0 +1,671 @@
+use std::collections::HashMap;
+use std::fmt::Display;
+
+use super::{ParameterMode, Program};
+
+/// Macro expansion depth before giving up on recursive macros.
+const MAX_MACRO_DEPTH: usize = 32;
+
+/// Assembles intcode source into a program.
+///
+/// # Syntax
+/// One statement per line, `;` starts a comment, mnemonics and macro
+/// names are case-insensitive.
+///
+/// * Instructions: `ADD a, b -> c`, `MUL a, b -> c`, `IN -> c`, `OUT a`,
+///   `JT a, b`, `JF a, b`, `LT a, b -> c`, `EQ a, b -> c`, `ARB a`, `HALT`.
+/// * Operands: `[expr]` position, `#expr` immediate, `[rb+expr]` / `[rb-n]` / `[rb]`
+///   relative. A bare `expr` is immediate, so `JT #1, loop` jumps to `loop`.
+/// * Expressions: a number, a label or constant name, or `name+n` / `name-n`.
+/// * Labels: `name:`, optionally followed by a statement on the same line.
+/// * Data: `DATA 1, -2, name`, `.zero n` (n zero cells), `.ascii "text\n"`.
+/// * Constants: `.const NAME = expr`, using numbers and earlier constants.
+/// * Relative-base helpers, using `rb` as a stack pointer growing upwards:
+///   `PUSH a`, `POP -> c`, `CALL target`, `RET`, plus `MOV a -> c` and `JMP target`.
+///   Set the stack up with `ARB #stack` before the first `PUSH` or `CALL`.
+/// * Macros: `.macro NAME p1, p2` ... `.endm`, invoked as `NAME a, b`.
+///   Parameters are substituted as words, labels defined in the body are local.
+///
+/// # Examples
+/// ```
+/// use advent_of_code::intcode::asm::assemble;
+/// use advent_of_code::intcode::*;
+///
+/// let source = "
+///     ARB #stack
+///     IN -> [x]
+///     CALL double
+///     OUT [x]
+///     HALT
+/// double:
+///     MUL [x], #2 -> [x]
+///     RET
+/// x: DATA 0
+/// stack: .zero 4
+/// ";
+/// let program = assemble(source).unwrap();
+/// let mut machine = program.machine_with_input(vec![21]);
+/// machine.execute();
+/// assert_eq!(machine.output(), &vec![42]);
+/// ```
+pub fn assemble(source: &str) -> Result<Program, AsmError> {
+    let lines = strip_comments(source);
+    let mut assembler = Assembler::default();
+    let statements = assembler.expand(&lines, 0)?;
+    assembler.encode(&statements).map(Program::new)
+}
+
+/// Assembly failure with the 1-based source line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AsmError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl Display for AsmError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Assembly error at line [{}]: {}",
+            self.line, self.message
+        )
+    }
+}
+
+impl std::error::Error for AsmError {}
+
+fn error<T>(line: usize, message: impl Into<String>) -> Result<T, AsmError> {
+    Err(AsmError {
+        line,
+        message: message.into(),
+    })
+}
+
+/// Source line with its 1-based number.
+#[derive(Clone)]
+struct Line {
+    number: usize,
+    text: String,
+}
+
+struct Macro {
+    parameters: Vec<String>,
+    body: Vec<Line>,
+}
+
+#[derive(Default)]
+struct Assembler {
+    macros: HashMap<String, Macro>,
+    unique: usize,
+}
+
+#[derive(Debug, Clone)]
+struct Expr {
+    symbol: Option<String>,
+    offset: i64,
+}
+
+#[derive(Debug, Clone)]
+struct Operand {
+    mode: ParameterMode,
+    expr: Expr,
+}
+
+enum Statement {
+    Label(String),
+    Const(String, Expr),
+    Instruction(i64, Vec<Operand>),
+    Data(Vec<Expr>),
+}
+
+fn strip_comments(source: &str) -> Vec<Line> {
+    source
+        .lines()
+        .enumerate()
+        .map(|(i, text)| Line {
+            number: i + 1,
+            text: strip_comment(text).trim().to_string(),
+        })
+        .filter(|line| !line.text.is_empty())
+        .collect()
+}
+
+// keeps `;` inside `.ascii` strings
+fn strip_comment(text: &str) -> &str {
+    let mut in_string = false;
+    for (i, c) in text.char_indices() {
+        match c {
+            '"' => in_string = !in_string,
+            ';' if !in_string => return &text[..i],
+            _ => (),
+        }
+    }
+    text
+}
+
+fn is_identifier(text: &str) -> bool {
+    let mut chars = text.chars();
+    matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn split_word(text: &str) -> (&str, &str) {
+    match text.find(char::is_whitespace) {
+        Some(i) => (&text[..i], text[i..].trim()),
+        None => (text, ""),
+    }
+}
+
+fn split_list(text: &str) -> Vec<&str> {
+    if text.trim().is_empty() {
+        return Vec::new();
+    }
+    text.split(',').map(str::trim).collect()
+}
+
+/// Replaces whole identifier words.
+fn replace_word(text: &str, word: &str, replacement: &str) -> String {
+    let mut result = String::new();
+    let mut rest = text;
+    while let Some(i) = rest.find(word) {
+        let before = rest[..i].chars().next_back();
+        let after = rest[i + word.len()..].chars().next();
+        let is_word_char =
+            |c: Option<char>| c.is_some_and(|c| c.is_ascii_alphanumeric() || c == '_');
+        result.push_str(&rest[..i]);
+        if is_word_char(before) || is_word_char(after) {
+            result.push_str(word);
+        } else {
+            result.push_str(replacement);
+        }
+        rest = &rest[i + word.len()..];
+    }
+    result.push_str(rest);
+    result
+}
+
+fn parse_expr(text: &str, line: usize) -> Result<Expr, AsmError> {
+    let text = text.trim();
+    if let Ok(value) = text.parse::<i64>() {
+        return Ok(Expr {
+            symbol: None,
+            offset: value,
+        });
+    }
+    let (symbol, offset) = match text.find(['+', '-']) {
+        Some(i) => {
+            let Ok(offset) = text[i..].replace('+', "").trim().parse::<i64>() else {
+                return error(line, format!("Invalid offset in expression [{text}]"));
+            };
+            (text[..i].trim(), offset)
+        }
+        None => (text, 0),
+    };
+    if !is_identifier(symbol) {
+        return error(line, format!("Invalid expression [{text}]"));
+    }
+    Ok(Expr {
+        symbol: Some(symbol.to_string()),
+        offset,
+    })
+}
+
+fn parse_operand(text: &str, line: usize) -> Result<Operand, AsmError> {
+    let text = text.trim();
+    if let Some(inner) = text.strip_prefix('[').and_then(|t| t.strip_suffix(']')) {
+        let inner = inner.trim();
+        let relative_offset = inner
+            .strip_prefix("rb")
+            .filter(|o| o.is_empty() || o.trim_start().starts_with(['+', '-']));
+        if let Some(offset) = relative_offset {
+            let offset = offset.trim();
+            let expr = if offset.is_empty() {
+                Expr {
+                    symbol: None,
+                    offset: 0,
+                }
+            } else if let Some(positive) = offset.strip_prefix('+') {
+                parse_expr(positive, line)?
+            } else if let Ok(value) = offset.replace(' ', "").parse::<i64>() {
+                Expr {
+                    symbol: None,
+                    offset: value,
+                }
+            } else {
+                return error(line, format!("Invalid relative operand [{text}]"));
+            };
+            return Ok(Operand {
+                mode: ParameterMode::Relative,
+                expr,
+            });
+        }
+        return Ok(Operand {
+            mode: ParameterMode::Position,
+            expr: parse_expr(inner, line)?,
+        });
+    }
+    let expr = parse_expr(text.strip_prefix('#').unwrap_or(text), line)?;
+    Ok(Operand {
+        mode: ParameterMode::Immediate,
+        expr,
+    })
+}
+
+fn parse_ascii(text: &str, line: usize) -> Result<Vec<Expr>, AsmError> {
+    let Some(inner) = text
+        .trim()
+        .strip_prefix('"')
+        .and_then(|t| t.strip_suffix('"'))
+    else {
+        return error(line, "Expected quoted string for .ascii");
+    };
+    let mut values = Vec::new();
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        let c = if c == '\\' {
+            match chars.next() {
+                Some('n') => '\n',
+                Some('t') => '\t',
+                Some(other) => other,
+                None => return error(line, "Dangling escape in .ascii"),
+            }
+        } else {
+            c
+        };
+        values.push(Expr {
+            symbol: None,
+            offset: c as i64,
+        });
+    }
+    Ok(values)
+}
+
+/// Opcode, input operand count and whether the last operand is written.
+pub(super) fn opcode_shape(mnemonic: &str) -> Option<(i64, usize, bool)> {
+    Some(match mnemonic {
+        "ADD" => (1, 2, true),
+        "MUL" => (2, 2, true),
+        "IN" => (3, 0, true),
+        "OUT" => (4, 1, false),
+        "JT" => (5, 2, false),
+        "JF" => (6, 2, false),
+        "LT" => (7, 2, true),
+        "EQ" => (8, 2, true),
+        "ARB" => (9, 1, false),
+        "HALT" => (99, 0, false),
+        _ => return None,
+    })
+}
+
+impl Assembler {
+    fn next_unique(&mut self) -> usize {
+        self.unique += 1;
+        self.unique
+    }
+
+    fn expand(
+        &mut self,
+        lines: &[Line],
+        depth: usize,
+    ) -> Result<Vec<(usize, Statement)>, AsmError> {
+        let mut statements = Vec::new();
+        let mut index = 0;
+        while index < lines.len() {
+            let line = &lines[index];
+            let number = line.number;
+            index += 1;
+
+            // labels, possibly followed by a statement
+            let mut text = line.text.as_str();
+            while let Some(i) = text.find(':') {
+                let label = text[..i].trim();
+                if !is_identifier(label) {
+                    break;
+                }
+                statements.push((number, Statement::Label(label.to_string())));
+                text = text[i + 1..].trim();
+            }
+            if text.is_empty() {
+                continue;
+            }
+
+            let (word, rest) = split_word(text);
+            let keyword = word.to_ascii_uppercase();
+            match keyword.as_str() {
+                ".MACRO" => {
+                    let (name, parameters) = split_word(rest);
+                    if !is_identifier(name) {
+                        return error(number, format!("Invalid macro name [{name}]"));
+                    }
+                    let mut body = Vec::new();
+                    loop {
+                        let Some(body_line) = lines.get(index) else {
+                            return error(number, format!("Missing .endm for macro [{name}]"));
+                        };
+                        index += 1;
+                        if body_line.text.eq_ignore_ascii_case(".endm") {
+                            break;
+                        }
+                        body.push(body_line.clone());
+                    }
+                    self.macros.insert(
+                        name.to_ascii_uppercase(),
+                        Macro {
+                            parameters: split_list(parameters)
+                                .into_iter()
+                                .map(str::to_string)
+                                .collect(),
+                            body,
+                        },
+                    );
+                }
+                ".ENDM" => return error(number, ".endm without .macro"),
+                ".CONST" => {
+                    let Some((name, value)) = rest.split_once('=') else {
+                        return error(number, "Expected .const NAME = value");
+                    };
+                    let name = name.trim();
+                    if !is_identifier(name) {
+                        return error(number, format!("Invalid constant name [{name}]"));
+                    }
+                    statements.push((
+                        number,
+                        Statement::Const(name.to_string(), parse_expr(value, number)?),
+                    ));
+                }
+                ".ZERO" => {
+                    let Ok(count) = rest.parse::<usize>() else {
+                        return error(number, format!("Invalid .zero count [{rest}]"));
+                    };
+                    let zero = Expr {
+                        symbol: None,
+                        offset: 0,
+                    };
+                    statements.push((number, Statement::Data(vec![zero; count])));
+                }
+                ".ASCII" => statements.push((number, Statement::Data(parse_ascii(rest, number)?))),
+                "DATA" => {
+                    let values = split_list(rest)
+                        .into_iter()
+                        .map(|v| parse_expr(v, number))
+                        .collect::<Result<_, _>>()?;
+                    statements.push((number, Statement::Data(values)));
+                }
+                "PUSH" | "POP" | "CALL" | "RET" | "MOV" | "JMP" => {
+                    let expansion = self.builtin(&keyword, rest, number)?;
+                    statements.extend(self.expand(&expansion, depth)?);
+                }
+                _ => {
+                    if let Some((opcode, inputs, writes)) = opcode_shape(&keyword) {
+                        statements.push((
+                            number,
+                            Statement::Instruction(
+                                opcode,
+                                parse_operands(rest, inputs, writes, number)?,
+                            ),
+                        ));
+                    } else if self.macros.contains_key(&keyword) {
+                        if depth >= MAX_MACRO_DEPTH {
+                            return error(number, format!("Macro [{word}] nested too deep"));
+                        }
+                        let expansion = self.instantiate(&keyword, rest, number)?;
+                        statements.extend(self.expand(&expansion, depth + 1)?);
+                    } else {
+                        return error(number, format!("Unknown mnemonic [{word}]"));
+                    }
+                }
+            }
+        }
+        Ok(statements)
+    }
+
+    fn builtin(&mut self, keyword: &str, rest: &str, number: usize) -> Result<Vec<Line>, AsmError> {
+        let texts = match keyword {
+            // operands are resolved against rb before the helper runs
+            "PUSH" => vec![format!("ADD {rest}, #0 -> [rb+1]"), "ARB #1".to_string()],
+            "POP" => {
+                let Some(target) = rest.strip_prefix("->") else {
+                    return error(number, "Expected POP -> target");
+                };
+                vec![
+                    format!("ADD [rb], #0 -> {}", target.trim()),
+                    "ARB #-1".to_string(),
+                ]
+            }
+            "CALL" => {
+                let ret = format!("__ret_{}", self.next_unique());
+                vec![
+                    format!("PUSH #{ret}"),
+                    format!("JT #1, {rest}"),
+                    format!("{ret}:"),
+                ]
+            }
+            "RET" => vec!["ARB #-1".to_string(), "JT #1, [rb+1]".to_string()],
+            "MOV" => {
+                let Some((source, target)) = rest.split_once("->") else {
+                    return error(number, "Expected MOV source -> target");
+                };
+                vec![format!("ADD {}, #0 -> {}", source.trim(), target.trim())]
+            }
+            "JMP" => vec![format!("JT #1, {rest}")],
+            _ => unreachable!(),
+        };
+        Ok(texts
+            .into_iter()
+            .map(|text| Line { number, text })
+            .collect())
+    }
+
+    fn instantiate(
+        &mut self,
+        name: &str,
+        rest: &str,
+        number: usize,
+    ) -> Result<Vec<Line>, AsmError> {
+        let arguments = split_list(rest);
+        let unique = self.next_unique();
+        let definition = &self.macros[name];
+        if arguments.len() != definition.parameters.len() {
+            return error(
+                number,
+                format!(
+                    "Macro [{name}] expects {} arguments, got {}",
+                    definition.parameters.len(),
+                    arguments.len()
+                ),
+            );
+        }
+
+        let local_labels: Vec<String> = definition
+            .body
+            .iter()
+            .filter_map(|line| line.text.split_once(':'))
+            .map(|(label, _)| label.trim().to_string())
+            .filter(|label| is_identifier(label))
+            .collect();
+
+        Ok(definition
+            .body
+            .iter()
+            .map(|line| {
+                let mut text = line.text.clone();
+                for (parameter, argument) in definition.parameters.iter().zip(&arguments) {
+                    text = replace_word(&text, parameter, argument);
+                }
+                for label in &local_labels {
+                    text = replace_word(&text, label, &format!("{label}__{unique}"));
+                }
+                Line { number, text }
+            })
+            .collect())
+    }
+
+    fn encode(&self, statements: &[(usize, Statement)]) -> Result<Vec<i64>, AsmError> {
+        // layout
+        let mut symbols: HashMap<&str, i64> = HashMap::new();
+        let mut address = 0;
+        for (number, statement) in statements {
+            let defined = match statement {
+                Statement::Label(name) => Some((name, address as i64)),
+                Statement::Const(name, expr) => Some((name, resolve(expr, &symbols, *number)?)),
+                Statement::Instruction(_, operands) => {
+                    address += 1 + operands.len();
+                    None
+                }
+                Statement::Data(values) => {
+                    address += values.len();
+                    None
+                }
+            };
+            if let Some((name, value)) = defined {
+                if symbols.insert(name, value).is_some() {
+                    return error(*number, format!("Duplicate symbol [{name}]"));
+                }
+            }
+        }
+
+        let mut image = Vec::with_capacity(address);
+        for (number, statement) in statements {
+            match statement {
+                Statement::Instruction(opcode, operands) => {
+                    let mut word = *opcode;
+                    let mut factor = 100;
+                    for operand in operands {
+                        word += factor * mode_digit(operand.mode);
+                        factor *= 10;
+                    }
+                    image.push(word);
+                    for operand in operands {
+                        image.push(resolve(&operand.expr, &symbols, *number)?);
+                    }
+                }
+                Statement::Data(values) => {
+                    for value in values {
+                        image.push(resolve(value, &symbols, *number)?);
+                    }
+                }
+                Statement::Label(_) | Statement::Const(..) => (),
+            }
+        }
+        Ok(image)
+    }
+}
+
+fn parse_operands(
+    text: &str,
+    inputs: usize,
+    writes: bool,
+    number: usize,
+) -> Result<Vec<Operand>, AsmError> {
+    let (input_text, output_text) = match text.split_once("->") {
+        Some((input_text, output_text)) => (input_text, Some(output_text)),
+        None => (text, None),
+    };
+    let mut operands = split_list(input_text)
+        .into_iter()
+        .map(|t| parse_operand(t, number))
+        .collect::<Result<Vec<_>, _>>()?;
+    if operands.len() != inputs {
+        return error(
+            number,
+            format!("Expected {inputs} input operands, got {}", operands.len()),
+        );
+    }
+    match (writes, output_text) {
+        (true, Some(output_text)) => {
+            let output = parse_operand(output_text, number)?;
+            if output.mode == ParameterMode::Immediate {
+                return error(number, "Output operand cannot be immediate");
+            }
+            operands.push(output);
+        }
+        (true, None) => return error(number, "Missing output operand after ->"),
+        (false, Some(_)) => return error(number, "Unexpected output operand"),
+        (false, None) => (),
+    }
+    Ok(operands)
+}
+
+fn mode_digit(mode: ParameterMode) -> i64 {
+    match mode {
+        ParameterMode::Position => 0,
+        ParameterMode::Immediate => 1,
+        ParameterMode::Relative => 2,
+    }
+}
+
+fn resolve(expr: &Expr, symbols: &HashMap<&str, i64>, number: usize) -> Result<i64, AsmError> {
+    match &expr.symbol {
+        None => Ok(expr.offset),
+        Some(symbol) => match symbols.get(symbol.as_str()) {
+            Some(value) => Ok(value + expr.offset),
+            None => error(number, format!("Unknown symbol [{symbol}]")),
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn image(source: &str) -> Vec<i64> {
+        assemble(source).unwrap().to_vec()
+    }
+
+    fn error_at(source: &str) -> (usize, String) {
+        let error = assemble(source).unwrap_err();
+        (error.line, error.message)
+    }
+
+    #[test]
+    fn encodes_modes_labels_and_data() {
+        assert_eq!(
+            image("ADD [a], #5 -> [rb-2]\nJT 1, end\nend: HALT\na: DATA 7, end"),
+            [21001, 8, 5, -2, 1105, 1, 7, 99, 7, 7]
+        );
+        assert_eq!(image(".const N = 3\nOUT #N+1\n.zero 2"), [104, 4, 0, 0]);
+        assert_eq!(image(".ascii \"A\\n\""), [65, 10]);
+    }
+
+    #[test]
+    fn macros_have_local_labels() {
+        let source = "
+.macro SKIP a
+    JT #1, after
+    OUT a
+after:
+.endm
+    SKIP #1
+    SKIP #2
+";
+        assert_eq!(image(source), [1105, 1, 5, 104, 1, 1105, 1, 10, 104, 2]);
+    }
+
+    #[test]
+    fn reports_errors_with_lines() {
+        assert_eq!(
+            error_at("HALT\nFOO 1"),
+            (2, "Unknown mnemonic [FOO]".to_string())
+        );
+        assert_eq!(error_at("OUT [nowhere]").1, "Unknown symbol [nowhere]");
+        assert_eq!(
+            error_at("a: HALT\na: HALT"),
+            (2, "Duplicate symbol [a]".to_string())
+        );
+        assert_eq!(
+            error_at("ADD 1, 2 -> #3").1,
+            "Output operand cannot be immediate"
+        );
+        assert_eq!(error_at("ADD 1, 2").1, "Missing output operand after ->");
+        assert_eq!(error_at("\n.macro M\nHALT").0, 2);
+        assert_eq!(error_at(".endm").1, ".endm without .macro");
+        assert_eq!(
+            error_at(".macro M\nM\n.endm\nM").1,
+            "Macro [M] nested too deep"
+        );
+        assert_eq!(
+            assemble("OUT 1 -> [2]").unwrap_err().to_string(),
+            "Assembly error at line [1]: Unexpected output operand"
+        );
+    }
+}
@@ -0,0 +1,111 @@
+use super::Instruction;
+
+/// Addresses above this are decoded on every visit instead of cached.
+const MAX_CACHED_ADDRESS: usize = 1 << 20;
+
+/// How a [`Machine`](super::Machine) fetches instructions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Backend {
+    /// Decodes the instruction at the instruction pointer on every step.
+    #[default]
+    Interpreter,
+    /// Keeps decoded instructions per address. Writes made by the program drop
+    /// the instructions covering the written cell, any other change to the
+    /// memory (debugger edits, `machine.memory[a] = v`) drops the whole cache.
+    Cached,
+}
+
+/// Decoded instructions by address.
+#[derive(Default, Clone)]
+pub(super) struct DecodeCache {
+    entries: Vec<Option<Instruction>>,
+    /// Memory generation the entries were decoded against.
+    pub(super) generation: u64,
+}
+
+impl DecodeCache {
+    pub(super) fn new(generation: u64) -> Self {
+        DecodeCache {
+            entries: Vec::new(),
+            generation,
+        }
+    }
+
+    pub(super) fn get(&self, address: usize) -> Option<Instruction> {
+        self.entries.get(address).copied().flatten()
+    }
+
+    pub(super) fn insert(&mut self, address: usize, instruction: Instruction) {
+        if address >= MAX_CACHED_ADDRESS {
+            return;
+        }
+        if address >= self.entries.len() {
+            self.entries.resize(address + 1, None);
+        }
+        self.entries[address] = Some(instruction);
+    }
+
+    /// Drops the instructions that include `address`.
+    pub(super) fn invalidate(&mut self, address: usize) {
+        let start = address.saturating_sub(3);
+        let end = (address + 1).min(self.entries.len());
+        for entry in self.entries.iter_mut().take(end).skip(start) {
+            *entry = None;
+        }
+    }
+
+    pub(super) fn clear(&mut self, generation: u64) {
+        self.entries.clear();
+        self.generation = generation;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::{ExecuteResult, Program};
+
+    #[test]
+    fn invalidates_covering_instructions() {
+        let mut cache = DecodeCache::new(0);
+        let halt = Instruction::try_from(&[99][..]).unwrap();
+        for address in 0..8 {
+            cache.insert(address, halt);
+        }
+        cache.insert(MAX_CACHED_ADDRESS, halt);
+
+        cache.invalidate(5);
+        let cached: Vec<bool> = (0..8).map(|a| cache.get(a).is_some()).collect();
+        assert_eq!(cached, [true, true, false, false, false, false, true, true]);
+        assert!(cache.get(MAX_CACHED_ADDRESS).is_none());
+    }
+
+    #[test]
+    fn sees_self_modifying_code() {
+        // OUT #1, then increments the OUT operand and loops while it is below 3
+        let program: Program = "104,1,1001,1,1,1,1007,1,3,15,1005,15,0,99,0,0"
+            .parse()
+            .unwrap();
+        for backend in [Backend::Interpreter, Backend::Cached] {
+            let mut machine = program.machine().with_backend(backend);
+            assert_eq!(machine.execute(), ExecuteResult::Halt(104));
+            assert_eq!(machine.output(), &[1, 2], "{backend:?}");
+        }
+    }
+
+    #[test]
+    fn sees_outside_memory_edits() {
+        // reads a value, outputs 5 and starts over
+        let program: Program = "3,7,104,5,1105,1,0,0".parse().unwrap();
+        let mut machine = program
+            .machine_with_input(vec![0])
+            .with_backend(Backend::Cached);
+        assert_eq!(machine.execute(), ExecuteResult::WaitingInput);
+
+        machine.memory[3] = 6;
+        machine.push_input(0);
+        assert_eq!(machine.execute(), ExecuteResult::WaitingInput);
+        assert_eq!(machine.output(), &[5, 6]);
+        assert_eq!(machine.backend(), Backend::Cached);
+    }
+}
@@ -0,0 +1,305 @@
+//! Conformance suite of the VM: every intcode example in `data/examples`,
+//! plus one program per opcode and parameter mode combination checked
+//! against the rules of the puzzle statements.
+
+use super::amplifier::{best, Topology};
+use super::asm::assemble;
+use super::coverage::{Coverage, Variant};
+use super::{ExecuteResult, IntcodeError, Machine, ParameterMode, Program};
+use crate::template::{read_file, read_file_part, Day};
+
+/// Example file as `(day, part)` (part 0 for a day with a single example), input, expected output and halt value.
+type Example = ((u8, u8), &'static [i64], &'static [i64], Option<i64>);
+
+const EXAMPLES: &[Example] = &[
+    ((2, 1), &[], &[], Some(3500)),
+    ((2, 2), &[], &[], Some(30)),
+    ((2, 3), &[], &[], Some(1)),
+    ((5, 1), &[], &[], Some(3500)),
+    ((5, 2), &[], &[], Some(30)),
+    ((5, 3), &[], &[], Some(1101)),
+    ((5, 4), &[], &[], Some(1002)),
+    ((5, 5), &[1337], &[1337], Some(1337)),
+    ((5, 6), &[8], &[1], None),
+    ((5, 6), &[9], &[0], None),
+    ((5, 7), &[7], &[1], None),
+    ((5, 7), &[9], &[0], None),
+    ((5, 8), &[8], &[1], None),
+    ((5, 8), &[9], &[0], None),
+    ((5, 9), &[7], &[1], None),
+    ((5, 9), &[9], &[0], None),
+    ((5, 10), &[0], &[0], None),
+    ((5, 10), &[7], &[1], None),
+    ((5, 11), &[0], &[0], None),
+    ((5, 11), &[7], &[1], None),
+    ((5, 12), &[7], &[999], None),
+    ((5, 12), &[8], &[1000], None),
+    ((5, 12), &[9], &[1001], None),
+    ((9, 2), &[], &[1219070632396864], None),
+    ((9, 3), &[], &[1125899906842624], None),
+    ((9, 4), &[1337], &[1337], None),
+    ((9, 5), &[], &[-1], None),
+    ((9, 6), &[], &[1], None),
+    ((9, 7), &[], &[109], None),
+    ((9, 8), &[], &[204], None),
+    ((9, 9), &[], &[204], None),
+    ((9, 10), &[], &[204], None),
+    ((9, 11), &[1337], &[1337], None),
+    ((9, 12), &[1337], &[1337], None),
+    (
+        (11, 0),
+        &[0, 0, 0, 0, 1, 0, 0],
+        &[1, 0, 0, 0, 1, 0, 1, 0, 0, 1, 1, 0, 1, 0],
+        Some(3),
+    ),
+    ((13, 1), &[], &[1, 2, 3, 6, 5, 4], Some(104)),
+    ((13, 2), &[], &[7, 0, 1337], Some(104)),
+    ((13, 3), &[], &[-1, 0, 1337], Some(2)),
+];
+
+/// Day 7 examples with the phases of the puzzle statement and the thruster signal.
+const AMPLIFIERS: &[(u8, &[i64], i64)] = &[
+    (1, &[4, 3, 2, 1, 0], 43210),
+    (2, &[0, 1, 2, 3, 4], 54321),
+    (3, &[1, 0, 4, 3, 2], 65210),
+    (11, &[4, 3, 2, 1, 0], 43210),
+    (21, &[0, 1, 2, 3, 4], 54321),
+    (31, &[1, 0, 4, 3, 2], 65210),
+    (4, &[9, 8, 7, 6, 5], 139629729),
+    (5, &[9, 7, 8, 5, 6], 18216),
+];
+
+fn example(day: u8, part: u8) -> Program {
+    let day = Day::new(day).unwrap();
+    let source = match part {
+        0 => read_file("examples", day),
+        _ => read_file_part("examples", day, part),
+    };
+    // day 7 examples list phase settings after the program
+    let firmware = source.lines().next().unwrap();
+    firmware.parse().unwrap()
+}
+
+fn halt_value(machine: &mut Machine) -> i64 {
+    match machine.execute() {
+        ExecuteResult::Halt(value) => value,
+        result => panic!("Unexpected result [{result:?}]!"),
+    }
+}
+
+#[test]
+fn examples() {
+    for &((day, part), input, output, halt) in EXAMPLES {
+        let mut machine = example(day, part).machine_with_input(input.to_vec());
+        let value = halt_value(&mut machine);
+        assert_eq!(machine.output(), output, "{day:02}-{part} with {input:?}");
+        if let Some(halt) = halt {
+            assert_eq!(value, halt, "{day:02}-{part} halt value");
+        }
+    }
+}
+
+#[test]
+fn quine() {
+    let program = example(9, 1);
+    let mut machine = program.machine();
+    halt_value(&mut machine);
+    assert_eq!(machine.output(), program.image());
+}
+
+#[test]
+fn amplifiers() {
+    for &(part, phases, signal) in AMPLIFIERS {
+        let program = example(7, part);
+        let mut amps: Vec<Machine> = phases
+            .iter()
+            .map(|&phase| program.machine_with_input(vec![phase]))
+            .collect();
+
+        // feeds the signal around until the last amp halts
+        let mut value = 0;
+        let mut halted = false;
+        while !halted {
+            for amp in &mut amps {
+                amp.push_input(value);
+                let result = amp.execute();
+                assert!(
+                    matches!(result, ExecuteResult::Halt(_) | ExecuteResult::WaitingInput),
+                    "07-{part}: {result:?}"
+                );
+                value = amp.take_output().pop().unwrap_or(value);
+                halted = matches!(result, ExecuteResult::Halt(_));
+            }
+        }
+        assert_eq!(value, signal, "07-{part}");
+
+        // the puzzle phases are the best permutation
+        let topology = match part {
+            4 | 5 => Topology::feedback(phases.len()),
+            _ => Topology::chain(phases.len()),
+        };
+        let mut sorted = phases.to_vec();
+        sorted.sort();
+        let evaluation = best(&program, &topology, &sorted).unwrap();
+        assert_eq!(
+            (&evaluation.phases[..], evaluation.signal),
+            (phases, signal)
+        );
+    }
+}
+
+/// Operand reading `value`: `[a]`/`[b]` cells, immediate, or `rb+index`
+/// cells of the frame.
+fn read_operand(mode: ParameterMode, index: usize, value: &str) -> String {
+    match mode {
+        ParameterMode::Position => format!("[{}]", ["a", "b"][index]),
+        ParameterMode::Immediate => format!("#{value}"),
+        ParameterMode::Relative => format!("[rb+{index}]"),
+    }
+}
+
+fn write_operand(mode: ParameterMode) -> &'static str {
+    match mode {
+        ParameterMode::Position => "[out]",
+        _ => "[rb+2]",
+    }
+}
+
+/// Program executing `variant` on `values`, then outputting its result.
+///
+/// Jumps go to `taken`, outputting 1, or fall through to output 0. The
+/// frame the relative base points to holds the values, the result cell
+/// and the cells `ARB` moves to.
+fn variant_program(variant: &Variant, values: [&str; 2]) -> Program {
+    let reads: Vec<String> = variant
+        .modes
+        .iter()
+        .enumerate()
+        .filter(|(index, _)| *index < 2)
+        .map(|(index, &mode)| read_operand(mode, index, values[index]))
+        .collect();
+    let write = variant.modes.last().map(|&mode| write_operand(mode));
+
+    let body = match variant.opcode {
+        1 | 2 | 7 | 8 => {
+            let mnemonic = ["", "ADD", "MUL", "", "", "", "", "LT", "EQ"][variant.opcode as usize];
+            let write = write.unwrap();
+            format!(
+                "{mnemonic} {}, {} -> {write}\nOUT {write}",
+                reads[0], reads[1]
+            )
+        }
+        3 => format!("IN -> {0}\nOUT {0}", write.unwrap()),
+        4 => format!("OUT {}", reads[0]),
+        5 | 6 => {
+            let mnemonic = if variant.opcode == 5 { "JT" } else { "JF" };
+            format!("{mnemonic} {}, {}\nOUT #0", reads[0], reads[1])
+        }
+        9 => format!("ARB {}\nOUT [rb+0]", reads[0]),
+        _ => String::new(),
+    };
+    let source = format!(
+        "
+    ARB #frame
+    {body}
+    HALT
+taken:
+    OUT #1
+    HALT
+a: DATA {0}
+b: DATA {1}
+out: DATA -1
+frame: DATA {0}, {1}, -1, 10, 11, 12
+",
+        values[0], values[1]
+    );
+    assemble(&source).unwrap_or_else(|error| panic!("{variant}: {error}"))
+}
+
+/// Output the puzzle rules give for `variant` on `values`, with `IN` reading 42.
+fn expected_output(variant: &Variant, values: [i64; 2]) -> Vec<i64> {
+    let [a, b] = values;
+    let frame = [a, b, -1, 10, 11, 12];
+    match variant.opcode {
+        1 => vec![a.wrapping_add(b)],
+        2 => vec![a.wrapping_mul(b)],
+        3 => vec![42],
+        4 => vec![a],
+        5 => vec![(a != 0) as i64],
+        6 => vec![(a == 0) as i64],
+        7 => vec![(a < b) as i64],
+        8 => vec![(a == b) as i64],
+        9 => vec![frame[a as usize]],
+        _ => vec![],
+    }
+}
+
+#[test]
+fn every_opcode_and_mode() {
+    let coverage = Coverage::new();
+    for variant in Variant::all() {
+        let value_pairs: &[[i64; 2]] = match variant.opcode {
+            1 | 2 | 7 | 8 => &[[6, 7], [7, 7], [-3, 2], [i64::MAX, 2]],
+            5 | 6 => &[[0, 0], [5, 0], [-1, 0]],
+            9 => &[[1, 0], [3, 0], [5, 0]],
+            _ => &[[42, 0], [-5, 0]],
+        };
+        for &[a, b] in value_pairs {
+            let jump = matches!(variant.opcode, 5 | 6);
+            let (a_text, b_text) = (a.to_string(), b.to_string());
+            let values = [a_text.as_str(), if jump { "taken" } else { &b_text }];
+            let program = variant_program(&variant, values);
+
+            let mut machine = program.machine_with_input(vec![42]);
+            machine.set_tracer(coverage.tracer(&variant.to_string(), &program));
+            assert_eq!(halt_value(&mut machine), program.image()[0], "{variant}");
+            assert_eq!(
+                machine.output(),
+                &expected_output(&variant, [a, b]),
+                "{variant} on {a}, {b}"
+            );
+        }
+    }
+    assert_eq!(coverage.missing(), vec![]);
+}
+
+#[test]
+fn faults() {
+    let cases = [
+        (
+            "11101,1,1,5,99",
+            IntcodeError::ImmediateModeWrite {
+                instruction_pointer: 0,
+            },
+        ),
+        (
+            "42,0,0,0",
+            IntcodeError::UnknownOpcode {
+                opcode: 42,
+                instruction_pointer: 0,
+            },
+        ),
+        (
+            "4,-1,99",
+            IntcodeError::ReadOutOfBounds {
+                address: -1,
+                instruction_pointer: 0,
+            },
+        ),
+        (
+            "109,-5,204,0,99",
+            IntcodeError::NegativeRelativeAddress {
+                address: -5,
+                instruction_pointer: 2,
+            },
+        ),
+    ];
+    for (source, error) in cases {
+        let program: Program = source.parse().unwrap();
+        assert_eq!(
+            program.machine().execute(),
+            ExecuteResult::Error(error),
+            "{source}"
+        );
+    }
+}
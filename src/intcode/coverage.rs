@@ -0,0 +1,326 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::{Display, Write};
+use std::sync::{Arc, Mutex};
+
+use itertools::Itertools;
+
+use super::analysis::analyze;
+use super::asm::opcode_shape;
+use super::trace::{TraceStep, Tracer};
+use super::{Instruction, ParameterMode, Program};
+
+const MNEMONICS: [&str; 10] = [
+    "ADD", "MUL", "IN", "OUT", "JT", "JF", "LT", "EQ", "ARB", "HALT",
+];
+
+const READ_MODES: [ParameterMode; 3] = [
+    ParameterMode::Position,
+    ParameterMode::Immediate,
+    ParameterMode::Relative,
+];
+
+/// Written parameters cannot be immediate.
+const WRITE_MODES: [ParameterMode; 2] = [ParameterMode::Position, ParameterMode::Relative];
+
+/// Opcode with the modes of its parameters, in operand order.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Variant {
+    pub opcode: i64,
+    pub modes: Vec<ParameterMode>,
+}
+
+impl Variant {
+    fn of(instruction: &Instruction, opcode: i64) -> Self {
+        let parameters = instruction
+            .read_parameters()
+            .into_iter()
+            .chain(instruction.write_parameter());
+        Variant {
+            opcode,
+            modes: parameters.map(|parameter| parameter.mode).collect(),
+        }
+    }
+
+    /// Every variant the machine can execute.
+    pub fn all() -> Vec<Variant> {
+        MNEMONICS
+            .iter()
+            .filter_map(|mnemonic| opcode_shape(mnemonic))
+            .flat_map(|(opcode, reads, writes)| {
+                let mut choices = vec![&READ_MODES[..]; reads];
+                if writes {
+                    choices.push(&WRITE_MODES);
+                }
+                let combinations = choices.into_iter().fold(vec![vec![]], |prefixes, modes| {
+                    prefixes
+                        .iter()
+                        .flat_map(|prefix: &Vec<ParameterMode>| {
+                            modes.iter().map(|mode| [&prefix[..], &[*mode]].concat())
+                        })
+                        .collect()
+                });
+                combinations
+                    .into_iter()
+                    .map(move |modes| Variant { opcode, modes })
+            })
+            .collect()
+    }
+}
+
+impl Display for Variant {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mnemonic = MNEMONICS
+            .iter()
+            .find(|m| opcode_shape(m).is_some_and(|(opcode, _, _)| opcode == self.opcode))
+            .unwrap_or(&"???");
+        let modes = self
+            .modes
+            .iter()
+            .map(|mode| match mode {
+                ParameterMode::Position => "pos",
+                ParameterMode::Immediate => "imm",
+                ParameterMode::Relative => "rel",
+            })
+            .join(",");
+        match modes.is_empty() {
+            true => write!(f, "{mnemonic}"),
+            false => write!(f, "{mnemonic} {modes}"),
+        }
+    }
+}
+
+/// Addresses of one program hit by its runs.
+#[derive(Debug, Clone, Default)]
+pub struct ProgramCoverage {
+    /// Instructions statically reachable from address 0.
+    pub code: BTreeSet<usize>,
+    /// Executed instruction addresses with their hit counts.
+    pub executed: BTreeMap<usize, u64>,
+    pub runs: usize,
+}
+
+impl ProgramCoverage {
+    /// Reachable instructions never executed.
+    pub fn missed(&self) -> Vec<usize> {
+        self.code
+            .iter()
+            .filter(|address| !self.executed.contains_key(address))
+            .copied()
+            .collect()
+    }
+
+    /// Share of reachable instructions executed, in percent.
+    pub fn percent(&self) -> f64 {
+        let hit = self.code.len() - self.missed().len();
+        100.0 * hit as f64 / self.code.len().max(1) as f64
+    }
+}
+
+#[derive(Default)]
+struct Data {
+    variants: BTreeMap<Variant, u64>,
+    programs: BTreeMap<String, ProgramCoverage>,
+}
+
+/// Coverage of the machine across runs, clones share the same data.
+///
+/// Each run gets its own [`CoverageTracer`] naming the program, runs of the
+/// same program add up.
+///
+/// # Examples
+/// ```
+/// use advent_of_code::intcode::*;
+/// use advent_of_code::intcode::coverage::*;
+///
+/// // outputs 1 if the input is 8, else 0
+/// let program: Program = "3,9,8,9,10,9,4,9,99,-1,8".parse().unwrap();
+/// let coverage = Coverage::new();
+/// for input in [8, 5] {
+///     let mut machine = program.machine_with_input(vec![input]);
+///     machine.set_tracer(coverage.tracer("05-1", &program));
+///     machine.execute();
+/// }
+///
+/// assert_eq!(coverage.hits(&Variant::all()[0]), 0);
+/// assert_eq!(coverage.variants().len(), 4);
+/// assert_eq!(coverage.missing().len(), Variant::all().len() - 4);
+/// let program_coverage = coverage.program("05-1").unwrap();
+/// assert_eq!((program_coverage.runs, program_coverage.percent()), (2, 100.0));
+/// assert!(coverage.report().starts_with("Opcode/mode coverage: 4 of 99 variants"));
+/// ```
+#[derive(Clone, Default)]
+pub struct Coverage {
+    data: Arc<Mutex<Data>>,
+}
+
+impl Coverage {
+    pub fn new() -> Self {
+        Coverage::default()
+    }
+
+    /// Tracer recording one run of `program` under `name`.
+    pub fn tracer(&self, name: &str, program: &Program) -> CoverageTracer {
+        let mut data = self.data.lock().unwrap();
+        let coverage = data.programs.entry(name.to_string()).or_default();
+        if coverage.runs == 0 {
+            coverage.code = analyze(program)
+                .blocks
+                .values()
+                .flat_map(|block| block.instructions.iter().map(|(address, _)| *address))
+                .collect();
+        }
+        coverage.runs += 1;
+        CoverageTracer {
+            coverage: self.clone(),
+            name: name.to_string(),
+        }
+    }
+
+    /// Executed variants with their counts.
+    pub fn variants(&self) -> BTreeMap<Variant, u64> {
+        self.data.lock().unwrap().variants.clone()
+    }
+
+    pub fn hits(&self, variant: &Variant) -> u64 {
+        let data = self.data.lock().unwrap();
+        data.variants.get(variant).copied().unwrap_or(0)
+    }
+
+    /// Variants no run executed.
+    pub fn missing(&self) -> Vec<Variant> {
+        let data = self.data.lock().unwrap();
+        Variant::all()
+            .into_iter()
+            .filter(|variant| !data.variants.contains_key(variant))
+            .collect()
+    }
+
+    pub fn program(&self, name: &str) -> Option<ProgramCoverage> {
+        self.data.lock().unwrap().programs.get(name).cloned()
+    }
+
+    /// Executed variants, missing variants and per-program address coverage.
+    pub fn report(&self) -> String {
+        let variants = self.variants();
+        let missing = self.missing();
+        let all = Variant::all().len();
+
+        let mut report = String::new();
+        writeln!(
+            report,
+            "Opcode/mode coverage: {} of {all} variants",
+            all - missing.len()
+        )
+        .unwrap();
+        for (variant, count) in &variants {
+            writeln!(report, "  {:<16} {count:>10}", variant.to_string()).unwrap();
+        }
+        if !missing.is_empty() {
+            writeln!(report, "Not covered:").unwrap();
+            for chunk in &missing.iter().chunks(6) {
+                writeln!(report, "  {}", chunk.map(|v| v.to_string()).join(", ")).unwrap();
+            }
+        }
+
+        writeln!(report, "Programs:").unwrap();
+        for (name, program) in &self.data.lock().unwrap().programs {
+            let missed = program.missed();
+            writeln!(
+                report,
+                "  {name:<12} {:>4}/{:<4} instructions {:>5.1}%  {} runs",
+                program.code.len() - missed.len(),
+                program.code.len(),
+                program.percent(),
+                program.runs
+            )
+            .unwrap();
+            if !missed.is_empty() {
+                let missed = missed.iter().map(|a| format!("{a:04}")).join(" ");
+                writeln!(report, "      missed: {missed}").unwrap();
+            }
+        }
+        report
+    }
+}
+
+impl Display for Coverage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.report())
+    }
+}
+
+/// Records executed instructions into a [`Coverage`].
+pub struct CoverageTracer {
+    coverage: Coverage,
+    name: String,
+}
+
+impl Tracer for CoverageTracer {
+    fn trace(&mut self, step: &TraceStep) {
+        let Some(instruction) = step.instruction() else {
+            return;
+        };
+        let variant = Variant::of(&instruction, step.words[0] % 100);
+
+        let mut data = self.coverage.data.lock().unwrap();
+        *data.variants.entry(variant).or_default() += 1;
+        let program = data.programs.entry(self.name.clone()).or_default();
+        *program
+            .executed
+            .entry(step.instruction_pointer)
+            .or_default() += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn enumerates_variants() {
+        let all = Variant::all();
+        // 4 three-parameter opcodes, 2 jumps, IN, OUT, ARB and HALT
+        assert_eq!(all.len(), 4 * 18 + 2 * 9 + 2 + 3 + 3 + 1);
+        assert_eq!(all.iter().unique().count(), all.len());
+        // written parameters are never immediate
+        assert!(!all.iter().any(|v| matches!(v.opcode, 1 | 2 | 3 | 7 | 8)
+            && v.modes.last() == Some(&ParameterMode::Immediate)));
+
+        let variant = Variant {
+            opcode: 1,
+            modes: vec![
+                ParameterMode::Immediate,
+                ParameterMode::Position,
+                ParameterMode::Relative,
+            ],
+        };
+        assert_eq!(variant.to_string(), "ADD imm,pos,rel");
+        assert_eq!(
+            Variant {
+                opcode: 99,
+                modes: vec![]
+            }
+            .to_string(),
+            "HALT"
+        );
+    }
+
+    #[test]
+    fn tracks_missed_addresses() {
+        // outputs 1 unless the input is 0
+        let program: Program = "3,11,1005,11,7,99,99,104,1,99,0,0".parse().unwrap();
+        let coverage = Coverage::new();
+        let mut machine = program.machine_with_input(vec![0]);
+        machine.set_tracer(coverage.tracer("jump", &program));
+        machine.execute();
+
+        let jump = coverage.program("jump").unwrap();
+        assert_eq!(jump.code, [0, 2, 5, 7, 9].into());
+        assert_eq!(jump.missed(), [7, 9]);
+        assert_eq!(jump.percent(), 60.0);
+        let jt = Variant::of(&Instruction::try_from(&[1005, 11, 7][..]).unwrap(), 5);
+        assert_eq!(coverage.hits(&jt), 1);
+        assert!(coverage.report().contains("      missed: 0007 0009\n"));
+        assert!(coverage.program("other").is_none());
+    }
+}
@@ -0,0 +1,730 @@
+use std::collections::BTreeSet;
+use std::fmt::Display;
+
+use super::history::{History, LastWrite, Undo};
+use super::trace::MemoryWrite;
+use super::watch::diff;
+use super::{ExecuteResult, Instruction, IntcodeError, Machine, Snapshot};
+
+/// Instructions executed by `continue` style commands before giving up.
+const DEFAULT_STEP_LIMIT: usize = 100_000_000;
+
+/// Instructions before a jump searched for its pushed return address.
+const CALL_SETUP_STEPS: usize = 2;
+
+/// Why the debugger handed control back.
+#[derive(Debug, Clone, PartialEq)]
+pub enum StopReason {
+    Step,
+    Breakpoint(usize),
+    OpcodeBreakpoint(i64),
+    Watchpoint {
+        address: usize,
+        old: i64,
+        new: i64,
+    },
+    Output(i64),
+    WaitingInput,
+    Halt(i64),
+    Error(IntcodeError),
+    StepLimit,
+    /// Stepping back found no older recorded instruction.
+    HistoryStart,
+}
+
+impl Display for StopReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StopReason::Step => write!(f, "stepped"),
+            StopReason::Breakpoint(address) => write!(f, "breakpoint at {address:04}"),
+            StopReason::OpcodeBreakpoint(opcode) => write!(f, "opcode breakpoint on {opcode}"),
+            StopReason::Watchpoint { address, old, new } => {
+                write!(f, "watchpoint [{address}]: {old} -> {new}")
+            }
+            StopReason::Output(value) => write!(f, "output {value}"),
+            StopReason::WaitingInput => write!(f, "waiting for input"),
+            StopReason::Halt(result) => write!(f, "halted with {result}"),
+            StopReason::Error(error) => write!(f, "{error}"),
+            StopReason::StepLimit => write!(f, "step limit reached"),
+            StopReason::HistoryStart => write!(f, "reached the start of the history"),
+        }
+    }
+}
+
+/// Debugger front-end around a [`Machine`].
+///
+/// Executed instructions are recorded in a [`History`] installed as the
+/// machine tracer, so execution can also run backwards.
+///
+/// # Examples
+/// ```
+/// use advent_of_code::intcode::*;
+/// use advent_of_code::intcode::debugger::*;
+///
+/// let program: Program = "3,9,1001,9,5,9,4,9,99,0".parse().unwrap();
+/// let mut debugger = Debugger::new(program.machine());
+/// debugger.add_watchpoint(9);
+/// assert_eq!(debugger.resume(), StopReason::WaitingInput);
+/// debugger.inject_input(&[37]);
+/// assert_eq!(debugger.resume(), StopReason::Watchpoint { address: 9, old: 0, new: 37 });
+/// assert_eq!(debugger.continue_until_output(), StopReason::Watchpoint { address: 9, old: 37, new: 42 });
+/// assert_eq!(debugger.continue_until_output(), StopReason::Output(42));
+///
+/// assert_eq!(debugger.last_write(9).unwrap().instruction_pointer, 2);
+/// assert_eq!(debugger.run_back_to_breakpoint(), StopReason::Watchpoint { address: 9, old: 37, new: 42 });
+/// assert_eq!(debugger.machine.instruction_pointer, 2);
+/// assert_eq!(debugger.run_back_to_breakpoint(), StopReason::Watchpoint { address: 9, old: 0, new: 37 });
+/// assert_eq!(debugger.step_back(), StopReason::HistoryStart);
+/// assert_eq!(debugger.machine.input(), &[37]);
+/// ```
+pub struct Debugger {
+    pub machine: Machine,
+    pub breakpoints: BTreeSet<usize>,
+    pub opcode_breakpoints: BTreeSet<i64>,
+    pub watchpoints: BTreeSet<usize>,
+    pub step_limit: usize,
+    history: History,
+    saved: Option<Snapshot>,
+    last_command: String,
+}
+
+impl Debugger {
+    pub fn new(mut machine: Machine) -> Self {
+        let history = History::new(History::DEFAULT_CAPACITY);
+        machine.set_tracer(history.clone());
+        Debugger {
+            machine,
+            breakpoints: BTreeSet::new(),
+            opcode_breakpoints: BTreeSet::new(),
+            watchpoints: BTreeSet::new(),
+            step_limit: DEFAULT_STEP_LIMIT,
+            history,
+            saved: None,
+            last_command: String::new(),
+        }
+    }
+
+    /// Keeps the last `capacity` instructions for stepping back, dropping
+    /// the current history.
+    pub fn with_history_capacity(mut self, capacity: usize) -> Self {
+        self.history = History::new(capacity);
+        self.machine.set_tracer(self.history.clone());
+        self
+    }
+
+    pub fn add_breakpoint(&mut self, address: usize) {
+        self.breakpoints.insert(address);
+    }
+
+    /// Breaks before any instruction with the given opcode (`1`..`9`, `99`).
+    pub fn add_opcode_breakpoint(&mut self, opcode: i64) {
+        self.opcode_breakpoints.insert(opcode);
+    }
+
+    pub fn add_watchpoint(&mut self, address: usize) {
+        self.watchpoints.insert(address);
+    }
+
+    /// Writes memory, recorded in the history so stepping back reverts it.
+    pub fn set_memory(&mut self, address: usize, value: i64) {
+        let old = self.machine.memory.get(address);
+        self.machine.memory.set(address, value);
+        self.history.record(Undo {
+            instruction_pointer: self.machine.instruction_pointer,
+            relative_base: self.machine.relative_base,
+            write: Some(MemoryWrite {
+                address,
+                old,
+                new: value,
+            }),
+            input: None,
+            output: None,
+        });
+    }
+
+    pub fn inject_input(&mut self, values: &[i64]) {
+        self.machine.extend_input(values.iter().copied());
+    }
+
+    /// Executes exactly one instruction.
+    pub fn step(&mut self) -> StopReason {
+        let watched = self.watched_write();
+        let output_len = self.machine.output().len();
+
+        match self.machine.execute_step() {
+            ExecuteResult::Continue => (),
+            ExecuteResult::WaitingInput => return StopReason::WaitingInput,
+            ExecuteResult::Halt(result) => return StopReason::Halt(result),
+            ExecuteResult::Error(error) => return StopReason::Error(error),
+            limit => unreachable!("{limit:?} from a single step"),
+        }
+
+        if let Some((address, old)) = watched {
+            return StopReason::Watchpoint {
+                address,
+                old,
+                new: self.machine.memory.get(address),
+            };
+        }
+        if self.machine.output().len() > output_len {
+            return StopReason::Output(*self.machine.output().last().unwrap());
+        }
+        StopReason::Step
+    }
+
+    /// Steps, running a call through until execution returns to the next
+    /// instruction with the relative base it had before the call.
+    ///
+    /// A call is a taken jump shortly after a write of the jump's
+    /// fall-through address, the pushed return address, directly before it
+    /// or before an `ARB` as in `CALL` of the assembler. Other jumps are
+    /// single steps.
+    pub fn step_over(&mut self) -> StopReason {
+        let Ok(instruction) = self.machine.current_instruction() else {
+            return self.step();
+        };
+        let next = self.machine.instruction_pointer + instruction.get_size() as usize;
+        let push = self
+            .history
+            .recent(CALL_SETUP_STEPS)
+            .into_iter()
+            .find(|undo| undo.write.is_some_and(|write| write.new == next as i64));
+
+        let reason = self.step();
+        let Some(push) = push else {
+            return reason;
+        };
+        if !matches!(
+            instruction,
+            Instruction::Jit { .. } | Instruction::Jif { .. }
+        ) || self.machine.instruction_pointer == next
+        {
+            return reason;
+        }
+        self.run_until(|machine| {
+            machine.instruction_pointer == next && machine.relative_base == push.relative_base
+        })
+    }
+
+    /// Runs until a breakpoint, watchpoint, halt, error or missing input.
+    pub fn resume(&mut self) -> StopReason {
+        self.run_until(|_| false)
+    }
+
+    /// Runs like [`Debugger::resume`], also stopping after the next output.
+    pub fn continue_until_output(&mut self) -> StopReason {
+        let output_len = self.machine.output().len();
+        let reason = self.run_until(|machine| machine.output().len() > output_len);
+        match reason {
+            StopReason::Step => StopReason::Output(*self.machine.output().last().unwrap()),
+            reason => reason,
+        }
+    }
+
+    /// Reverts the last executed instruction.
+    pub fn step_back(&mut self) -> StopReason {
+        match self.history.step_back(&mut self.machine) {
+            Some(_) => StopReason::Step,
+            None => StopReason::HistoryStart,
+        }
+    }
+
+    /// Runs backwards until a breakpoint, or the write to a watched address
+    /// is undone. Stops before the instruction that hit it.
+    pub fn run_back_to_breakpoint(&mut self) -> StopReason {
+        while let Some(undo) = self.history.step_back(&mut self.machine) {
+            if let Some(write) = undo
+                .write
+                .filter(|write| self.watchpoints.contains(&write.address))
+            {
+                return StopReason::Watchpoint {
+                    address: write.address,
+                    old: write.old,
+                    new: write.new,
+                };
+            }
+            if let Some(reason) = self.breakpoint_hit() {
+                return reason;
+            }
+        }
+        StopReason::HistoryStart
+    }
+
+    /// Most recent recorded write to `address`.
+    pub fn last_write(&self, address: usize) -> Option<LastWrite> {
+        self.history.last_write(address)
+    }
+
+    /// Undo log of the executed instructions.
+    pub fn history(&self) -> &History {
+        &self.history
+    }
+
+    fn run_until(&mut self, done: impl Fn(&Machine) -> bool) -> StopReason {
+        for executed in 0..self.step_limit {
+            if executed > 0 {
+                if done(&self.machine) {
+                    return StopReason::Step;
+                }
+                if let Some(reason) = self.breakpoint_hit() {
+                    return reason;
+                }
+            }
+            match self.step() {
+                StopReason::Step | StopReason::Output(_) => (),
+                reason => return reason,
+            }
+        }
+        StopReason::StepLimit
+    }
+
+    fn breakpoint_hit(&self) -> Option<StopReason> {
+        let address = self.machine.instruction_pointer;
+        if self.breakpoints.contains(&address) {
+            return Some(StopReason::Breakpoint(address));
+        }
+        let opcode = self.machine.memory.get(address) % 100;
+        if self.opcode_breakpoints.contains(&opcode) {
+            return Some(StopReason::OpcodeBreakpoint(opcode));
+        }
+        None
+    }
+
+    /// Watched address and its value if the next instruction writes to it.
+    fn watched_write(&self) -> Option<(usize, i64)> {
+        if self.watchpoints.is_empty() {
+            return None;
+        }
+        let instruction = self.machine.current_instruction().ok()?;
+        let result = instruction.write_parameter()?;
+        let address = self.machine.get_output_parameter_address(result).ok()? as usize;
+        self.watchpoints
+            .contains(&address)
+            .then(|| (address, self.machine.memory.get(address)))
+    }
+
+    /// Current instruction as `IP: MNEMONIC operands`.
+    pub fn location(&self) -> String {
+        let ip = self.machine.instruction_pointer;
+        match self.machine.current_instruction() {
+            Ok(instruction) => format!("{ip:04}: {instruction}"),
+            Err(error) => format!("{ip:04}: {error}"),
+        }
+    }
+
+    /// Runs a REPL command line and returns the text to show.
+    ///
+    /// An empty line repeats the previous command.
+    pub fn execute_command(&mut self, line: &str) -> String {
+        let line = if line.trim().is_empty() {
+            self.last_command.clone()
+        } else {
+            line.trim().to_string()
+        };
+        self.last_command = line.clone();
+
+        let (command, arguments) = match line.split_once(char::is_whitespace) {
+            Some((command, arguments)) => (command, arguments.trim()),
+            None => (line.as_str(), ""),
+        };
+
+        match self.try_command(command, arguments) {
+            Ok(text) => text,
+            Err(message) => format!("error: {message}"),
+        }
+    }
+
+    fn try_command(&mut self, command: &str, arguments: &str) -> Result<String, String> {
+        let stopped =
+            |debugger: &Debugger, reason: StopReason| format!("{reason}\n{}", debugger.location());
+
+        Ok(match command {
+            "s" | "step" => {
+                let count = if arguments.is_empty() {
+                    1
+                } else {
+                    parse_number::<usize>(arguments)?
+                };
+                let mut reason = StopReason::Step;
+                for _ in 0..count {
+                    reason = self.step();
+                    if !matches!(reason, StopReason::Step | StopReason::Output(_)) {
+                        break;
+                    }
+                }
+                stopped(self, reason)
+            }
+            "rs" | "rstep" => {
+                let count = if arguments.is_empty() {
+                    1
+                } else {
+                    parse_number::<usize>(arguments)?
+                };
+                let mut reason = StopReason::Step;
+                for _ in 0..count {
+                    reason = self.step_back();
+                    if reason != StopReason::Step {
+                        break;
+                    }
+                }
+                stopped(self, reason)
+            }
+            "rc" | "rcontinue" => {
+                let reason = self.run_back_to_breakpoint();
+                stopped(self, reason)
+            }
+            "who" => {
+                let address = parse_address(arguments, &self.machine)?;
+                match self.last_write(address) {
+                    Some(LastWrite {
+                        instruction_pointer,
+                        write,
+                        steps_ago,
+                    }) => format!(
+                        "[{address}] {} -> {} by {instruction_pointer:04}, {steps_ago} steps ago",
+                        write.old, write.new
+                    ),
+                    None => format!(
+                        "no write to [{address}] in the last {} steps",
+                        self.history.len()
+                    ),
+                }
+            }
+            "n" | "next" => {
+                let reason = self.step_over();
+                stopped(self, reason)
+            }
+            "c" | "continue" => {
+                let reason = self.resume();
+                stopped(self, reason)
+            }
+            "o" | "output" => {
+                let reason = self.continue_until_output();
+                stopped(self, reason)
+            }
+            "b" | "break" => {
+                if let Some(opcode) = arguments.strip_prefix("op ") {
+                    let opcode = parse_opcode(opcode.trim())?;
+                    self.add_opcode_breakpoint(opcode);
+                    format!("breakpoint on opcode {opcode}")
+                } else if arguments.is_empty() {
+                    format!(
+                        "breakpoints: {:?}\nopcode breakpoints: {:?}",
+                        self.breakpoints, self.opcode_breakpoints
+                    )
+                } else {
+                    let address = parse_number(arguments)?;
+                    self.add_breakpoint(address);
+                    format!("breakpoint at {address:04}")
+                }
+            }
+            "db" | "delete" => {
+                if let Some(opcode) = arguments.strip_prefix("op ") {
+                    let opcode = parse_opcode(opcode.trim())?;
+                    self.opcode_breakpoints.remove(&opcode);
+                } else {
+                    self.breakpoints.remove(&parse_number(arguments)?);
+                }
+                "deleted".to_string()
+            }
+            "w" | "watch" => {
+                if arguments.is_empty() {
+                    format!("watchpoints: {:?}", self.watchpoints)
+                } else {
+                    let address = parse_number(arguments)?;
+                    self.add_watchpoint(address);
+                    format!("watching [{address}]")
+                }
+            }
+            "dw" | "unwatch" => {
+                self.watchpoints.remove(&parse_number(arguments)?);
+                "deleted".to_string()
+            }
+            "in" | "input" => {
+                let values = super::parse_values(arguments).map_err(|e| e.to_string())?;
+                self.inject_input(&values);
+                format!("queued {values:?}")
+            }
+            "ascii" => {
+                let values: Vec<i64> = format!("{arguments}\n").chars().map(|c| c as i64).collect();
+                self.inject_input(&values);
+                format!("queued {} values", values.len())
+            }
+            "i" | "info" => {
+                let machine = &self.machine;
+                format!(
+                    "IP: {}\nRB: {}\nINPUT: {:?} (pending)\nOUTPUT: {:?}\n{}",
+                    machine.instruction_pointer,
+                    machine.relative_base,
+                    machine.input(),
+                    machine.output(),
+                    self.location()
+                )
+            }
+            "x" | "mem" => {
+                let mut parts = arguments.split_whitespace();
+                let address = parse_address(parts.next().unwrap_or(""), &self.machine)?;
+                let count = match parts.next() {
+                    Some(count) => parse_number(count)?,
+                    None => 1,
+                };
+                let end = address
+                    .checked_add(count)
+                    .filter(|&end| end <= self.machine.memory.limit())
+                    .ok_or_else(|| format!("[{count}] cells from [{address}] over memory limit"))?;
+                let values: Vec<i64> = (address..end).map(|a| self.machine.memory.get(a)).collect();
+                format!("[{address}]: {values:?}")
+            }
+            "set" => {
+                let mut parts = arguments.split_whitespace();
+                let address = parse_address(parts.next().unwrap_or(""), &self.machine)?;
+                let value = parse_number::<i64>(parts.next().unwrap_or(""))?;
+                if address >= self.machine.memory.limit() {
+                    return Err(format!("address [{address}] over memory limit"));
+                }
+                self.set_memory(address, value);
+                format!("[{address}] = {value}")
+            }
+            "snap" => {
+                self.saved = Some(self.machine.snapshot());
+                "snapshot taken".to_string()
+            }
+            "diff" => {
+                let saved = self.saved.as_ref().ok_or("no snapshot, use `snap` first")?;
+                let changes = diff(saved, &self.machine.snapshot());
+                let mut lines = vec![format!("{} cells changed", changes.len())];
+                lines.extend(changes.iter().map(|change| {
+                    format!(
+                        "[{}]: {} -> {}",
+                        change.address, change.before, change.after
+                    )
+                }));
+                lines.join("\n")
+            }
+            "save" => {
+                if arguments.is_empty() {
+                    return Err("usage: save <file>".to_string());
+                }
+                self.machine.save(arguments).map_err(|e| e.to_string())?;
+                format!("state saved to [{arguments}]")
+            }
+            "load" => {
+                if arguments.is_empty() {
+                    return Err("usage: load <file>".to_string());
+                }
+                let mut machine = Machine::load(arguments).map_err(|e| e.to_string())?;
+                self.history.clear();
+                machine.set_tracer(self.history.clone());
+                self.machine = machine;
+                format!("state loaded from [{arguments}]\n{}", self.location())
+            }
+            "l" | "list" => {
+                let count = if arguments.is_empty() {
+                    5
+                } else {
+                    parse_number(arguments)?
+                };
+                self.list(count)
+            }
+            "h" | "help" => HELP.to_string(),
+            _ => return Err(format!("unknown command [{command}], try `help`")),
+        })
+    }
+
+    /// Decodes `count` instructions sequentially from the instruction pointer.
+    pub fn list(&self, count: usize) -> String {
+        let mut lines = Vec::new();
+        let mut address = self.machine.instruction_pointer;
+        for _ in 0..count {
+            if address >= self.machine.memory.limit() {
+                break;
+            }
+            let words = self.machine.memory.instruction_words(address);
+            let marker = if self.breakpoints.contains(&address) {
+                '*'
+            } else {
+                ' '
+            };
+            match Instruction::try_from(&*words) {
+                Ok(instruction) => {
+                    lines.push(format!("{marker}{address:04}: {instruction}"));
+                    address += instruction.get_size() as usize;
+                }
+                Err(_) => {
+                    lines.push(format!("{marker}{address:04}: DATA {}", words[0]));
+                    address += 1;
+                }
+            }
+        }
+        lines.join("\n")
+    }
+}
+
+const HELP: &str = "\
+s, step [n]        execute n instructions
+n, next            step over calls
+c, continue        run to breakpoint, watchpoint, input or halt
+o, output          continue until the next output
+rs, rstep [n]      revert n instructions
+rc, rcontinue      run backwards to a breakpoint or watched write
+who <addr>         last instruction writing addr
+b, break [addr]    add breakpoint or list breakpoints
+b op <opcode>      break on opcode (number or mnemonic)
+db <addr|op x>     delete breakpoint
+w, watch [addr]    add watchpoint on memory writes or list them
+dw <addr>          delete watchpoint
+in <v1,v2,..>      queue input values
+ascii <text>       queue text and newline as ASCII input
+i, info            instruction pointer, relative base and IO buffers
+x <addr> [n]       examine memory, addr may be rb+k
+set <addr> <v>     write memory
+snap               remember the current memory
+diff               cells changed since `snap`
+save <file>        save the machine state to a file
+load <file>        resume a saved machine state, clears the history
+l, list [n]        list instructions from the instruction pointer
+q, quit            exit";
+
+fn parse_number<T: std::str::FromStr>(text: &str) -> Result<T, String> {
+    text.trim()
+        .parse()
+        .map_err(|_| format!("invalid number [{text}]"))
+}
+
+/// Parses `123`, `rb`, `rb+4` or `rb-2`.
+fn parse_address(text: &str, machine: &Machine) -> Result<usize, String> {
+    let text = text.trim();
+    let address = match text.strip_prefix("rb") {
+        Some("") => machine.relative_base,
+        Some(offset) => {
+            let offset = parse_number::<i64>(offset.trim_start_matches('+'))?;
+            machine
+                .relative_base
+                .checked_add(offset)
+                .ok_or_else(|| format!("relative address [rb{offset:+}] overflows"))?
+        }
+        None => parse_number::<i64>(text)?,
+    };
+    if address < 0 {
+        return Err(format!("negative address [{address}]"));
+    }
+    Ok(address as usize)
+}
+
+fn parse_opcode(text: &str) -> Result<i64, String> {
+    if let Ok(opcode) = text.parse::<i64>() {
+        return Ok(opcode);
+    }
+    super::asm::opcode_shape(&text.to_ascii_uppercase())
+        .map(|(opcode, _, _)| opcode)
+        .ok_or_else(|| format!("unknown opcode [{text}]"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::asm::assemble;
+
+    fn assembled(source: &str) -> Debugger {
+        Debugger::new(assemble(source).unwrap().machine())
+    }
+
+    #[test]
+    fn steps_over_calls_only() {
+        let mut debugger = assembled(
+            "
+            ARB #100
+            CALL f
+            OUT #2
+            HALT
+        f:  OUT #1
+            RET
+            ",
+        );
+        // ARB, then the push and the ARB of the call
+        for _ in 0..3 {
+            assert_eq!(debugger.step_over(), StopReason::Step);
+        }
+        assert_eq!(debugger.step_over(), StopReason::Step);
+        assert_eq!(debugger.machine.output(), &[1]);
+        assert_eq!(debugger.machine.relative_base, 100);
+        assert_eq!(debugger.step_over(), StopReason::Output(2));
+
+        // a plain jump is a single step, even onto code that outputs
+        let mut debugger = assembled("JT #1, f\nHALT\nf: OUT #1\nHALT");
+        assert_eq!(debugger.step_over(), StopReason::Step);
+        assert_eq!(debugger.machine.instruction_pointer, 4);
+        assert!(debugger.machine.output().is_empty());
+    }
+
+    #[test]
+    fn steps_back_over_memory_edits() {
+        let mut debugger = assembled("OUT [x]\nHALT\nx: DATA 5");
+        assert_eq!(debugger.execute_command("set 3 9"), "[3] = 9");
+        assert_eq!(debugger.step(), StopReason::Output(9));
+
+        debugger.step_back();
+        debugger.step_back();
+        assert_eq!(debugger.machine.memory.get(3), 5);
+        assert_eq!(debugger.step(), StopReason::Output(5));
+    }
+
+    #[test]
+    fn bounds_the_history() {
+        let mut debugger = assembled("a: JMP a").with_history_capacity(3);
+        debugger.step_limit = 10;
+        assert_eq!(debugger.resume(), StopReason::StepLimit);
+        assert_eq!(debugger.history().len(), 3);
+
+        for _ in 0..3 {
+            assert_eq!(debugger.step_back(), StopReason::Step);
+        }
+        assert_eq!(debugger.step_back(), StopReason::HistoryStart);
+    }
+
+    #[test]
+    fn saves_and_loads_state() {
+        let path = std::env::temp_dir().join(format!("intcode-debug-{}.snap", std::process::id()));
+        let path = path.to_str().unwrap();
+        let mut debugger = assembled("OUT #1\nOUT #2\nHALT");
+        debugger.step();
+        assert_eq!(
+            debugger.execute_command(&format!("save {path}")),
+            format!("state saved to [{path}]")
+        );
+        debugger.step();
+
+        assert!(debugger
+            .execute_command(&format!("load {path}"))
+            .starts_with(&format!("state loaded from [{path}]\n")));
+        std::fs::remove_file(path).unwrap();
+        assert_eq!(debugger.machine.output(), &[1]);
+        assert_eq!(debugger.history().len(), 0);
+        assert_eq!(debugger.step(), StopReason::Output(2));
+        assert_eq!(
+            debugger.execute_command("load"),
+            "error: usage: load <file>"
+        );
+    }
+
+    #[test]
+    fn rejects_overflowing_addresses() {
+        let mut debugger = assembled("HALT");
+        debugger.machine.relative_base = i64::MAX;
+        assert_eq!(
+            debugger.execute_command("mem rb+1"),
+            "error: relative address [rb+1] overflows"
+        );
+
+        let limit = debugger.machine.memory.limit();
+        assert_eq!(
+            debugger.execute_command(&format!("mem 1 {}", usize::MAX)),
+            format!("error: [{}] cells from [1] over memory limit", usize::MAX)
+        );
+        assert_eq!(
+            debugger.execute_command(&format!("mem 1 {limit}")),
+            format!("error: [{limit}] cells from [1] over memory limit")
+        );
+        assert_eq!(debugger.execute_command("mem 0 2"), "[0]: [99, 0]");
+    }
+}
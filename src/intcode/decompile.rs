@@ -0,0 +1,732 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+
+use super::analysis::{analyze, Analysis, Block, EdgeKind};
+use super::{Instruction, Parameter, ParameterMode, Program};
+
+const INDENT: &str = "    ";
+
+/// Lifts a program into structured pseudo-Rust, one `fn` per function of
+/// the [`analysis`](super::analysis).
+///
+/// Memory is `mem[a]`, relative cells `rb[k]`. Backward jumps become
+/// labelled `loop`s, forward conditional jumps over a block run become
+/// `if`s, anything else stays a `goto`. A comparison feeding only the next
+/// jump is folded into its condition, the call sequence of the relative-base
+/// convention becomes a call and a jump through a relative cell a `return`.
+/// An operand patched as `ADD #table, [i] -> [operand]` reads `mem[table + i]`.
+///
+/// # Examples
+/// ```
+/// use advent_of_code::intcode::asm::assemble;
+/// use advent_of_code::intcode::decompile::decompile;
+///
+/// let program = assemble("
+///     ARB #stack
+/// loop:
+///     IN -> [i]
+///     LT [i], #3 -> [small]
+///     JF [small], done
+///     ADD #table, [i] -> [load+1]
+/// load:
+///     OUT [0]
+///     JMP loop
+/// done:
+///     HALT
+/// i: DATA 0
+/// small: DATA 0
+/// table: DATA 10, 20, 30
+/// stack: .zero 4
+/// ").unwrap();
+///
+/// assert_eq!(
+///     decompile(&program),
+///     "fn main() {\n\
+///     \x20   rb += 26;\n\
+///     \x20   'l0002: loop {\n\
+///     \x20       mem[21] = input();\n\
+///     \x20       if mem[21] >= 3 {\n\
+///     \x20           break 'l0002;\n\
+///     \x20       }\n\
+///     \x20       // mem[16] = 23 + mem[21], patches the operand below\n\
+///     \x20       output(mem[23 + mem[21]]);\n\
+///     \x20   }\n\
+///     \x20   halt();\n\
+///     }\n"
+/// );
+/// ```
+pub fn decompile(program: &Program) -> String {
+    let analysis = analyze(program);
+    let decompiler = Decompiler::new(&analysis, program.image());
+
+    let mut placed = BTreeSet::new();
+    let mut functions = Vec::new();
+    for function in analysis.functions.values() {
+        placed.extend(function.blocks.iter().copied());
+        let blocks: Vec<&Block> = function
+            .blocks
+            .iter()
+            .map(|s| &analysis.blocks[s])
+            .collect();
+        functions.push(decompiler.function(function.entry, &blocks, ""));
+    }
+
+    // blocks only reached through computed jumps
+    let orphans: Vec<&Block> = analysis
+        .blocks
+        .values()
+        .filter(|block| !placed.contains(&block.start))
+        .collect();
+    if let Some(first) = orphans.first() {
+        functions.push(decompiler.function(first.start, &orphans, " // no known caller"));
+    }
+    functions.join("\n")
+}
+
+fn function_name(entry: usize) -> String {
+    match entry {
+        0 => "main".to_string(),
+        _ => format!("f{entry:04}"),
+    }
+}
+
+fn loop_name(header: usize) -> String {
+    format!("'l{header:04}")
+}
+
+/// Lifted form of one instruction.
+enum Statement {
+    Text(String),
+    /// Jump taken when the condition holds, `None` when always taken.
+    Jump(Option<Condition>, Target),
+    Return(Option<Condition>),
+    Halt,
+}
+
+#[derive(Clone)]
+struct Condition {
+    left: String,
+    operator: &'static str,
+    right: String,
+}
+
+impl Condition {
+    fn negated(&self) -> Condition {
+        let operator = match self.operator {
+            "<" => ">=",
+            ">=" => "<",
+            "==" => "!=",
+            _ => "==",
+        };
+        Condition {
+            operator,
+            ..self.clone()
+        }
+    }
+
+    fn render(&self) -> String {
+        format!("{} {} {}", self.left, self.operator, self.right)
+    }
+}
+
+enum Target {
+    Address(usize),
+    Computed(String),
+}
+
+/// Output line, labels are kept only if a `goto` needs them.
+enum Line {
+    Label(usize, usize),
+    Text(usize, String),
+}
+
+struct Decompiler<'a> {
+    analysis: &'a Analysis,
+    image: &'a [i64],
+    /// Call jump address -> function entry.
+    calls: BTreeMap<usize, usize>,
+    /// Addresses of return address stores hidden in calls.
+    call_setup: BTreeSet<usize>,
+    /// Operand address -> rendered patched operand.
+    patched: BTreeMap<usize, String>,
+    /// Comparisons folded into the following jump.
+    folded: BTreeSet<usize>,
+}
+
+impl<'a> Decompiler<'a> {
+    fn new(analysis: &'a Analysis, image: &'a [i64]) -> Self {
+        let mut decompiler = Decompiler {
+            analysis,
+            image,
+            calls: BTreeMap::new(),
+            call_setup: BTreeSet::new(),
+            patched: BTreeMap::new(),
+            folded: BTreeSet::new(),
+        };
+        decompiler.find_calls();
+        decompiler.find_patches();
+        decompiler.find_folds();
+        decompiler
+    }
+
+    fn find_calls(&mut self) {
+        for block in self.analysis.blocks.values() {
+            let Some(call) = block.successors.iter().find(|e| e.kind == EdgeKind::Call) else {
+                continue;
+            };
+            let Some(after) = block
+                .successors
+                .iter()
+                .find(|e| e.kind == EdgeKind::AfterCall)
+            else {
+                continue;
+            };
+            let (jump, _) = block.instructions.last().unwrap();
+            self.calls.insert(*jump, call.target);
+            if let Some((setup, _)) = block.instructions.iter().rev().find(|(_, i)| {
+                matches!(i, Instruction::Add { first_parameter: a, second_parameter: b, result }
+                    if a.mode == ParameterMode::Immediate
+                        && b.mode == ParameterMode::Immediate
+                        && result.mode == ParameterMode::Relative
+                        && a.value.checked_add(b.value) == Some(after.target as i64))
+            }) {
+                self.call_setup.insert(*setup);
+            }
+        }
+    }
+
+    fn find_patches(&mut self) {
+        let mut writers: BTreeMap<usize, Vec<usize>> = BTreeMap::new();
+        for write in &self.analysis.code_writes {
+            writers.entry(write.address).or_default().push(write.writer);
+        }
+        for write in &self.analysis.code_writes {
+            let (Some(base), [_]) = (write.table, &writers[&write.address][..]) else {
+                continue;
+            };
+            let Some(Instruction::Add {
+                first_parameter: a,
+                second_parameter: b,
+                ..
+            }) = self.instruction(write.writer)
+            else {
+                continue;
+            };
+            let index = if a.mode == ParameterMode::Immediate {
+                b
+            } else {
+                a
+            };
+            self.patched
+                .insert(write.address, format!("{base} + {}", self.operand(index)));
+        }
+    }
+
+    /// Comparisons whose result cell is only read by the next jump.
+    fn find_folds(&mut self) {
+        for block in self.analysis.blocks.values() {
+            for pair in block.instructions.windows(2) {
+                let [(compare, first), (jump, second)] = pair else {
+                    continue;
+                };
+                let (Some(cell), Some(tested)) = (compare_target(first), tested_cell(second))
+                else {
+                    continue;
+                };
+                let only_reader = self
+                    .analysis
+                    .data
+                    .get(&cell)
+                    .is_some_and(|data| data.readers.len() == 1 && data.readers.contains(jump));
+                if cell == tested && only_reader {
+                    self.folded.insert(*compare);
+                }
+            }
+        }
+    }
+
+    fn instruction(&self, address: usize) -> Option<&Instruction> {
+        let start = self.analysis.block_of(address)?;
+        self.analysis.blocks[&start]
+            .instructions
+            .iter()
+            .find(|(a, _)| *a == address)
+            .map(|(_, instruction)| instruction)
+    }
+
+    /// Operand as an expression, `operand_address` is the word holding it.
+    fn operand_at(&self, parameter: &Parameter, operand_address: Option<usize>) -> String {
+        if let Some(patched) = operand_address.and_then(|a| self.patched.get(&a)) {
+            return match parameter.mode {
+                ParameterMode::Position => format!("mem[{patched}]"),
+                _ => format!("({patched})"),
+            };
+        }
+        self.operand(parameter)
+    }
+
+    fn operand(&self, parameter: &Parameter) -> String {
+        match parameter.mode {
+            ParameterMode::Position => format!("mem[{}]", parameter.value),
+            ParameterMode::Immediate => parameter.value.to_string(),
+            ParameterMode::Relative => format!("rb[{}]", parameter.value),
+        }
+    }
+
+    fn lift(&self, address: usize, instruction: &Instruction) -> Option<Statement> {
+        if self.call_setup.contains(&address) || self.folded.contains(&address) {
+            return None;
+        }
+        let arg =
+            |index: usize, parameter: &Parameter| self.operand_at(parameter, Some(address + index));
+
+        let text = match instruction {
+            Instruction::Add {
+                first_parameter: a,
+                second_parameter: b,
+                result,
+            } => {
+                let value = match (arg(1, a), arg(2, b)) {
+                    (a, b)
+                        if let Some(sum) =
+                            constants(&a, &b).and_then(|(a, b)| a.checked_add(b)) =>
+                    {
+                        sum.to_string()
+                    }
+                    (a, b) if b == "0" => a,
+                    (a, b) if a == "0" => b,
+                    (a, b) => format!("{a} + {b}"),
+                };
+                self.assign(address, arg(3, result), value)
+            }
+            Instruction::Mul {
+                first_parameter: a,
+                second_parameter: b,
+                result,
+            } => {
+                let value = match (arg(1, a), arg(2, b)) {
+                    (a, b)
+                        if let Some(product) =
+                            constants(&a, &b).and_then(|(a, b)| a.checked_mul(b)) =>
+                    {
+                        product.to_string()
+                    }
+                    (a, b) if b == "1" => a,
+                    (a, b) if a == "1" => b,
+                    (a, b) if b == "-1" => format!("-{a}"),
+                    (a, b) if a == "-1" => format!("-{b}"),
+                    (a, b) => format!("{a} * {b}"),
+                };
+                self.assign(address, arg(3, result), value)
+            }
+            Instruction::Lst {
+                first_parameter: a,
+                second_parameter: b,
+                result,
+            } => self.assign(
+                address,
+                arg(3, result),
+                format!("({} < {}) as i64", arg(1, a), arg(2, b)),
+            ),
+            Instruction::Equ {
+                first_parameter: a,
+                second_parameter: b,
+                result,
+            } => self.assign(
+                address,
+                arg(3, result),
+                format!("({} == {}) as i64", arg(1, a), arg(2, b)),
+            ),
+            Instruction::Inp { result } => {
+                self.assign(address, arg(1, result), "input()".to_string())
+            }
+            Instruction::Out { parameter } => format!("output({});", arg(1, parameter)),
+            Instruction::Arb { first_parameter } => match first_parameter.mode {
+                ParameterMode::Immediate if first_parameter.value < 0 => {
+                    format!("rb -= {};", -first_parameter.value)
+                }
+                _ => format!("rb += {};", arg(1, first_parameter)),
+            },
+            Instruction::Halt | Instruction::Reserved => return Some(Statement::Halt),
+            Instruction::Jit {
+                first_parameter,
+                second_parameter,
+            }
+            | Instruction::Jif {
+                first_parameter,
+                second_parameter,
+            } => {
+                if let Some(entry) = self.calls.get(&address) {
+                    return Some(Statement::Text(format!("{}();", function_name(*entry))));
+                }
+                let condition = self.condition(address, instruction, first_parameter);
+                if condition.as_ref().is_some_and(|c| c.operator == "never") {
+                    return None;
+                }
+                let target = match second_parameter.mode {
+                    // a negative target faults, kept as written
+                    ParameterMode::Immediate => match usize::try_from(second_parameter.value) {
+                        Ok(target) => Target::Address(target),
+                        Err(_) => Target::Computed(second_parameter.value.to_string()),
+                    },
+                    ParameterMode::Relative => return Some(Statement::Return(condition)),
+                    ParameterMode::Position => Target::Computed(arg(2, second_parameter)),
+                };
+                return Some(Statement::Jump(condition, target));
+            }
+        };
+        Some(Statement::Text(text))
+    }
+
+    fn assign(&self, address: usize, target: String, value: String) -> String {
+        let patch = self
+            .analysis
+            .code_writes
+            .iter()
+            .any(|write| write.writer == address && self.patched.contains_key(&write.address));
+        match patch {
+            true => format!("// {target} = {value}, patches the operand below"),
+            false => format!("{target} = {value};"),
+        }
+    }
+
+    /// Condition under which the jump is taken, `None` when always taken.
+    fn condition(
+        &self,
+        address: usize,
+        instruction: &Instruction,
+        tested: &Parameter,
+    ) -> Option<Condition> {
+        let jump_if_true = matches!(instruction, Instruction::Jit { .. });
+        if tested.mode == ParameterMode::Immediate {
+            return match (tested.value != 0) == jump_if_true {
+                true => None,
+                false => Some(Condition {
+                    left: String::new(),
+                    operator: "never",
+                    right: String::new(),
+                }),
+            };
+        }
+
+        let folded = self
+            .analysis
+            .block_of(address)
+            .and_then(|start| {
+                let instructions = &self.analysis.blocks[&start].instructions;
+                let index = instructions.iter().position(|(a, _)| *a == address)?;
+                index.checked_sub(1).map(|i| instructions[i])
+            })
+            .filter(|(compare, _)| self.folded.contains(compare));
+        let condition = match folded {
+            Some((
+                compare,
+                Instruction::Lst {
+                    first_parameter: a,
+                    second_parameter: b,
+                    ..
+                }
+                | Instruction::Equ {
+                    first_parameter: a,
+                    second_parameter: b,
+                    ..
+                },
+            )) => Condition {
+                left: self.operand_at(&a, Some(compare + 1)),
+                operator: match self.image[compare] % 100 {
+                    7 => "<",
+                    _ => "==",
+                },
+                right: self.operand_at(&b, Some(compare + 2)),
+            },
+            _ => Condition {
+                left: self.operand_at(tested, Some(address + 1)),
+                operator: "!=",
+                right: "0".to_string(),
+            },
+        };
+        Some(match jump_if_true {
+            true => condition,
+            false => condition.negated(),
+        })
+    }
+
+    fn function(&self, entry: usize, blocks: &[&Block], note: &str) -> String {
+        let mut lines = Vec::new();
+        let mut gotos = BTreeSet::new();
+        let mut emitter = Emitter {
+            decompiler: self,
+            blocks,
+            lines: &mut lines,
+            gotos: &mut gotos,
+            loops: Vec::new(),
+        };
+        emitter.range(0, blocks.len(), 1, None);
+
+        let mut text = format!("fn {}() {{{note}\n", function_name(entry));
+        for line in lines {
+            match line {
+                Line::Label(depth, address) if gotos.contains(&address) => {
+                    let indent = INDENT.repeat(depth.saturating_sub(1));
+                    writeln!(text, "{indent}L{address:04}:").unwrap();
+                }
+                Line::Label(..) => (),
+                Line::Text(depth, line) => {
+                    writeln!(text, "{}{line}", INDENT.repeat(depth)).unwrap();
+                }
+            }
+        }
+        text.push_str("}\n");
+        text
+    }
+}
+
+/// Both operands as numbers, for folding immediates.
+/// Both operands when they are literals, folded by the caller if the result fits.
+fn constants(a: &str, b: &str) -> Option<(i64, i64)> {
+    Some((a.parse().ok()?, b.parse().ok()?))
+}
+
+/// Result cell of an `LT`/`EQ` written to a position.
+fn compare_target(instruction: &Instruction) -> Option<usize> {
+    match instruction {
+        Instruction::Lst { result, .. } | Instruction::Equ { result, .. }
+            if result.mode == ParameterMode::Position =>
+        {
+            usize::try_from(result.value).ok()
+        }
+        _ => None,
+    }
+}
+
+/// Position cell a conditional jump tests.
+fn tested_cell(instruction: &Instruction) -> Option<usize> {
+    match instruction {
+        Instruction::Jit {
+            first_parameter, ..
+        }
+        | Instruction::Jif {
+            first_parameter, ..
+        } if first_parameter.mode == ParameterMode::Position => {
+            usize::try_from(first_parameter.value).ok()
+        }
+        _ => None,
+    }
+}
+
+/// Structures the blocks of one function into lines.
+struct Emitter<'a, 'b> {
+    decompiler: &'b Decompiler<'a>,
+    blocks: &'b [&'b Block],
+    lines: &'b mut Vec<Line>,
+    gotos: &'b mut BTreeSet<usize>,
+    /// Open loops as (header, exit address).
+    loops: Vec<(usize, usize)>,
+}
+
+impl Emitter<'_, '_> {
+    fn index_of(&self, address: usize) -> Option<usize> {
+        self.blocks.iter().position(|block| block.start == address)
+    }
+
+    /// Last block in `from..to` jumping back to block `from`.
+    fn latch(&self, from: usize, to: usize) -> Option<usize> {
+        let header = self.blocks[from].start;
+        (from..to).rev().find(|&i| {
+            self.blocks[i]
+                .successors
+                .iter()
+                .any(|edge| edge.kind == EdgeKind::Jump && edge.target == header)
+        })
+    }
+
+    /// True if no block outside `from..to` jumps into `from + 1..to`.
+    fn closed(&self, from: usize, to: usize) -> bool {
+        let inside: BTreeSet<usize> = self.blocks[from + 1..to].iter().map(|b| b.start).collect();
+        self.blocks
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| *i < from || *i >= to)
+            .all(|(_, block)| block.successors.iter().all(|e| !inside.contains(&e.target)))
+    }
+
+    fn text(&mut self, depth: usize, line: String) {
+        self.lines.push(Line::Text(depth, line));
+    }
+
+    fn range(&mut self, from: usize, to: usize, depth: usize, mut skip_loop: Option<usize>) {
+        let mut index = from;
+        while index < to {
+            let block = self.blocks[index];
+            if skip_loop != Some(index) {
+                if let Some(latch) = self.latch(index, to).filter(|&l| self.closed(index, l + 1)) {
+                    let name = loop_name(block.start);
+                    let exit = self.blocks[latch].end;
+                    self.lines.push(Line::Label(depth, block.start));
+                    self.text(depth, format!("{name}: loop {{"));
+                    self.loops.push((block.start, exit));
+                    self.range(index, latch + 1, depth + 1, Some(index));
+                    self.loops.pop();
+                    self.text(depth, "}".to_string());
+                    index = latch + 1;
+                    continue;
+                }
+            }
+            skip_loop = None;
+
+            self.lines.push(Line::Label(depth, block.start));
+            let statements: Vec<Statement> = block
+                .instructions
+                .iter()
+                .filter_map(|(address, instruction)| self.decompiler.lift(*address, instruction))
+                .collect();
+            let last = statements.len();
+            let next_start = self.blocks[..to].get(index + 1).map(|b| b.start);
+            let range_end = self.blocks[to - 1].end;
+
+            for (position, statement) in statements.into_iter().enumerate() {
+                match statement {
+                    Statement::Text(line) => self.text(depth, line),
+                    Statement::Halt => self.text(depth, "halt();".to_string()),
+                    Statement::Return(None) => self.text(depth, "return;".to_string()),
+                    Statement::Return(Some(condition)) => {
+                        self.text(depth, format!("if {} {{", condition.render()));
+                        self.text(depth + 1, "return;".to_string());
+                        self.text(depth, "}".to_string());
+                    }
+                    Statement::Jump(condition, Target::Computed(target)) => {
+                        let jump = format!("goto {target};");
+                        self.guarded(depth, condition, jump);
+                    }
+                    Statement::Jump(Some(condition), Target::Address(target))
+                        if position + 1 == last
+                            && target > block.start
+                            && target <= range_end
+                            && !self.is_loop_edge(target) =>
+                    {
+                        // forward skip over following blocks: if (not taken) { ... }
+                        let end = self.index_of(target).unwrap_or(to);
+                        if end <= to && self.closed(index, end) && end > index + 1 {
+                            self.text(depth, format!("if {} {{", condition.negated().render()));
+                            self.range(index + 1, end, depth + 1, None);
+                            self.text(depth, "}".to_string());
+                            index = end - 1;
+                        } else {
+                            let jump = self.jump(target, next_start);
+                            self.guarded(depth, Some(condition), jump);
+                        }
+                    }
+                    Statement::Jump(None, Target::Address(target))
+                        if position + 1 == last
+                            && index + 1 == to
+                            && self.loops.last().map(|l| l.0) == Some(target)
+                            && self.blocks[from].start == target => {}
+                    Statement::Jump(condition, Target::Address(target)) => {
+                        let fallthrough = next_start.filter(|_| position + 1 == last);
+                        let jump = self.jump(target, fallthrough);
+                        self.guarded(depth, condition, jump);
+                    }
+                }
+            }
+
+            // a loop body falling out of its last block leaves the loop
+            let falls_through = !block.successors.is_empty()
+                && block.successors.iter().any(|e| e.kind == EdgeKind::Next);
+            if index + 1 == to && falls_through {
+                if let Some(&(header, exit)) = self.loops.last() {
+                    if block.end == exit && self.blocks[from].start == header {
+                        self.text(depth, format!("break {};", loop_name(header)));
+                    }
+                }
+            }
+            index += 1;
+        }
+    }
+
+    fn is_loop_edge(&self, target: usize) -> bool {
+        self.loops
+            .iter()
+            .any(|&(header, exit)| target == header || target == exit)
+    }
+
+    fn jump(&mut self, target: usize, fallthrough: Option<usize>) -> String {
+        for &(header, exit) in self.loops.iter().rev() {
+            if target == header {
+                return format!("continue {};", loop_name(header));
+            }
+            if target == exit {
+                return format!("break {};", loop_name(header));
+            }
+        }
+        if fallthrough == Some(target) {
+            return String::new();
+        }
+        self.gotos.insert(target);
+        format!("goto L{target:04};")
+    }
+
+    fn guarded(&mut self, depth: usize, condition: Option<Condition>, jump: String) {
+        if jump.is_empty() {
+            return;
+        }
+        match condition {
+            None => self.text(depth, jump),
+            Some(condition) => {
+                self.text(depth, format!("if {} {{", condition.render()));
+                self.text(depth + 1, jump);
+                self.text(depth, "}".to_string());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::asm::assemble;
+
+    #[test]
+    fn folds_constants_that_fit() {
+        let max = i64::MAX;
+        let program: Program = format!("1101,{max},1,9,1102,{max},2,9,1101,2,3,9,99,0")
+            .parse()
+            .unwrap();
+        assert_eq!(
+            decompile(&program),
+            format!(
+                "fn main() {{\n\
+                \x20   mem[9] = {max} + 1;\n\
+                \x20   mem[9] = {max} * 2;\n\
+                \x20   mem[9] = 5;\n\
+                \x20   halt();\n\
+                }}\n"
+            )
+        );
+
+        // a push overflowing to the return address is not a call
+        let program: Program = format!("21101,{max},1,1,1105,1,7,99").parse().unwrap();
+        assert!(decompile(&program).contains(&format!("rb[1] = {max} + 1;")));
+    }
+
+    #[test]
+    fn lifts_calls_and_returns() {
+        let program = assemble("ARB #stack\nCALL f\nHALT\nf: OUT #1\nRET\nstack: .zero 4").unwrap();
+        let text = decompile(&program);
+        assert!(text.contains("    f0012();\n    halt();\n"), "{text}");
+        assert!(text.contains("fn f0012() {\n    output(1);\n"), "{text}");
+        assert!(text.ends_with("    return;\n}\n"), "{text}");
+    }
+
+    #[test]
+    fn keeps_negative_jump_targets() {
+        let program: Program = "1005,5,-5,99".parse().unwrap();
+        let text = decompile(&program);
+        assert!(
+            text.contains("if mem[5] != 0 {\n        goto -5;"),
+            "{text}"
+        );
+
+        let program: Program = "1105,1,-7".parse().unwrap();
+        assert_eq!(decompile(&program), "fn main() {\n    goto -7;\n}\n");
+    }
+}
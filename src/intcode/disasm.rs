@@ -0,0 +1,366 @@
+use std::collections::BTreeSet;
+use std::fmt::Display;
+
+use super::{Instruction, Parameter, ParameterMode, Program};
+
+/// Data words printed per `DATA` line.
+const DATA_WORDS_PER_LINE: usize = 8;
+
+/// Annotated listing of a program.
+///
+/// Code is found by walking the control flow from address 0, following
+/// immediate jump targets and return addresses pushed before calls
+/// (`ADD #ret, #0 -> [rb+1]` followed by an unconditional jump that ends at `ret`).
+/// Everything that is never reached is listed as data.
+///
+/// # Examples
+/// ```
+/// use advent_of_code::intcode::*;
+/// use advent_of_code::intcode::disasm::*;
+///
+/// let program: Program = "1105,1,4,33,204,-1,99".parse().unwrap();
+/// let listing = disassemble(&program);
+/// assert_eq!(
+///     listing.to_string(),
+///     "0000: 1105,1,4              JT #1, L0004\n\
+///      0003: 33                    DATA 33\n\
+///      L0004:\n\
+///      0004: 204,-1                OUT [rb-1]\n\
+///      0006: 99                    HALT\n"
+/// );
+/// ```
+pub struct Listing {
+    pub entries: Vec<Entry>,
+    pub labels: BTreeSet<usize>,
+}
+
+pub struct Entry {
+    pub address: usize,
+    pub words: Vec<i64>,
+    pub kind: EntryKind,
+}
+
+pub enum EntryKind {
+    Code(Instruction),
+    Data,
+}
+
+impl Listing {
+    pub fn label(address: usize) -> String {
+        format!("L{address:04}")
+    }
+
+    fn operand(&self, parameter: &Parameter) -> String {
+        match parameter.mode {
+            ParameterMode::Immediate
+                if parameter.value >= 0 && self.labels.contains(&(parameter.value as usize)) =>
+            {
+                Listing::label(parameter.value as usize)
+            }
+            _ => parameter.to_string(),
+        }
+    }
+
+    pub(super) fn format_instruction(&self, instruction: &Instruction) -> String {
+        match instruction {
+            Instruction::Jit {
+                first_parameter,
+                second_parameter,
+            }
+            | Instruction::Jif {
+                first_parameter,
+                second_parameter,
+            } => format!(
+                "{} {}, {}",
+                instruction.mnemonic(),
+                first_parameter,
+                self.operand(second_parameter)
+            ),
+            _ => instruction.to_string(),
+        }
+    }
+}
+
+impl Display for Listing {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for entry in &self.entries {
+            if self.labels.contains(&entry.address) {
+                writeln!(f, "{}:", Listing::label(entry.address))?;
+            }
+            let words = entry
+                .words
+                .iter()
+                .map(|w| w.to_string())
+                .collect::<Vec<_>>()
+                .join(",");
+            let text = match &entry.kind {
+                EntryKind::Code(instruction) => self.format_instruction(instruction),
+                EntryKind::Data => format!("DATA {words}"),
+            };
+            writeln!(f, "{:04}: {:<21} {}", entry.address, words, text)?;
+        }
+        Ok(())
+    }
+}
+
+impl Instruction {
+    pub fn mnemonic(&self) -> &'static str {
+        match self {
+            Instruction::Add { .. } => "ADD",
+            Instruction::Mul { .. } => "MUL",
+            Instruction::Inp { .. } => "IN",
+            Instruction::Out { .. } => "OUT",
+            Instruction::Jit { .. } => "JT",
+            Instruction::Jif { .. } => "JF",
+            Instruction::Lst { .. } => "LT",
+            Instruction::Equ { .. } => "EQ",
+            Instruction::Arb { .. } => "ARB",
+            Instruction::Halt => "HALT",
+            Instruction::Reserved => "RESERVED",
+        }
+    }
+}
+
+impl Display for Parameter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.mode {
+            ParameterMode::Position => write!(f, "[{}]", self.value),
+            ParameterMode::Immediate => write!(f, "#{}", self.value),
+            ParameterMode::Relative if self.value < 0 => {
+                write!(f, "[rb-{}]", self.value.unsigned_abs())
+            }
+            ParameterMode::Relative => write!(f, "[rb+{}]", self.value),
+        }
+    }
+}
+
+/// Formats as `ADD [rb+3], #5 -> [100]`.
+impl Display for Instruction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mnemonic = self.mnemonic();
+        match self {
+            Instruction::Add {
+                first_parameter,
+                second_parameter,
+                result,
+            }
+            | Instruction::Mul {
+                first_parameter,
+                second_parameter,
+                result,
+            }
+            | Instruction::Lst {
+                first_parameter,
+                second_parameter,
+                result,
+            }
+            | Instruction::Equ {
+                first_parameter,
+                second_parameter,
+                result,
+            } => write!(
+                f,
+                "{mnemonic} {first_parameter}, {second_parameter} -> {result}"
+            ),
+            Instruction::Inp { result } => write!(f, "{mnemonic} -> {result}"),
+            Instruction::Out {
+                parameter: first_parameter,
+            }
+            | Instruction::Arb { first_parameter } => write!(f, "{mnemonic} {first_parameter}"),
+            Instruction::Jit {
+                first_parameter,
+                second_parameter,
+            }
+            | Instruction::Jif {
+                first_parameter,
+                second_parameter,
+            } => write!(f, "{mnemonic} {first_parameter}, {second_parameter}"),
+            Instruction::Halt | Instruction::Reserved => write!(f, "{mnemonic}"),
+        }
+    }
+}
+
+/// Builds the annotated listing of a program.
+pub fn disassemble(program: &Program) -> Listing {
+    let image = program.image();
+
+    // owner[a] is the start of the instruction covering address a.
+    let mut owner: Vec<Option<usize>> = vec![None; image.len()];
+    let mut labels = BTreeSet::new();
+    let mut return_candidates = BTreeSet::new();
+    let mut jump_ends = BTreeSet::new();
+
+    let mut to_visit = vec![0];
+    loop {
+        while let Some(address) = to_visit.pop() {
+            walk(
+                image,
+                address,
+                &mut owner,
+                &mut labels,
+                &mut to_visit,
+                &mut return_candidates,
+                &mut jump_ends,
+            );
+        }
+
+        // return addresses pushed by calls
+        let accepted: Vec<usize> = return_candidates
+            .iter()
+            .filter(|a| jump_ends.contains(*a) && owner[**a].is_none())
+            .copied()
+            .collect();
+        if accepted.is_empty() {
+            break;
+        }
+        for address in accepted {
+            labels.insert(address);
+            to_visit.push(address);
+        }
+    }
+
+    let mut entries = Vec::new();
+    let mut address = 0;
+    while address < image.len() {
+        if owner[address] == Some(address) {
+            let instruction = Instruction::try_from(&image[address..]).unwrap();
+            let size = instruction.get_size() as usize;
+            entries.push(Entry {
+                address,
+                words: image[address..address + size].to_vec(),
+                kind: EntryKind::Code(instruction),
+            });
+            address += size;
+        } else {
+            let start = address;
+            while address < image.len()
+                && owner[address].is_none()
+                && address - start < DATA_WORDS_PER_LINE
+                && (address == start || !labels.contains(&address))
+            {
+                address += 1;
+            }
+            entries.push(Entry {
+                address: start,
+                words: image[start..address].to_vec(),
+                kind: EntryKind::Data,
+            });
+        }
+    }
+
+    // labels only make sense on listed lines
+    labels.retain(|a| entries.iter().any(|e| e.address == *a));
+
+    Listing { entries, labels }
+}
+
+fn walk(
+    image: &[i64],
+    mut address: usize,
+    owner: &mut [Option<usize>],
+    labels: &mut BTreeSet<usize>,
+    to_visit: &mut Vec<usize>,
+    return_candidates: &mut BTreeSet<usize>,
+    jump_ends: &mut BTreeSet<usize>,
+) {
+    while address < image.len() && owner[address].is_none() {
+        let Ok(instruction) = Instruction::try_from(&image[address..]) else {
+            return;
+        };
+        let size = instruction.get_size() as usize;
+        if owner[address..address + size].iter().any(|o| o.is_some()) {
+            return;
+        }
+        for cell in &mut owner[address..address + size] {
+            *cell = Some(address);
+        }
+        let next = address + size;
+
+        match &instruction {
+            Instruction::Halt | Instruction::Reserved => return,
+            Instruction::Jit {
+                first_parameter,
+                second_parameter,
+            }
+            | Instruction::Jif {
+                first_parameter,
+                second_parameter,
+            } => {
+                if second_parameter.mode == ParameterMode::Immediate
+                    && second_parameter.value >= 0
+                    && (second_parameter.value as usize) < image.len()
+                {
+                    let target = second_parameter.value as usize;
+                    labels.insert(target);
+                    to_visit.push(target);
+                }
+                let always_jumps = first_parameter.mode == ParameterMode::Immediate
+                    && match instruction {
+                        Instruction::Jit { .. } => first_parameter.value != 0,
+                        _ => first_parameter.value == 0,
+                    };
+                if always_jumps {
+                    jump_ends.insert(next);
+                    return;
+                }
+            }
+            Instruction::Add {
+                first_parameter,
+                second_parameter,
+                ..
+            }
+            | Instruction::Mul {
+                first_parameter,
+                second_parameter,
+                ..
+            } if first_parameter.mode == ParameterMode::Immediate
+                && second_parameter.mode == ParameterMode::Immediate =>
+            {
+                let value = if let Instruction::Add { .. } = instruction {
+                    first_parameter.value.checked_add(second_parameter.value)
+                } else {
+                    first_parameter.value.checked_mul(second_parameter.value)
+                };
+                // an overflowing product is no address
+                if let Some(value) = value.filter(|&v| v >= 0 && (v as usize) < image.len()) {
+                    return_candidates.insert(value as usize);
+                }
+            }
+            _ => (),
+        }
+        address = next;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn listing(source: &str) -> String {
+        disassemble(&source.parse().unwrap()).to_string()
+    }
+
+    #[test]
+    fn overflowing_immediates() {
+        assert_eq!(
+            listing("1101,9223372036854775807,1,0,99"),
+            "0000: 1101,9223372036854775807,1,0 ADD #9223372036854775807, #1 -> [0]\n\
+             0004: 99                    HALT\n"
+        );
+        assert!(listing("1102,9223372036854775807,3,0,99").contains("MUL"));
+        assert!(listing("204,-9223372036854775808,99").contains("OUT [rb-9223372036854775808]"));
+    }
+
+    #[test]
+    fn follows_calls_and_keeps_data() {
+        // pushes 7 as return address, calls 9 which returns through [rb+1]
+        let text = listing("21101,7,0,1,1105,1,9,99,42,2106,0,1");
+        assert!(text.contains("JT #1, L0009"), "{text}");
+        assert!(text.contains("L0007:\n0007: 99"), "{text}");
+        assert!(
+            text.contains("0008: 42                    DATA 42"),
+            "{text}"
+        );
+        assert!(text.contains("JF #0, [rb+1]"), "{text}");
+    }
+}
@@ -0,0 +1,23 @@
+//! Small programs shared by the intcode test modules.
+
+use super::Program;
+
+/// Echoes every input value until the program is stopped.
+pub fn echo() -> Program {
+    "3,7,4,7,1105,1,0,0".parse().unwrap()
+}
+
+/// Adds its two inputs and outputs the sum, then halts.
+pub fn adder() -> Program {
+    "3,11,3,12,1,11,12,12,4,12,99,0,0".parse().unwrap()
+}
+
+/// Jumps to itself forever.
+pub fn spin() -> Program {
+    "1105,1,0".parse().unwrap()
+}
+
+/// Outputs every input value plus one until the program is stopped.
+pub fn incrementer() -> Program {
+    "3,9,1001,9,1,9,4,9,1105,1,0".parse().unwrap()
+}
@@ -0,0 +1,324 @@
+use std::cell::{RefCell, RefMut};
+use std::collections::VecDeque;
+use std::fmt::Display;
+use std::future::{poll_fn, Future};
+use std::pin::{pin, Pin};
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll, Wake, Waker};
+
+use super::{ExecuteResult, IntcodeError, Limits, Machine, Memory};
+
+/// Machine driven by its async reader, with queued input and output.
+pub type QueueMachine = Machine<VecDeque<i64>, VecDeque<i64>>;
+
+/// Async handle on a machine, clones share the machine.
+///
+/// `read_output` runs the machine until it outputs a value, halts or fails.
+/// When the machine needs input that has not been written yet, the read waits
+/// until another task calls `write_input`. A machine going over its
+/// [`Limits`] stops with the matching [`IntcodeError`].
+///
+/// # Examples
+/// ```
+/// use advent_of_code::intcode::*;
+/// use advent_of_code::intcode::futures::*;
+///
+/// // doubles every input until the input is 0
+/// let program: Program = "3,15,1006,15,14,1002,15,2,15,4,15,1105,1,0,99,0"
+///     .parse()
+///     .unwrap();
+/// let doubler = AsyncMachine::new(program.memory());
+///
+/// let sum = block_on(async {
+///     let mut sum = 0;
+///     for value in 1..=3 {
+///         doubler.write_input(value).await;
+///         sum += doubler.read_output().await.unwrap().unwrap();
+///     }
+///     doubler.write_input(0).await;
+///     assert_eq!(doubler.read_output().await, Ok(None));
+///     sum
+/// });
+/// assert_eq!(sum, 12);
+/// ```
+#[derive(Clone)]
+pub struct AsyncMachine {
+    inner: Rc<RefCell<Inner>>,
+}
+
+struct Inner {
+    machine: QueueMachine,
+    stopped: Option<Result<i64, IntcodeError>>,
+    readers: Vec<Waker>,
+}
+
+impl AsyncMachine {
+    pub fn new(memory: impl Into<Memory>) -> Self {
+        AsyncMachine::from_machine(Machine::with_io(memory, VecDeque::new(), VecDeque::new()))
+    }
+
+    pub fn from_machine(machine: QueueMachine) -> Self {
+        AsyncMachine {
+            inner: Rc::new(RefCell::new(Inner {
+                machine,
+                stopped: None,
+                readers: Vec::new(),
+            })),
+        }
+    }
+
+    /// Budgets of the machine over all reads, see [`Machine::set_limits`].
+    pub fn with_limits(self, limits: Limits) -> Self {
+        self.inner.borrow_mut().machine.set_limits(limits);
+        self
+    }
+
+    /// Next output value, `None` once the machine halted.
+    pub async fn read_output(&self) -> Result<Option<i64>, IntcodeError> {
+        poll_fn(|cx| self.poll_output(cx)).await
+    }
+
+    /// Reads `count` values, `None` if the machine halts first.
+    pub async fn read_outputs(&self, count: usize) -> Result<Option<Vec<i64>>, IntcodeError> {
+        let mut values = Vec::with_capacity(count);
+        for _ in 0..count {
+            match self.read_output().await? {
+                Some(value) => values.push(value),
+                None => return Ok(None),
+            }
+        }
+        Ok(Some(values))
+    }
+
+    /// Queues a value for the next `IN`, waking tasks waiting on this machine.
+    pub async fn write_input(&self, value: i64) {
+        let mut inner = self.inner.borrow_mut();
+        inner.machine.push_input(value);
+        inner.readers.drain(..).for_each(Waker::wake);
+    }
+
+    /// Halt value or error once the machine has stopped.
+    pub fn stopped(&self) -> Option<Result<i64, IntcodeError>> {
+        self.inner.borrow().stopped.clone()
+    }
+
+    /// The underlying machine, for inspection between awaits.
+    pub fn machine(&self) -> RefMut<'_, QueueMachine> {
+        RefMut::map(self.inner.borrow_mut(), |inner| &mut inner.machine)
+    }
+
+    fn poll_output(&self, cx: &mut Context<'_>) -> Poll<Result<Option<i64>, IntcodeError>> {
+        let mut inner = self.inner.borrow_mut();
+        loop {
+            if let Some(value) = inner.machine.output_mut().pop_front() {
+                return Poll::Ready(Ok(Some(value)));
+            }
+            match &inner.stopped {
+                Some(Ok(_)) => return Poll::Ready(Ok(None)),
+                Some(Err(error)) => return Poll::Ready(Err(error.clone())),
+                None => (),
+            }
+            let machine = &mut inner.machine;
+            match machine.execute_until(|machine| !machine.output().is_empty()) {
+                ExecuteResult::Continue => (),
+                ExecuteResult::Halt(value) => inner.stopped = Some(Ok(value)),
+                ExecuteResult::Error(error) => inner.stopped = Some(Err(error)),
+                ExecuteResult::WaitingInput => {
+                    inner.readers.push(cx.waker().clone());
+                    return Poll::Pending;
+                }
+                limit => {
+                    let error = limit.limit_error(machine.instruction_pointer).unwrap();
+                    inner.stopped = Some(Err(error));
+                }
+            }
+        }
+    }
+}
+
+/// Waker flagging that some task may progress.
+struct Flag(AtomicBool);
+
+impl Wake for Flag {
+    fn wake(self: Arc<Self>) {
+        self.0.store(true, Ordering::SeqCst);
+    }
+}
+
+/// Single-threaded round-robin executor for tasks driving machines.
+///
+/// Tasks may borrow local state. Every pending future must be woken by an
+/// [`AsyncMachine`] write, a round with no wake and no finished task is a
+/// deadlock.
+///
+/// # Examples
+/// ```
+/// use advent_of_code::intcode::*;
+/// use advent_of_code::intcode::futures::*;
+///
+/// // echoes inputs forever
+/// let program: Program = "3,7,4,7,1105,1,0,0".parse().unwrap();
+/// let echo = AsyncMachine::new(program.memory());
+/// let mut received = Vec::new();
+///
+/// let mut executor = Executor::new();
+/// executor.spawn(async {
+///     while let Ok(Some(value)) = echo.read_output().await {
+///         received.push(value);
+///     }
+/// });
+/// executor.spawn(async {
+///     for value in [1, 2, 3] {
+///         echo.write_input(value).await;
+///     }
+/// });
+/// assert_eq!(executor.run(), Err(Deadlock { tasks: 1 }));
+/// drop(executor);
+/// assert_eq!(received, vec![1, 2, 3]);
+/// ```
+#[derive(Default)]
+pub struct Executor<'a> {
+    tasks: Vec<Pin<Box<dyn Future<Output = ()> + 'a>>>,
+}
+
+impl<'a> Executor<'a> {
+    pub fn new() -> Self {
+        Executor::default()
+    }
+
+    pub fn spawn(&mut self, task: impl Future<Output = ()> + 'a) {
+        self.tasks.push(Box::pin(task));
+    }
+
+    /// Polls the tasks until all of them finished.
+    pub fn run(&mut self) -> Result<(), Deadlock> {
+        let flag = Arc::new(Flag(AtomicBool::new(false)));
+        let waker = Waker::from(flag.clone());
+        let mut cx = Context::from_waker(&waker);
+
+        while !self.tasks.is_empty() {
+            flag.0.store(false, Ordering::SeqCst);
+            let pending = self.tasks.len();
+            self.tasks
+                .retain_mut(|task| task.as_mut().poll(&mut cx).is_pending());
+            if self.tasks.len() == pending && !flag.0.load(Ordering::SeqCst) {
+                return Err(Deadlock {
+                    tasks: self.tasks.len(),
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Runs a future to completion on the current thread.
+///
+/// # Panics
+/// If the future waits on a machine that nobody writes to.
+pub fn block_on<F: Future>(future: F) -> F::Output {
+    let flag = Arc::new(Flag(AtomicBool::new(false)));
+    let waker = Waker::from(flag.clone());
+    let mut cx = Context::from_waker(&waker);
+
+    let mut future = pin!(future);
+    loop {
+        if let Poll::Ready(value) = future.as_mut().poll(&mut cx) {
+            return value;
+        }
+        if !flag.0.swap(false, Ordering::SeqCst) {
+            panic!("{}", Deadlock { tasks: 1 });
+        }
+    }
+}
+
+/// Every remaining task waits for input nobody writes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Deadlock {
+    pub tasks: usize,
+}
+
+impl Display for Deadlock {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Deadlock, [{}] tasks wait for input!", self.tasks)
+    }
+}
+
+impl std::error::Error for Deadlock {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::fixtures::{incrementer, spin};
+    use crate::intcode::Program;
+
+    fn machine(source: &str) -> AsyncMachine {
+        AsyncMachine::new(source.parse::<Program>().unwrap().memory())
+    }
+
+    #[test]
+    fn tasks_exchange_values() {
+        // adds one to every input, both machines pass a value back and forth
+        let (a, b) = (
+            AsyncMachine::new(incrementer().memory()),
+            AsyncMachine::new(incrementer().memory()),
+        );
+        let mut seen = Vec::new();
+
+        let mut executor = Executor::new();
+        executor.spawn(async {
+            a.write_input(0).await;
+            for _ in 0..3 {
+                let value = a.read_output().await.unwrap().unwrap();
+                seen.push(value);
+                b.write_input(value).await;
+            }
+        });
+        executor.spawn(async {
+            for _ in 0..3 {
+                let value = b.read_output().await.unwrap().unwrap();
+                a.write_input(value).await;
+            }
+        });
+        assert_eq!(executor.run(), Ok(()));
+        drop(executor);
+        assert_eq!(seen, [1, 3, 5]);
+        assert_eq!(a.machine().input().len(), 1);
+    }
+
+    #[test]
+    fn reports_stops() {
+        let halts = machine("104,1,99");
+        assert_eq!(block_on(halts.read_outputs(2)), Ok(None));
+        assert_eq!(halts.stopped(), Some(Ok(104)));
+
+        let fails = machine("104,1,98");
+        let error = IntcodeError::UnknownOpcode {
+            opcode: 98,
+            instruction_pointer: 2,
+        };
+        assert_eq!(block_on(fails.read_outputs(1)), Ok(Some(vec![1])));
+        assert_eq!(block_on(fails.read_output()), Err(error.clone()));
+        assert_eq!(fails.stopped(), Some(Err(error)));
+    }
+
+    #[test]
+    #[should_panic(expected = "Deadlock")]
+    fn block_on_panics_on_deadlock() {
+        block_on(machine("3,0,99").read_output()).ok();
+    }
+
+    #[test]
+    fn limits_stop_the_machine() {
+        let spinning = AsyncMachine::new(spin().memory()).with_limits(Limits {
+            max_steps: Some(10),
+            ..Limits::default()
+        });
+        let error = IntcodeError::StepLimit {
+            instruction_pointer: 0,
+        };
+        assert_eq!(block_on(spinning.read_output()), Err(error.clone()));
+        assert_eq!(spinning.stopped(), Some(Err(error)));
+    }
+}
@@ -0,0 +1,646 @@
+use std::fmt::Display;
+use std::panic::{self, AssertUnwindSafe};
+
+use super::{Backend, ExecuteResult, IntcodeError, Limits, Machine, Memory};
+
+/// Settings of a fuzzing campaign.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Config {
+    pub seed: u64,
+    pub cases: usize,
+    /// Instructions per generated program.
+    pub max_instructions: usize,
+    /// Input values per case.
+    pub max_inputs: usize,
+    /// Step budget of every run, generated programs may loop forever.
+    pub max_steps: u64,
+    /// Memory limit of every run, small so bounds checks get exercised.
+    pub memory_limit: usize,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            seed: 2019,
+            cases: 1000,
+            max_instructions: 24,
+            max_inputs: 4,
+            max_steps: 1000,
+            memory_limit: 256,
+        }
+    }
+}
+
+/// Fault classes shared by the machine and the reference model.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fault {
+    ReadOutOfBounds,
+    WriteOutOfBounds,
+    NegativeRelativeAddress,
+    ImmediateModeWrite,
+    UnknownOpcode,
+    UnknownParameterMode,
+    TruncatedInstruction,
+    /// Run stopped before halting, never raised by an executed instruction.
+    Stopped,
+}
+
+impl From<&IntcodeError> for Fault {
+    fn from(error: &IntcodeError) -> Self {
+        match error {
+            IntcodeError::ReadOutOfBounds { .. } => Fault::ReadOutOfBounds,
+            IntcodeError::WriteOutOfBounds { .. } => Fault::WriteOutOfBounds,
+            IntcodeError::NegativeRelativeAddress { .. } => Fault::NegativeRelativeAddress,
+            IntcodeError::ImmediateModeWrite { .. } => Fault::ImmediateModeWrite,
+            IntcodeError::UnknownOpcode { .. } => Fault::UnknownOpcode,
+            IntcodeError::UnknownParameterMode { .. } => Fault::UnknownParameterMode,
+            IntcodeError::TruncatedInstruction { .. } => Fault::TruncatedInstruction,
+            IntcodeError::InputExhausted { .. }
+            | IntcodeError::StepLimit { .. }
+            | IntcodeError::TimeLimit { .. }
+            | IntcodeError::InfiniteLoop { .. }
+            | IntcodeError::InvalidProgram(_)
+            | IntcodeError::InvalidInput(_) => Fault::Stopped,
+        }
+    }
+}
+
+/// How a run ended.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Status {
+    Halt(i64),
+    WaitingInput,
+    /// Fault and the instruction pointer it happened at.
+    Fault(Fault, i64),
+    StepLimit,
+    /// The implementation panicked.
+    Panic(String),
+}
+
+/// Observable state at the end of a run.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Run {
+    pub status: Status,
+    pub output: Vec<i64>,
+    pub instruction_pointer: i64,
+    pub relative_base: i64,
+    /// Memory up to the limit, trailing zeros trimmed.
+    pub memory: Vec<i64>,
+}
+
+impl Run {
+    /// Name of the first field that differs.
+    pub fn difference(&self, other: &Run) -> Option<&'static str> {
+        if self.status != other.status {
+            Some("status")
+        } else if self.output != other.output {
+            Some("output")
+        } else if self.instruction_pointer != other.instruction_pointer {
+            Some("instruction pointer")
+        } else if self.relative_base != other.relative_base {
+            Some("relative base")
+        } else if self.memory != other.memory {
+            Some("memory")
+        } else {
+            None
+        }
+    }
+}
+
+impl Display for Run {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "  status: {:?}", self.status)?;
+        writeln!(f, "  output: {:?}", self.output)?;
+        writeln!(
+            f,
+            "  IP: {}  RB: {}",
+            self.instruction_pointer, self.relative_base
+        )?;
+        write!(f, "  memory: {:?}", self.memory)
+    }
+}
+
+fn trimmed(mut memory: Vec<i64>) -> Vec<i64> {
+    while memory.last() == Some(&0) {
+        memory.pop();
+    }
+    memory
+}
+
+/// Runs `program` on [`Machine`] with the given backend.
+pub fn machine_run(program: &[i64], input: &[i64], backend: Backend, config: &Config) -> Run {
+    let mut machine = Machine::new_with_input(
+        Memory::new(program.to_vec()).with_limit(config.memory_limit),
+        input.to_vec(),
+    )
+    .with_backend(backend)
+    .with_limits(Limits {
+        max_steps: Some(config.max_steps),
+        ..Limits::default()
+    });
+
+    let result = panic::catch_unwind(AssertUnwindSafe(|| machine.execute()));
+    let status = match result {
+        Ok(ExecuteResult::Halt(value)) => Status::Halt(value),
+        Ok(ExecuteResult::WaitingInput) => Status::WaitingInput,
+        Ok(ExecuteResult::Error(error)) => Status::Fault(
+            Fault::from(&error),
+            error.instruction_pointer().map_or(-1, |ip| ip as i64),
+        ),
+        Ok(ExecuteResult::StepLimit) => Status::StepLimit,
+        Ok(other) => Status::Panic(format!("unexpected {other:?}")),
+        Err(payload) => Status::Panic(panic_message(payload)),
+    };
+
+    let memory = (0..config.memory_limit.min(machine.memory.len()))
+        .map(|a| machine.memory.get(a))
+        .collect();
+    Run {
+        status,
+        output: machine.take_output(),
+        instruction_pointer: machine.instruction_pointer as i64,
+        relative_base: machine.relative_base,
+        memory: trimmed(memory),
+    }
+}
+
+fn panic_message(payload: Box<dyn std::any::Any + Send>) -> String {
+    payload
+        .downcast_ref::<&str>()
+        .map(|s| s.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "panic".to_string())
+}
+
+/// Straightforward interpreter written from the puzzle statements, the model
+/// [`Machine`] is checked against.
+///
+/// Arithmetic wraps. Operands are resolved left to right, then the write
+/// address, so the first bad operand decides the fault. `IN` resolves its
+/// address before waiting for input. Mode digits must be 0, 1 or 2, and
+/// every address must be below the memory limit.
+pub fn reference_run(program: &[i64], input: &[i64], config: &Config) -> Run {
+    let limit = config.memory_limit;
+    let mut memory = program.to_vec();
+    memory.truncate(limit);
+    memory.resize(limit, 0);
+
+    let mut model = Model {
+        memory,
+        ip: 0,
+        rb: 0,
+        input: input.iter().copied().collect(),
+        output: Vec::new(),
+    };
+
+    let mut status = Status::StepLimit;
+    for _ in 0..config.max_steps {
+        match model.step() {
+            Ok(None) => (),
+            Ok(Some(end)) => {
+                status = end;
+                break;
+            }
+            Err(fault) => {
+                status = Status::Fault(fault, model.ip);
+                break;
+            }
+        }
+    }
+
+    Run {
+        status,
+        output: model.output,
+        instruction_pointer: model.ip,
+        relative_base: model.rb,
+        memory: trimmed(model.memory),
+    }
+}
+
+struct Model {
+    memory: Vec<i64>,
+    ip: i64,
+    rb: i64,
+    input: std::collections::VecDeque<i64>,
+    output: Vec<i64>,
+}
+
+impl Model {
+    fn word(&self, address: i64) -> Option<i64> {
+        usize::try_from(address)
+            .ok()
+            .and_then(|a| self.memory.get(a).copied())
+    }
+
+    /// Address of operand `index` (1-based) for the given mode.
+    fn address(&self, mode: i64, index: i64, out_of_bounds: Fault) -> Result<usize, Fault> {
+        let value = self.memory[(self.ip + index) as usize];
+        let address = match mode {
+            0 => value,
+            2 => {
+                let address = value.wrapping_add(self.rb);
+                if address < 0 {
+                    return Err(Fault::NegativeRelativeAddress);
+                }
+                address
+            }
+            _ => unreachable!("immediate operands have no address"),
+        };
+        match usize::try_from(address) {
+            Ok(address) if address < self.memory.len() => Ok(address),
+            _ => Err(out_of_bounds),
+        }
+    }
+
+    fn read(&self, modes: &[i64], index: i64) -> Result<i64, Fault> {
+        match modes[index as usize - 1] {
+            1 => Ok(self.memory[(self.ip + index) as usize]),
+            mode => Ok(self.memory[self.address(mode, index, Fault::ReadOutOfBounds)?]),
+        }
+    }
+
+    fn target(&self, modes: &[i64], index: i64) -> Result<usize, Fault> {
+        match modes[index as usize - 1] {
+            1 => Err(Fault::ImmediateModeWrite),
+            mode => self.address(mode, index, Fault::WriteOutOfBounds),
+        }
+    }
+
+    /// Executes one instruction, `Some` status when the run ends.
+    fn step(&mut self) -> Result<Option<Status>, Fault> {
+        let Some(opcode) = self.word(self.ip) else {
+            return Err(Fault::ReadOutOfBounds);
+        };
+        if opcode < 0 {
+            return Err(Fault::UnknownOpcode);
+        }
+        let size: i64 = match opcode % 100 {
+            1 | 2 | 7 | 8 => 4,
+            5 | 6 => 3,
+            3 | 4 | 9 => 2,
+            99 => 1,
+            _ => return Err(Fault::UnknownOpcode),
+        };
+        if self.ip + size > self.memory.len() as i64 {
+            return Err(Fault::TruncatedInstruction);
+        }
+
+        let mut modes = Vec::new();
+        let mut digits = opcode / 100;
+        while digits > 0 {
+            if digits % 10 > 2 {
+                return Err(Fault::UnknownParameterMode);
+            }
+            modes.push(digits % 10);
+            digits /= 10;
+        }
+        modes.resize(modes.len().max(3), 0);
+
+        let next = self.ip + size;
+        match opcode % 100 {
+            1 | 2 | 7 | 8 => {
+                let a = self.read(&modes, 1)?;
+                let b = self.read(&modes, 2)?;
+                let target = self.target(&modes, 3)?;
+                self.memory[target] = match opcode % 100 {
+                    1 => a.wrapping_add(b),
+                    2 => a.wrapping_mul(b),
+                    7 => (a < b) as i64,
+                    _ => (a == b) as i64,
+                };
+            }
+            3 => {
+                let target = self.target(&modes, 1)?;
+                let Some(value) = self.input.pop_front() else {
+                    return Ok(Some(Status::WaitingInput));
+                };
+                self.memory[target] = value;
+            }
+            4 => {
+                let value = self.read(&modes, 1)?;
+                self.output.push(value);
+            }
+            5 | 6 => {
+                let condition = self.read(&modes, 1)?;
+                if (condition != 0) == (opcode % 100 == 5) {
+                    self.ip = self.read(&modes, 2)?;
+                    return Ok(None);
+                }
+            }
+            9 => {
+                let value = self.read(&modes, 1)?;
+                self.rb = self.rb.wrapping_add(value);
+            }
+            _ => {
+                self.ip = next;
+                return Ok(Some(Status::Halt(self.memory[0])));
+            }
+        }
+        self.ip = next;
+        Ok(None)
+    }
+}
+
+/// Input and program on which an implementation disagrees with the model.
+#[derive(Debug, Clone)]
+pub struct Counterexample {
+    pub program: Vec<i64>,
+    pub input: Vec<i64>,
+    pub backend: Backend,
+    pub machine: Run,
+    pub reference: Run,
+}
+
+impl Display for Counterexample {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let program: Vec<String> = self.program.iter().map(|v| v.to_string()).collect();
+        writeln!(f, "program: {}", program.join(","))?;
+        writeln!(f, "input: {:?}", self.input)?;
+        writeln!(
+            f,
+            "{} differs ({:?} backend):",
+            self.machine
+                .difference(&self.reference)
+                .unwrap_or("nothing"),
+            self.backend
+        )?;
+        writeln!(f, "machine:\n{}", self.machine)?;
+        write!(f, "reference:\n{}", self.reference)
+    }
+}
+
+/// Runs one case on every backend, the first disagreement with the model.
+pub fn check(program: &[i64], input: &[i64], config: &Config) -> Option<Counterexample> {
+    let reference = reference_run(program, input, config);
+    [Backend::Interpreter, Backend::Cached]
+        .into_iter()
+        .find_map(|backend| {
+            let machine = machine_run(program, input, backend, config);
+            machine.difference(&reference)?;
+            Some(Counterexample {
+                program: program.to_vec(),
+                input: input.to_vec(),
+                backend,
+                machine,
+                reference: reference.clone(),
+            })
+        })
+}
+
+/// Shrinks a counterexample while the implementations still disagree:
+/// drops input values and trailing words, then moves words towards zero.
+pub fn minimize(counterexample: Counterexample, config: &Config) -> Counterexample {
+    let mut best = counterexample;
+    loop {
+        let candidates = shrink_candidates(&best.program, &best.input);
+        let smaller = candidates
+            .into_iter()
+            .find_map(|(program, input)| check(&program, &input, config));
+        match smaller {
+            Some(smaller) => best = smaller,
+            None => return best,
+        }
+    }
+}
+
+fn shrink_candidates(program: &[i64], input: &[i64]) -> Vec<(Vec<i64>, Vec<i64>)> {
+    let mut candidates = Vec::new();
+    for i in 0..input.len() {
+        let mut smaller = input.to_vec();
+        smaller.remove(i);
+        candidates.push((program.to_vec(), smaller));
+    }
+    for len in 0..program.len() {
+        candidates.push((program[..len].to_vec(), input.to_vec()));
+    }
+    for (i, &word) in program.iter().enumerate() {
+        let simpler: &[i64] = match word {
+            0 => &[],
+            1 | -1 => &[0],
+            _ => &[0, word / 2, word.signum()],
+        };
+        for &value in simpler {
+            let mut smaller = program.to_vec();
+            smaller[i] = value;
+            candidates.push((smaller, input.to_vec()));
+        }
+    }
+    for (i, &value) in input.iter().enumerate() {
+        if value != 0 {
+            let mut smaller = input.to_vec();
+            smaller[i] = value / 2;
+            candidates.push((program.to_vec(), smaller));
+        }
+    }
+    candidates
+}
+
+/// SplitMix64, enough randomness for program generation.
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Rng(seed)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Value in `0..bound`.
+    pub fn below(&mut self, bound: u64) -> u64 {
+        self.next_u64() % bound.max(1)
+    }
+
+    pub fn range(&mut self, low: i64, high: i64) -> i64 {
+        low + self.below((high - low + 1) as u64) as i64
+    }
+
+    /// True with probability `1 / n`.
+    pub fn one_in(&mut self, n: u64) -> bool {
+        self.below(n) == 0
+    }
+}
+
+/// Random program of mostly valid instructions with operands pointing into
+/// the program and a data area after it, sprinkled with bad modes, opcodes
+/// and addresses.
+pub fn generate_program(rng: &mut Rng, config: &Config) -> Vec<i64> {
+    const OPCODES: [i64; 10] = [1, 2, 3, 4, 5, 6, 7, 8, 9, 99];
+    const SIZES: [usize; 10] = [4, 4, 2, 2, 3, 3, 4, 4, 2, 1];
+
+    let count = 1 + rng.below(config.max_instructions as u64) as usize;
+    let kinds: Vec<usize> = (0..count)
+        .map(|i| match i + 1 == count || rng.one_in(20) {
+            true => 9,
+            false => rng.below(9) as usize,
+        })
+        .collect();
+    let starts: Vec<i64> = kinds
+        .iter()
+        .scan(0, |address, &kind| {
+            let start = *address;
+            *address += SIZES[kind] as i64;
+            Some(start)
+        })
+        .collect();
+    let code_len = kinds.iter().map(|&kind| SIZES[kind]).sum::<usize>() as i64;
+    let data_len = 1 + rng.below(8) as i64;
+
+    let mut program = Vec::new();
+    for &kind in &kinds {
+        let opcode = OPCODES[kind];
+        let params = SIZES[kind] - 1;
+        let writes = matches!(opcode, 1 | 2 | 3 | 7 | 8);
+        let jumps = matches!(opcode, 5 | 6);
+
+        let mut modes = Vec::new();
+        for p in 0..params {
+            let mode = match rng.below(40) {
+                0 => 3,
+                1..=12 => 1,
+                13..=24 => 2,
+                _ => 0,
+            };
+            let is_write = writes && p + 1 == params;
+            modes.push(if is_write && mode == 1 && !rng.one_in(4) {
+                0
+            } else {
+                mode
+            });
+        }
+        let word = if rng.one_in(60) {
+            rng.range(-5, 120)
+        } else {
+            opcode + modes.iter().rev().fold(0, |acc, mode| acc * 10 + mode) * 100
+        };
+        program.push(word);
+
+        for (p, &mode) in modes.iter().enumerate() {
+            let value = match (mode, jumps && p == 1) {
+                (1, true) => starts[rng.below(starts.len() as u64) as usize],
+                (1, false) => rng.range(-3, 30),
+                (2, _) => rng.range(-4, code_len + data_len),
+                _ if rng.one_in(30) => match rng.below(3) {
+                    0 => -1,
+                    1 => config.memory_limit as i64 + rng.range(-2, 2),
+                    _ => rng.range(0, config.memory_limit as i64 - 1),
+                },
+                _ => rng.range(0, code_len + data_len - 1),
+            };
+            program.push(value);
+        }
+    }
+    for _ in 0..data_len {
+        program.push(rng.range(-3, 20));
+    }
+    program
+}
+
+pub fn generate_input(rng: &mut Rng, config: &Config) -> Vec<i64> {
+    let count = rng.below(config.max_inputs as u64 + 1);
+    (0..count).map(|_| rng.range(-5, 50)).collect()
+}
+
+/// Generates and checks `config.cases` programs, returning the first
+/// counterexample minimized.
+///
+/// # Examples
+/// ```
+/// use advent_of_code::intcode::fuzz::*;
+///
+/// let config = Config {
+///     cases: 300,
+///     ..Config::default()
+/// };
+/// if let Err(counterexample) = fuzz(&config) {
+///     panic!("{counterexample}");
+/// }
+/// ```
+///
+/// Panics of the machine count as divergences, the panic hook is silenced
+/// while the campaign runs.
+pub fn fuzz(config: &Config) -> Result<(), Box<Counterexample>> {
+    let hook = panic::take_hook();
+    panic::set_hook(Box::new(|_| {}));
+
+    let mut rng = Rng::new(config.seed);
+    let mut result = Ok(());
+    for _ in 0..config.cases {
+        let program = generate_program(&mut rng, config);
+        let input = generate_input(&mut rng, config);
+        if let Some(counterexample) = check(&program, &input, config) {
+            result = Err(Box::new(minimize(counterexample, config)));
+            break;
+        }
+    }
+
+    panic::set_hook(hook);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn model_agrees_on_edge_cases() {
+        let config = Config::default();
+        let max = i64::MAX;
+        let cases: [(&[i64], Status); 5] = [
+            // wrapping addition
+            (&[1101, max, 1, 0, 4, 0, 99], Status::Halt(i64::MIN)),
+            // ARB -1, then reads [rb+0]
+            (
+                &[109, -1, 204, 0, 99],
+                Status::Fault(Fault::NegativeRelativeAddress, 2),
+            ),
+            (
+                &[1101, 0, 0, 256, 99],
+                Status::Fault(Fault::WriteOutOfBounds, 0),
+            ),
+            (&[3, 0, 99], Status::WaitingInput),
+            (&[1105, 1, 0], Status::StepLimit),
+        ];
+        for (program, status) in cases {
+            assert_eq!(
+                reference_run(program, &[], &config).status,
+                status,
+                "{program:?}"
+            );
+            assert!(check(program, &[], &config).is_none(), "{program:?}");
+        }
+    }
+
+    #[test]
+    fn campaign_finds_no_divergence() {
+        let config = Config {
+            seed: 7,
+            cases: 200,
+            ..Config::default()
+        };
+        assert!(fuzz(&config).is_ok());
+
+        let mut rng = Rng::new(config.seed);
+        let program = generate_program(&mut rng, &config);
+        assert_eq!(
+            program,
+            generate_program(&mut Rng::new(config.seed), &config)
+        );
+        assert!((0..100).all(|_| (-2..=2).contains(&rng.range(-2, 2))));
+    }
+
+    #[test]
+    fn shrinks_towards_smaller_cases() {
+        let candidates = shrink_candidates(&[1002, -1], &[8]);
+        assert!(candidates.contains(&(vec![1002, -1], vec![])));
+        assert!(candidates.contains(&(vec![], vec![8])));
+        assert!(candidates.contains(&(vec![501, -1], vec![8])));
+        assert!(candidates.contains(&(vec![1002, 0], vec![8])));
+        assert!(candidates.contains(&(vec![1002, -1], vec![4])));
+    }
+}
@@ -0,0 +1,221 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+use super::trace::{MemoryWrite, TraceStep, Tracer};
+use super::Machine;
+
+/// What an executed instruction changed, enough to revert it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Undo {
+    pub instruction_pointer: usize,
+    pub relative_base: i64,
+    pub write: Option<MemoryWrite>,
+    pub input: Option<i64>,
+    pub output: Option<i64>,
+}
+
+impl From<&TraceStep> for Undo {
+    fn from(step: &TraceStep) -> Self {
+        Undo {
+            instruction_pointer: step.instruction_pointer,
+            relative_base: step.relative_base,
+            write: step.write,
+            input: step.input,
+            output: step.output,
+        }
+    }
+}
+
+/// Most recent write to an address still in the history.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LastWrite {
+    /// Address of the writing instruction.
+    pub instruction_pointer: usize,
+    pub write: MemoryWrite,
+    /// Instructions executed since, 0 for the last one.
+    pub steps_ago: usize,
+}
+
+/// Bounded undo log of a machine, clones share the log.
+///
+/// Installed as the machine tracer it records every executed instruction,
+/// dropping the oldest ones past its capacity. Memory edited outside of
+/// execution is only tracked once passed to [`History::record`].
+///
+/// # Examples
+/// ```
+/// use advent_of_code::intcode::*;
+/// use advent_of_code::intcode::history::History;
+///
+/// let program: Program = "3,9,1002,9,3,9,4,9,99,0".parse().unwrap();
+/// let history = History::new(History::DEFAULT_CAPACITY);
+/// let mut machine = program.machine_with_input(vec![14]);
+/// machine.set_tracer(history.clone());
+/// machine.execute();
+/// assert_eq!(machine.output(), &vec![42]);
+///
+/// let write = history.last_write(9).unwrap();
+/// assert_eq!((write.instruction_pointer, write.write.old, write.steps_ago), (2, 14, 2));
+///
+/// history.step_back(&mut machine);
+/// history.step_back(&mut machine);
+/// assert_eq!(machine.instruction_pointer, 6);
+/// assert!(machine.output().is_empty());
+///
+/// while history.step_back(&mut machine).is_some() {}
+/// assert_eq!(machine.memory[9], 0);
+/// assert_eq!(machine.input(), &[14]);
+/// ```
+#[derive(Clone)]
+pub struct History {
+    log: Arc<Mutex<Log>>,
+}
+
+struct Log {
+    undos: VecDeque<Undo>,
+    capacity: usize,
+}
+
+impl History {
+    /// Recent instructions kept, about 5 MB of undo records.
+    pub const DEFAULT_CAPACITY: usize = 1 << 16;
+
+    pub fn new(capacity: usize) -> Self {
+        History {
+            log: Arc::new(Mutex::new(Log {
+                undos: VecDeque::new(),
+                capacity,
+            })),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.log.lock().unwrap().undos.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.log.lock().unwrap().capacity
+    }
+
+    pub fn clear(&self) {
+        self.log.lock().unwrap().undos.clear();
+    }
+
+    /// Appends an undo record, dropping the oldest one when full.
+    pub fn record(&self, undo: Undo) {
+        let mut log = self.log.lock().unwrap();
+        if log.capacity == 0 {
+            return;
+        }
+        if log.undos.len() == log.capacity {
+            log.undos.pop_front();
+        }
+        log.undos.push_back(undo);
+    }
+
+    /// Last recorded instruction, not reverted.
+    pub fn last(&self) -> Option<Undo> {
+        self.log.lock().unwrap().undos.back().copied()
+    }
+
+    /// Up to `count` last recorded instructions, newest first.
+    pub fn recent(&self, count: usize) -> Vec<Undo> {
+        let log = self.log.lock().unwrap();
+        log.undos.iter().rev().take(count).copied().collect()
+    }
+
+    /// Reverts the last recorded instruction, giving back consumed input
+    /// and dropping produced output. `None` once the history is exhausted.
+    pub fn step_back(&self, machine: &mut Machine) -> Option<Undo> {
+        let undo = self.log.lock().unwrap().undos.pop_back()?;
+        if let Some(write) = undo.write {
+            machine.memory.set(write.address, write.old);
+        }
+        if let Some(value) = undo.input {
+            machine.input_mut().push_front(value);
+        }
+        if undo.output.is_some() {
+            machine.output_mut().pop();
+        }
+        machine.instruction_pointer = undo.instruction_pointer;
+        machine.relative_base = undo.relative_base;
+        Some(undo)
+    }
+
+    /// Who last wrote `address`, if the write is still in the history.
+    pub fn last_write(&self, address: usize) -> Option<LastWrite> {
+        let log = self.log.lock().unwrap();
+        log.undos
+            .iter()
+            .rev()
+            .enumerate()
+            .find_map(|(steps_ago, undo)| {
+                let write = undo.write.filter(|write| write.address == address)?;
+                Some(LastWrite {
+                    instruction_pointer: undo.instruction_pointer,
+                    write,
+                    steps_ago,
+                })
+            })
+    }
+}
+
+impl Tracer for History {
+    fn trace(&mut self, step: &TraceStep) {
+        self.record(Undo::from(step));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::Program;
+
+    /// Counts down from 3, outputting every value.
+    fn countdown(history: &History) -> Machine {
+        let program: Program = "4,11,1001,11,-1,11,1005,11,0,99,0,3".parse().unwrap();
+        let mut machine = program.machine();
+        machine.set_tracer(history.clone());
+        machine.execute();
+        machine
+    }
+
+    #[test]
+    fn keeps_the_most_recent_steps() {
+        let history = History::new(4);
+        let mut machine = countdown(&history);
+        assert_eq!(machine.output(), &[3, 2, 1]);
+        assert_eq!((history.len(), history.capacity()), (4, 4));
+
+        let recent: Vec<usize> = history
+            .recent(2)
+            .iter()
+            .map(|u| u.instruction_pointer)
+            .collect();
+        assert_eq!(recent, [9, 6]);
+        assert_eq!(history.last_write(11).unwrap().steps_ago, 2);
+
+        while history.step_back(&mut machine).is_some() {}
+        assert_eq!((machine.instruction_pointer, machine.memory[11]), (0, 1));
+        assert_eq!(machine.output(), &[3, 2]);
+    }
+
+    #[test]
+    fn records_nothing_without_capacity() {
+        let history = History::new(0);
+        countdown(&history);
+        history.record(Undo {
+            instruction_pointer: 0,
+            relative_base: 0,
+            write: None,
+            input: None,
+            output: None,
+        });
+        assert!(history.is_empty());
+        assert_eq!(history.last(), None);
+    }
+}
@@ -0,0 +1,237 @@
+use std::collections::VecDeque;
+use std::sync::mpsc::{Receiver, Sender};
+
+/// Source of values for `IN` instructions.
+pub trait Input {
+    /// Next value, `None` if there is nothing to read yet.
+    ///
+    /// A `None` makes the machine stop with `ExecuteResult::WaitingInput`
+    /// without consuming the instruction, so execution can resume later.
+    fn read(&mut self) -> Option<i64>;
+}
+
+/// Character of an ASCII output value, `None` for anything outside 0..=127.
+pub fn ascii_char(value: i64) -> Option<char> {
+    u8::try_from(value)
+        .ok()
+        .filter(u8::is_ascii)
+        .map(char::from)
+}
+
+/// Sink for values of `OUT` instructions.
+pub trait Output {
+    fn write(&mut self, value: i64);
+}
+
+/// Queue of pending values, the default machine input.
+impl Input for VecDeque<i64> {
+    fn read(&mut self) -> Option<i64> {
+        self.pop_front()
+    }
+}
+
+/// Collects every value, the default machine output.
+impl Output for Vec<i64> {
+    fn write(&mut self, value: i64) {
+        self.push(value);
+    }
+}
+
+impl Output for VecDeque<i64> {
+    fn write(&mut self, value: i64) {
+        self.push_back(value);
+    }
+}
+
+/// Non-blocking, waits for input while the channel is empty or closed.
+impl Input for Receiver<i64> {
+    fn read(&mut self) -> Option<i64> {
+        self.try_recv().ok()
+    }
+}
+
+/// Values written after the receiver is dropped are lost.
+impl Output for Sender<i64> {
+    fn write(&mut self, value: i64) {
+        let _ = self.send(value);
+    }
+}
+
+/// Channel input blocking until a value arrives, for machines running on
+/// their own thread. Waits for input once every sender is dropped.
+pub struct BlockingReceiver(pub Receiver<i64>);
+
+impl Input for BlockingReceiver {
+    fn read(&mut self) -> Option<i64> {
+        self.0.recv().ok()
+    }
+}
+
+/// Input produced on demand by a closure.
+///
+/// # Examples
+/// ```
+/// use advent_of_code::intcode::*;
+///
+/// let program: Program = "3,0,4,0,3,0,4,0,99".parse().unwrap();
+/// let mut next = 0;
+/// let mut seen = Vec::new();
+/// let input = InputFn(|| {
+///     next += 10;
+///     Some(next)
+/// });
+/// let mut machine = program.machine_with_io(input, OutputFn(|v| seen.push(v)));
+/// machine.execute();
+/// drop(machine);
+/// assert_eq!(seen, vec![10, 20]);
+/// ```
+pub struct InputFn<F: FnMut() -> Option<i64>>(pub F);
+
+impl<F: FnMut() -> Option<i64>> Input for InputFn<F> {
+    fn read(&mut self) -> Option<i64> {
+        (self.0)()
+    }
+}
+
+/// Output handed to a closure as it is produced.
+pub struct OutputFn<F: FnMut(i64)>(pub F);
+
+impl<F: FnMut(i64)> Output for OutputFn<F> {
+    fn write(&mut self, value: i64) {
+        (self.0)(value)
+    }
+}
+
+/// Input pulled lazily from an iterator, waits for input once it is exhausted.
+pub struct InputIter<T: Iterator<Item = i64>>(pub T);
+
+impl<T: Iterator<Item = i64>> Input for InputIter<T> {
+    fn read(&mut self) -> Option<i64> {
+        self.0.next()
+    }
+}
+
+/// Text fed to the machine one character code at a time.
+#[derive(Debug, Clone, Default)]
+pub struct AsciiInput {
+    pending: VecDeque<i64>,
+}
+
+impl AsciiInput {
+    pub fn new(text: &str) -> Self {
+        let mut input = AsciiInput::default();
+        input.push_str(text);
+        input
+    }
+
+    pub fn push_str(&mut self, text: &str) {
+        self.pending.extend(text.chars().map(|c| c as i64));
+    }
+
+    /// Queues `line` followed by a newline.
+    pub fn push_line(&mut self, line: &str) {
+        self.push_str(line);
+        self.pending.push_back('\n' as i64);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+}
+
+impl Input for AsciiInput {
+    fn read(&mut self) -> Option<i64> {
+        self.pending.pop_front()
+    }
+}
+
+/// Output decoded as text, values outside ASCII are kept apart in `values`.
+#[derive(Debug, Clone, Default)]
+pub struct AsciiOutput {
+    pub text: String,
+    pub values: Vec<i64>,
+}
+
+impl AsciiOutput {
+    pub fn new() -> Self {
+        AsciiOutput::default()
+    }
+}
+
+impl Output for AsciiOutput {
+    fn write(&mut self, value: i64) {
+        match ascii_char(value) {
+            Some(c) => self.text.push(c),
+            None => self.values.push(value),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc::channel;
+
+    use super::*;
+    use crate::intcode::fixtures::echo;
+    use crate::intcode::{ExecuteResult, IntcodeError, Machine, Memory};
+
+    #[test]
+    fn waits_without_consuming_the_instruction() {
+        let mut machine = echo().machine_with_io(InputIter([1, 2].into_iter()), Vec::new());
+        assert_eq!(machine.execute(), ExecuteResult::WaitingInput);
+        assert_eq!(machine.instruction_pointer, 0);
+        assert_eq!(machine.output(), &[1, 2]);
+    }
+
+    #[test]
+    fn channels_connect_machines() {
+        let (sender, receiver) = channel();
+        let (echo_sender, echo_receiver) = channel();
+        let mut machine = echo().machine_with_io(receiver, echo_sender);
+
+        sender.send(5).unwrap();
+        assert_eq!(machine.execute(), ExecuteResult::WaitingInput);
+        sender.send(6).unwrap();
+        drop(sender);
+        assert_eq!(machine.execute(), ExecuteResult::WaitingInput);
+        assert_eq!(echo_receiver.try_iter().collect::<Vec<_>>(), [5, 6]);
+    }
+
+    #[test]
+    fn ascii_io_splits_text_and_values() {
+        let mut input = AsciiInput::new("a");
+        input.push_line("b");
+        let mut machine = echo().machine_with_io(input, AsciiOutput::new());
+        machine.input_mut().push_str("\u{e9}");
+        machine.execute();
+
+        let output = machine.output();
+        assert_eq!(
+            (output.text.as_str(), &output.values[..]),
+            ("ab\n", &[233][..])
+        );
+        assert!(machine.input().is_empty());
+        assert_eq!(
+            (ascii_char(127), ascii_char(128), ascii_char(-1)),
+            (Some('\u{7f}'), None, None)
+        );
+    }
+
+    #[test]
+    fn failed_writes_keep_the_input() {
+        let memory = Memory::new(vec![3, 50, 99]).with_limit(10);
+        let mut machine = Machine::new_with_input(memory, vec![5]);
+        assert_eq!(
+            machine.execute(),
+            ExecuteResult::Error(IntcodeError::WriteOutOfBounds {
+                address: 50,
+                instruction_pointer: 0
+            })
+        );
+        assert_eq!(machine.input(), &[5]);
+
+        machine.memory = machine.memory.clone().with_limit(100);
+        assert_eq!(machine.execute(), ExecuteResult::Halt(3));
+        assert_eq!(machine.memory.get(50), 5);
+    }
+}
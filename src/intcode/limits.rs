@@ -0,0 +1,160 @@
+use std::env;
+use std::sync::OnceLock;
+use std::time::Duration;
+
+/// Budgets of a machine, charged over all of its
+/// [`Machine::execute`](super::Machine::execute) calls until the limits are
+/// set again.
+///
+/// Machines are unlimited by default. Binaries opt into the `INTCODE_*`
+/// variables with [`Limits::from_env`], `cargo all` sets a timeout this way.
+///
+/// # Examples
+/// ```
+/// use advent_of_code::intcode::*;
+///
+/// // jumps to itself forever
+/// let program: Program = "1105,1,0".parse().unwrap();
+/// let mut machine = program.machine().with_limits(Limits {
+///     max_steps: Some(1000),
+///     ..Limits::default()
+/// });
+/// assert_eq!(machine.execute(), ExecuteResult::StepLimit);
+///
+/// machine.set_limits(Limits {
+///     detect_loops: true,
+///     ..Limits::default()
+/// });
+/// assert_eq!(machine.execute(), ExecuteResult::InfiniteLoop(0));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Limits {
+    /// Instructions executed before giving up with `StepLimit`.
+    pub max_steps: Option<u64>,
+    /// Wall-clock time spent executing before giving up with `TimeLimit`.
+    pub timeout: Option<Duration>,
+    /// Stops with `InfiniteLoop` once the instruction pointer, relative base
+    /// and memory repeat at a backward jump with no IO in between. Hashes
+    /// the whole memory on every backward jump, so it slows loops down.
+    pub detect_loops: bool,
+}
+
+impl Limits {
+    /// Environment variable holding the default step budget.
+    pub const MAX_STEPS_VAR: &'static str = "INTCODE_MAX_STEPS";
+    /// Environment variable holding the default wall-clock budget in milliseconds.
+    pub const TIMEOUT_VAR: &'static str = "INTCODE_TIMEOUT_MS";
+    /// Environment variable turning loop detection on by default (`1`).
+    pub const DETECT_LOOPS_VAR: &'static str = "INTCODE_DETECT_LOOPS";
+
+    /// Limits from the `INTCODE_*` environment variables, read once per process.
+    pub fn from_env() -> Limits {
+        static LIMITS: OnceLock<Limits> = OnceLock::new();
+        *LIMITS.get_or_init(|| {
+            let number = |name: &str| env::var(name).ok()?.trim().parse::<u64>().ok();
+            Limits {
+                max_steps: number(Limits::MAX_STEPS_VAR),
+                timeout: number(Limits::TIMEOUT_VAR).map(Duration::from_millis),
+                detect_loops: env::var(Limits::DETECT_LOOPS_VAR)
+                    .is_ok_and(|value| value.trim() == "1"),
+            }
+        })
+    }
+
+    pub fn is_unlimited(&self) -> bool {
+        *self == Limits::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::fixtures::{echo, spin};
+    use crate::intcode::{run_machine_with_limits, ExecuteResult, IntcodeError, Program};
+
+    /// Counts up at address 9 forever, outputting nothing.
+    const COUNTER: &str = "1001,9,1,9,1105,1,0,0,0,0";
+
+    #[test]
+    fn stops_and_resumes() {
+        let program: Program = COUNTER.parse().unwrap();
+        let steps = Limits {
+            max_steps: Some(10),
+            ..Limits::default()
+        };
+        let mut machine = program.machine().with_limits(steps);
+        assert_eq!(machine.execute(), ExecuteResult::StepLimit);
+        assert_eq!(machine.execute(), ExecuteResult::StepLimit);
+        assert_eq!(machine.memory[9], 5);
+
+        machine.set_limits(steps);
+        assert_eq!(machine.execute(), ExecuteResult::StepLimit);
+        assert_eq!(machine.memory[9], 10);
+
+        let timeout = Limits {
+            timeout: Some(Duration::ZERO),
+            ..Limits::default()
+        };
+        assert_eq!(
+            program.machine().with_limits(timeout).execute(),
+            ExecuteResult::TimeLimit
+        );
+    }
+
+    #[test]
+    fn charges_every_call() {
+        // three steps per echoed value, waiting on input is free
+        let mut machine = echo().machine().with_limits(Limits {
+            max_steps: Some(7),
+            ..Limits::default()
+        });
+        for value in 1..=3 {
+            machine.push_input(value);
+            machine.execute();
+        }
+        assert_eq!(machine.execute(), ExecuteResult::StepLimit);
+        assert_eq!(machine.output(), &[1, 2]);
+    }
+
+    #[test]
+    fn detects_loops_without_io_only() {
+        let detect = Limits {
+            detect_loops: true,
+            ..Limits::default()
+        };
+        assert_eq!(
+            spin().machine().with_limits(detect).execute(),
+            ExecuteResult::InfiniteLoop(0)
+        );
+
+        // memory changes every round, so the state never repeats
+        let mut counter = COUNTER
+            .parse::<Program>()
+            .unwrap()
+            .machine()
+            .with_limits(Limits {
+                max_steps: Some(1000),
+                ..detect
+            });
+        assert_eq!(counter.execute(), ExecuteResult::StepLimit);
+    }
+
+    #[test]
+    fn programs_pass_limits_on() {
+        let limits = Limits {
+            max_steps: Some(5),
+            ..Limits::default()
+        };
+        let program: Program = COUNTER.parse().unwrap();
+        assert!(program.machine().limits().is_unlimited());
+
+        let limited = program.with_limits(limits);
+        assert_eq!(limited.with_patch(9, 1).machine().limits(), limits);
+        assert_eq!(
+            run_machine_with_limits(COUNTER, "", limits),
+            Err(IntcodeError::StepLimit {
+                instruction_pointer: 4
+            })
+        );
+    }
+}
@@ -0,0 +1,310 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::sync::Arc;
+
+/// Default hard cap on addressable cells (16M cells, 128 MiB when dense).
+pub const DEFAULT_MEMORY_LIMIT: usize = 1 << 24;
+
+/// Cells per page of the sparse backing.
+pub const PAGE_SIZE: usize = 1024;
+
+/// Widest instruction (opcode + 3 parameters).
+const MAX_INSTRUCTION_SIZE: usize = 4;
+
+static ZERO: i64 = 0;
+
+/// Intcode memory, growing on write and reading unwritten cells as zero.
+///
+/// Memory is split into fixed-size pages, kept either in a dense page table
+/// or in a sparse map for programs poking huge addresses. Pages are shared
+/// between clones and copied on first write, so cloning a machine's memory
+/// costs one reference count per page. Addresses at or above the limit are
+/// rejected so runaway programs fail instead of eating the host.
+///
+/// # Examples
+/// ```
+/// use advent_of_code::intcode::Memory;
+///
+/// let mut memory = Memory::new(vec![1, 2, 3]);
+/// assert_eq!(memory.get(10_000), 0);
+/// memory[10_000] = 5;
+/// assert_eq!(memory.len(), 10_001);
+///
+/// let snapshot = memory.clone();
+/// memory[1] = 20;
+/// assert_eq!((memory[1], snapshot[1]), (20, 2));
+///
+/// let mut memory = Memory::sparse(vec![1, 2, 3]).with_limit(1 << 40);
+/// memory[1 << 35] = 7;
+/// assert_eq!(memory.get(1 << 35), 7);
+/// ```
+#[derive(Clone)]
+pub struct Memory {
+    backing: Backing,
+    limit: usize,
+    len: usize,
+    generation: u64,
+}
+
+type Page = [i64; PAGE_SIZE];
+
+#[derive(Clone)]
+enum Backing {
+    Dense(Vec<Arc<Page>>),
+    Sparse(HashMap<usize, Arc<Page>>),
+}
+
+impl Memory {
+    /// Dense memory initialized with the given image.
+    pub fn new(image: Vec<i64>) -> Self {
+        Memory::load(Backing::Dense(Vec::new()), image)
+    }
+
+    /// Sparse page-backed memory initialized with the given image.
+    pub fn sparse(image: Vec<i64>) -> Self {
+        Memory::load(Backing::Sparse(HashMap::new()), image)
+    }
+
+    fn load(backing: Backing, image: Vec<i64>) -> Self {
+        let mut memory = Memory {
+            len: 0,
+            backing,
+            limit: DEFAULT_MEMORY_LIMIT,
+            generation: 0,
+        };
+        for (address, value) in image.into_iter().enumerate() {
+            memory.set(address, value);
+        }
+        memory
+    }
+
+    /// Sets the number of addressable cells.
+    pub fn with_limit(mut self, limit: usize) -> Self {
+        self.limit = limit;
+        self
+    }
+
+    pub fn limit(&self) -> usize {
+        self.limit
+    }
+
+    /// One past the highest cell ever written (or loaded).
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Counter bumped on every write, used to notice changes.
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    pub fn is_sparse(&self) -> bool {
+        matches!(self.backing, Backing::Sparse(_))
+    }
+
+    /// Reads a cell, unwritten cells are zero.
+    pub fn get(&self, address: usize) -> i64 {
+        self.page(address / PAGE_SIZE)
+            .map_or(0, |page| page[address % PAGE_SIZE])
+    }
+
+    /// Writes a cell, growing memory as needed.
+    ///
+    /// Panics if the address is not below the limit, callers are
+    /// expected to check [`Memory::limit`] first.
+    pub fn set(&mut self, address: usize, value: i64) {
+        *self.cell_mut(address) = value;
+    }
+
+    /// Words of the instruction starting at `address`, cut at the limit.
+    pub fn instruction_words(&self, address: usize) -> Cow<'_, [i64]> {
+        let end = (address + MAX_INSTRUCTION_SIZE).min(self.limit);
+        let offset = address % PAGE_SIZE;
+        match self.page(address / PAGE_SIZE) {
+            Some(page) if offset + (end - address) <= PAGE_SIZE => {
+                Cow::Borrowed(&page[offset..offset + (end - address)])
+            }
+            _ => Cow::Owned((address..end).map(|a| self.get(a)).collect()),
+        }
+    }
+
+    /// Copies the touched part of the memory, `[0, len)`.
+    pub fn to_vec(&self) -> Vec<i64> {
+        (0..self.len).map(|a| self.get(a)).collect()
+    }
+
+    /// Allocated pages as `(page index, cells)`, ordered by index.
+    pub(super) fn pages(&self) -> Vec<(usize, &Page)> {
+        let mut pages: Vec<(usize, &Page)> = match &self.backing {
+            Backing::Dense(pages) => pages.iter().map(|p| &**p).enumerate().collect(),
+            Backing::Sparse(pages) => pages.iter().map(|(id, p)| (*id, &**p)).collect(),
+        };
+        pages.sort_unstable_by_key(|(id, _)| *id);
+        pages
+    }
+
+    /// Rebuilds memory from [`Memory::pages`], `len` and `limit`.
+    pub(super) fn from_pages(
+        sparse: bool,
+        limit: usize,
+        len: usize,
+        pages: Vec<(usize, Box<Page>)>,
+    ) -> Self {
+        let backing = if sparse {
+            Backing::Sparse(HashMap::new())
+        } else {
+            Backing::Dense(Vec::new())
+        };
+        let mut memory = Memory::load(backing, vec![]).with_limit(limit);
+        for (id, page) in pages {
+            *memory.page_mut(id) = Arc::from(page);
+        }
+        memory.len = len;
+        memory
+    }
+
+    fn page(&self, id: usize) -> Option<&Page> {
+        match &self.backing {
+            Backing::Dense(pages) => pages.get(id).map(|p| &**p),
+            Backing::Sparse(pages) => pages.get(&id).map(|p| &**p),
+        }
+    }
+
+    fn page_mut(&mut self, id: usize) -> &mut Arc<Page> {
+        match &mut self.backing {
+            Backing::Dense(pages) => {
+                if id >= pages.len() {
+                    pages.resize_with(id + 1, || Arc::new([0; PAGE_SIZE]));
+                }
+                &mut pages[id]
+            }
+            Backing::Sparse(pages) => pages.entry(id).or_insert_with(|| Arc::new([0; PAGE_SIZE])),
+        }
+    }
+
+    fn cell_mut(&mut self, address: usize) -> &mut i64 {
+        if address >= self.limit {
+            panic!(
+                "Unexpected memory address [{address}] over limit [{}]!",
+                self.limit
+            );
+        }
+        self.len = self.len.max(address + 1);
+        self.generation = self.generation.wrapping_add(1);
+        let page = self.page_mut(address / PAGE_SIZE);
+        &mut Arc::make_mut(page)[address % PAGE_SIZE]
+    }
+}
+
+impl From<Vec<i64>> for Memory {
+    fn from(image: Vec<i64>) -> Self {
+        Memory::new(image)
+    }
+}
+
+impl Debug for Memory {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.backing {
+            Backing::Dense(_) => f.debug_list().entries(self.to_vec()).finish(),
+            Backing::Sparse(_) => f
+                .debug_map()
+                .entries(
+                    self.pages()
+                        .into_iter()
+                        .map(|(id, page)| (id * PAGE_SIZE, &page[..])),
+                )
+                .finish(),
+        }
+    }
+}
+
+impl std::ops::Index<usize> for Memory {
+    type Output = i64;
+
+    fn index(&self, address: usize) -> &i64 {
+        self.page(address / PAGE_SIZE)
+            .map_or(&ZERO, |page| &page[address % PAGE_SIZE])
+    }
+}
+
+impl std::ops::IndexMut<usize> for Memory {
+    fn index_mut(&mut self, address: usize) -> &mut i64 {
+        self.cell_mut(address)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::{ExecuteResult, IntcodeError, Machine};
+
+    #[test]
+    fn grows_on_write() {
+        let mut memory = Memory::new(vec![1, 2, 3]);
+        assert_eq!((memory.len(), memory.get(5000)), (3, 0));
+
+        memory.set(PAGE_SIZE * 3 + 1, 9);
+        assert_eq!(memory.len(), PAGE_SIZE * 3 + 2);
+        assert_eq!(memory[PAGE_SIZE * 3 + 1], 9);
+        assert_eq!(memory.to_vec()[..3], [1, 2, 3]);
+        assert_eq!(memory.pages().len(), 4);
+    }
+
+    #[test]
+    fn sparse_allocates_touched_pages_only() {
+        let mut memory = Memory::sparse(vec![1]).with_limit(1 << 40);
+        memory.set(1 << 35, 7);
+        assert!(memory.is_sparse());
+        assert_eq!(memory.get(1 << 35), 7);
+        let ids: Vec<usize> = memory.pages().iter().map(|(id, _)| *id).collect();
+        assert_eq!(ids, [0, (1 << 35) / PAGE_SIZE]);
+    }
+
+    #[test]
+    fn clones_copy_pages_on_write() {
+        let mut memory = Memory::new(vec![0; 2 * PAGE_SIZE]);
+        let clone = memory.clone();
+        memory[PAGE_SIZE] = 1;
+
+        assert_eq!((memory[PAGE_SIZE], clone[PAGE_SIZE]), (1, 0));
+        let (pages, clone_pages) = (memory.pages(), clone.pages());
+        assert!(std::ptr::eq(pages[0].1, clone_pages[0].1));
+        assert!(!std::ptr::eq(pages[1].1, clone_pages[1].1));
+    }
+
+    #[test]
+    #[should_panic(expected = "over limit")]
+    fn set_over_limit_panics() {
+        Memory::new(vec![]).with_limit(10).set(10, 1);
+    }
+
+    #[test]
+    fn machine_faults_at_limit() {
+        // writes then reads address 10 of a 10 cell memory
+        let limited = |image: Vec<i64>| {
+            let mut machine = Machine::new(image);
+            machine.memory = machine.memory.clone().with_limit(10);
+            machine.execute()
+        };
+        assert_eq!(
+            limited(vec![1101, 1, 1, 10, 99]),
+            ExecuteResult::Error(IntcodeError::WriteOutOfBounds {
+                address: 10,
+                instruction_pointer: 0
+            })
+        );
+        assert_eq!(
+            limited(vec![4, 10, 99]),
+            ExecuteResult::Error(IntcodeError::ReadOutOfBounds {
+                address: 10,
+                instruction_pointer: 0
+            })
+        );
+        assert_eq!(limited(vec![1101, 1, 1, 9, 99]), ExecuteResult::Halt(1101));
+    }
+}
@@ -1,112 +1,162 @@
-pub fn run_machine_with_extended_memory(firmware: &str, input: &str) -> Option<(i64, Vec<i64>)> {
-    let firmware: Vec<i64> = firmware
-        .split(",")
-        .map(|i| i.parse().expect("Expected i64 list"))
-        .collect();
-
-    let input: Vec<i64> = if !input.is_empty() {
-        input
-            .split(",")
-            .map(|i| i.parse().expect("Expected i64 list"))
-            .collect()
-    } else {
-        vec![]
-    };
-
-    // extend memory to at least 4096
-    let ext_memory_size = 4096;
-    let mut ext_memory: Vec<i64> = Vec::with_capacity(ext_memory_size);
-    for i in 0..ext_memory_size {
-        if i < firmware.len() {
-            ext_memory.push(firmware[i]);
-        } else {
-            ext_memory.push(0);
-        }
-    }
+use std::collections::{HashSet, VecDeque};
+use std::fmt::{Debug, Display};
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::ops::Range;
+use std::time::{Duration, Instant};
+
+pub mod amplifier;
+pub mod analysis;
+pub mod ascii;
+pub mod asm;
+mod cache;
+#[cfg(test)]
+mod conformance;
+pub mod coverage;
+pub mod debugger;
+pub mod decompile;
+pub mod disasm;
+#[cfg(test)]
+mod fixtures;
+pub mod futures;
+pub mod fuzz;
+pub mod history;
+mod io;
+mod limits;
+mod memory;
+pub mod network;
+pub mod profile;
+mod program;
+mod snapshot;
+pub mod trace;
+pub mod watch;
+
+pub use cache::Backend;
+pub use io::*;
+pub use limits::Limits;
+pub use memory::*;
+pub use program::*;
+pub use snapshot::*;
+
+// memory grows on write now, kept for existing callers.
+pub fn run_machine_with_extended_memory(
+    firmware: &str,
+    input: &str,
+) -> Result<(i64, Vec<i64>), IntcodeError> {
+    run_machine(firmware, input)
+}
 
-    let mut machine = Machine::new_with_input(ext_memory, input);
-    match machine.execute() {
-        ExecuteResult::Halt(result) => Some((result, machine.output)),
-        ExecuteResult::Panic(m) => panic!("{}", m),
-        _ => panic!("Unexpected result!"),
-    }
+pub fn run_machine(memory: &str, input: &str) -> Result<(i64, Vec<i64>), IntcodeError> {
+    run_machine_with_limits(memory, input, Limits::default())
 }
 
-pub fn run_machine(memory: &str, input: &str) -> Option<(i64, Vec<i64>)> {
-    let memory: Vec<i64> = memory
-        .split(",")
-        .map(|i| i.parse().expect("Expected i64 list"))
-        .collect();
-
-    let input: Vec<i64> = if !input.is_empty() {
-        input
-            .split(",")
-            .map(|i| i.parse().expect("Expected i64 list"))
-            .collect()
+/// Runs like [`run_machine`], failing once the run goes over `limits`.
+pub fn run_machine_with_limits(
+    memory: &str,
+    input: &str,
+    limits: Limits,
+) -> Result<(i64, Vec<i64>), IntcodeError> {
+    let program: Program = memory.parse().map_err(IntcodeError::InvalidProgram)?;
+
+    let input: Vec<i64> = if !input.trim().is_empty() {
+        parse_values(input).map_err(IntcodeError::InvalidInput)?
     } else {
         vec![]
     };
 
-    let mut machine = Machine::new_with_input(memory, input);
+    let mut machine = program.with_limits(limits).machine_with_input(input);
     match machine.execute() {
-        ExecuteResult::Halt(result) => Some((result, machine.output)),
-        ExecuteResult::Panic(m) => panic!("{}", m),
-        _ => panic!("Unexpected result!"),
+        ExecuteResult::Halt(result) => Ok((result, machine.take_output())),
+        ExecuteResult::Error(error) => Err(error),
+        ExecuteResult::WaitingInput => Err(IntcodeError::InputExhausted {
+            instruction_pointer: machine.instruction_pointer,
+        }),
+        ExecuteResult::Continue => unreachable!("execute runs until the machine stops"),
+        limit => Err(limit.limit_error(machine.instruction_pointer).unwrap()),
     }
 }
 
-pub struct Machine {
-    pub memory: Vec<i64>,
+pub struct Machine<I: Input = VecDeque<i64>, O: Output = Vec<i64>> {
+    pub memory: Memory,
     pub instruction_pointer: usize,
 
-    pub input: Vec<i64>,
-    pub input_pointer: usize,
-
-    pub output: Vec<i64>,
-    pub output_pointer: usize,
+    input: I,
+    output: O,
 
     pub relative_base: i64,
+
+    tracer: Option<Box<dyn trace::Tracer>>,
+    watches: watch::Watches,
+    decoded: Option<cache::DecodeCache>,
+    limits: Limits,
+    /// Steps and time charged to the limits since they were set.
+    steps_used: u64,
+    time_used: Duration,
+}
+
+/// Steps between two wall-clock checks in [`Machine::execute`].
+const TIME_CHECK_INTERVAL: u64 = 1 << 12;
+
+/// Forks the machine, memory pages are shared copy-on-write.
+/// The tracer and watches are not cloned.
+impl<I: Input + Clone, O: Output + Clone> Clone for Machine<I, O> {
+    fn clone(&self) -> Self {
+        let mut machine = self.snapshot().machine();
+        machine.decoded = self.decoded.clone();
+        machine.limits = self.limits;
+        machine.steps_used = self.steps_used;
+        machine.time_used = self.time_used;
+        machine
+    }
 }
 
-#[allow(clippy::let_and_return)]
-#[allow(dead_code)]
 impl Machine {
-    pub fn new(memory: Vec<i64>) -> Self {
-        Machine {
-            memory,
-            instruction_pointer: 0,
-            input: Vec::new(),
-            input_pointer: 0,
-            output: Vec::new(),
-            output_pointer: 0,
-            relative_base: 0,
-        }
+    pub fn new(memory: impl Into<Memory>) -> Self {
+        Machine::with_io(memory, VecDeque::new(), Vec::new())
     }
 
-    pub fn new_with_input(memory: Vec<i64>, input: Vec<i64>) -> Self {
-        Machine {
-            memory,
-            instruction_pointer: 0,
-            input,
-            input_pointer: 0,
-            output: Vec::new(),
-            output_pointer: 0,
-            relative_base: 0,
-        }
+    pub fn new_with_input(memory: impl Into<Memory>, input: Vec<i64>) -> Self {
+        Machine::with_io(memory, input.into(), Vec::new())
     }
 
-    pub fn new_with_ascii_input(memory: Vec<i64>, input: &str) -> Self {
-        Machine {
+    pub fn new_with_ascii_input(memory: impl Into<Memory>, input: &str) -> Self {
+        Machine::with_io(
             memory,
-            instruction_pointer: 0,
-            input: input.chars().map(|c| c as i64).collect(),
-            input_pointer: 0,
-            output: Vec::new(),
-            output_pointer: 0,
-            relative_base: 0,
-        }
+            input.chars().map(|c| c as i64).collect(),
+            Vec::new(),
+        )
+    }
+}
+
+impl<O: Output> Machine<VecDeque<i64>, O> {
+    /// Queues a value for the next `IN`.
+    pub fn push_input(&mut self, value: i64) {
+        self.input.push_back(value);
+    }
+
+    pub fn extend_input(&mut self, values: impl IntoIterator<Item = i64>) {
+        self.input.extend(values);
     }
 
+    /// Queues the character codes of `text`.
+    pub fn push_ascii_input(&mut self, text: &str) {
+        self.extend_input(text.chars().map(|c| c as i64));
+    }
+}
+
+impl<I: Input> Machine<I, Vec<i64>> {
+    /// Removes everything output so far.
+    pub fn take_output(&mut self) -> Vec<i64> {
+        std::mem::take(&mut self.output)
+    }
+
+    /// Output decoded as text, values outside ASCII are left out
+    /// (see [`ascii::AsciiMachine`] to keep them).
+    pub fn get_ascii_output(&self) -> String {
+        self.output.iter().filter_map(|v| ascii_char(*v)).collect()
+    }
+}
+
+impl<I: Input + Clone, O: Output + Clone> Machine<I, O> {
     // fn get_noun(&self) -> u64 {
     //     self.memory[1]
     // }
@@ -115,139 +165,303 @@ impl Machine {
     //     self.memory[2]
     // }
 
-    pub fn clone_with_noun_verb(&self, noun: i64, verb: i64) -> Machine {
-        let mut new_memory = self.memory.clone();
-        new_memory[1] = noun;
-        new_memory[2] = verb;
+    pub fn clone_with_noun_verb(&self, noun: i64, verb: i64) -> Self {
+        let mut machine = self.clone();
+        machine.memory[1] = noun;
+        machine.memory[2] = verb;
+        machine.instruction_pointer = 0;
+        machine
+    }
 
-        Machine {
-            memory: new_memory,
-            instruction_pointer: 0,
+    /// Captures the machine state, sharing memory pages until either side writes.
+    pub fn snapshot(&self) -> Snapshot<I, O> {
+        Snapshot {
+            memory: self.memory.clone(),
+            instruction_pointer: self.instruction_pointer,
             input: self.input.clone(),
-            input_pointer: self.input_pointer,
             output: self.output.clone(),
-            output_pointer: self.output_pointer,
             relative_base: self.relative_base,
         }
     }
 
+    /// Rewinds the machine to a snapshot, the tracer is kept.
+    pub fn restore(&mut self, snapshot: &Snapshot<I, O>) {
+        self.memory = snapshot.memory.clone();
+        self.instruction_pointer = snapshot.instruction_pointer;
+        self.input = snapshot.input.clone();
+        self.output = snapshot.output.clone();
+        self.relative_base = snapshot.relative_base;
+        if let Some(cache) = &mut self.decoded {
+            cache.clear(self.memory.generation());
+        }
+    }
+}
+
+impl<I: Input + Debug, O: Output + Debug> Machine<I, O> {
     pub fn dump(&self) -> String {
         let memory_dump = format!("MEM: {:?}", self.memory);
         let ins_p_dump = format!("IP: {}", self.instruction_pointer);
         let input_dump = format!("INP: {:?}", self.input);
-        let inp_p_dump = format!("INP_P: {}", self.input_pointer);
         let output_dump = format!("OUT: {:?}", self.output);
-        let otp_p_dump = format!("OUT_P: {}", self.output_pointer);
         let rel_base = format!("REL_B: {}", self.relative_base);
 
         format!(
-            " ======\n {memory_dump}\n {ins_p_dump}\n {input_dump} \n {inp_p_dump}\n {output_dump} \n {otp_p_dump} \n {rel_base}"
+            " ======\n {memory_dump}\n {ins_p_dump}\n {input_dump} \n {output_dump} \n {rel_base}"
         )
         .to_string()
     }
+}
+
+#[allow(clippy::let_and_return)]
+#[allow(dead_code)]
+impl<I: Input, O: Output> Machine<I, O> {
+    /// Machine reading from `input` and writing to `output`.
+    pub fn with_io(memory: impl Into<Memory>, input: I, output: O) -> Self {
+        Machine {
+            memory: memory.into(),
+            instruction_pointer: 0,
+            input,
+            output,
+            relative_base: 0,
+            tracer: None,
+            watches: watch::Watches::default(),
+            decoded: None,
+            limits: Limits::default(),
+            steps_used: 0,
+            time_used: Duration::ZERO,
+        }
+    }
+
+    /// Switches how instructions are fetched, see [`Backend`].
+    pub fn set_backend(&mut self, backend: Backend) {
+        self.decoded = match backend {
+            Backend::Interpreter => None,
+            Backend::Cached => Some(cache::DecodeCache::new(self.memory.generation())),
+        };
+    }
+
+    pub fn with_backend(mut self, backend: Backend) -> Self {
+        self.set_backend(backend);
+        self
+    }
+
+    pub fn backend(&self) -> Backend {
+        match self.decoded {
+            Some(_) => Backend::Cached,
+            None => Backend::Interpreter,
+        }
+    }
+
+    /// Budgets of the machine, charged over all its [`Machine::execute`]
+    /// calls from now on, see [`Limits`].
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.limits = limits;
+        self.steps_used = 0;
+        self.time_used = Duration::ZERO;
+    }
+
+    pub fn with_limits(mut self, limits: Limits) -> Self {
+        self.set_limits(limits);
+        self
+    }
+
+    pub fn limits(&self) -> Limits {
+        self.limits
+    }
+
+    pub fn input(&self) -> &I {
+        &self.input
+    }
 
-    pub fn get_input(&mut self) -> &mut Vec<i64> {
+    pub fn input_mut(&mut self) -> &mut I {
         &mut self.input
     }
 
-    pub fn get_parameter_value(&self, parameter: &Parameter) -> i64 {
+    pub fn output(&self) -> &O {
+        &self.output
+    }
+
+    pub fn output_mut(&mut self) -> &mut O {
+        &mut self.output
+    }
+
+    pub fn get_parameter_value(&self, parameter: &Parameter) -> Result<i64, IntcodeError> {
         match parameter.mode {
             ParameterMode::Position => {
-                let addr = parameter.value as usize;
-                if addr >= self.memory.len() {
-                    panic!("Unexpected memory address for parameter address [{addr}]!");
+                let addr = parameter.value;
+                if addr < 0 || addr as usize >= self.memory.limit() {
+                    Err(IntcodeError::ReadOutOfBounds {
+                        address: addr,
+                        instruction_pointer: self.instruction_pointer,
+                    })
                 } else {
-                    self.memory[addr]
+                    Ok(self.memory[addr as usize])
                 }
             }
-            ParameterMode::Immediate => parameter.value,
+            ParameterMode::Immediate => Ok(parameter.value),
             ParameterMode::Relative => {
-                let expected_addr = parameter.value + self.relative_base;
-                if expected_addr < 0 {
-                    panic!("Unexpected memory address for relative parameter address [{expected_addr}]!");
-                }
-                let addr = expected_addr as usize;
-                if addr >= self.memory.len() {
-                    panic!("Unexpected memory address for parameter address [{addr}]!");
+                let addr = self.get_relative_address(parameter)?;
+                if addr as usize >= self.memory.limit() {
+                    Err(IntcodeError::ReadOutOfBounds {
+                        address: addr,
+                        instruction_pointer: self.instruction_pointer,
+                    })
                 } else {
-                    self.memory[addr]
+                    Ok(self.memory[addr as usize])
                 }
             }
         }
     }
 
-    pub fn get_output_parameter_address(&self, parameter: &Parameter) -> i64 {
+    pub fn get_output_parameter_address(&self, parameter: &Parameter) -> Result<i64, IntcodeError> {
         match parameter.mode {
             ParameterMode::Position => {
-                let addr = parameter.value as usize;
-                if addr >= self.memory.len() {
-                    panic!("Unexpected memory address for parameter address [{addr}]!");
+                let addr = parameter.value;
+                if addr < 0 || addr as usize >= self.memory.limit() {
+                    Err(IntcodeError::WriteOutOfBounds {
+                        address: addr,
+                        instruction_pointer: self.instruction_pointer,
+                    })
                 } else {
-                    addr as i64
+                    Ok(addr)
                 }
             }
             ParameterMode::Relative => {
-                let expected_addr = parameter.value + self.relative_base;
-                if expected_addr < 0 {
-                    panic!("Unexpected memory address for relative parameter address [{expected_addr}]!");
-                }
-                let addr = expected_addr as usize;
-                if addr >= self.memory.len() {
-                    panic!("Unexpected memory address for parameter address [{addr}]!");
+                let addr = self.get_relative_address(parameter)?;
+                if addr as usize >= self.memory.limit() {
+                    Err(IntcodeError::WriteOutOfBounds {
+                        address: addr,
+                        instruction_pointer: self.instruction_pointer,
+                    })
                 } else {
-                    addr as i64
+                    Ok(addr)
                 }
             }
-            ParameterMode::Immediate => {
-                panic!("Unexpected Immediate parameter for output instruction!")
-            }
+            ParameterMode::Immediate => Err(IntcodeError::ImmediateModeWrite {
+                instruction_pointer: self.instruction_pointer,
+            }),
         }
     }
 
-    pub fn write_to_memory(&mut self, address: i64, value: i64) {
-        let address = address as usize;
-        if address >= self.memory.len() {
-            panic!("Unexpected memory address for parameter address [{address}]!");
-        } else {
-            self.memory[address] = value;
+    fn get_relative_address(&self, parameter: &Parameter) -> Result<i64, IntcodeError> {
+        let addr = parameter.value.wrapping_add(self.relative_base);
+        if addr < 0 {
+            return Err(IntcodeError::NegativeRelativeAddress {
+                address: addr,
+                instruction_pointer: self.instruction_pointer,
+            });
         }
+        Ok(addr)
     }
 
-    // execute until halt
+    pub fn write_to_memory(&mut self, address: i64, value: i64) -> Result<(), IntcodeError> {
+        if address < 0 || address as usize >= self.memory.limit() {
+            return Err(IntcodeError::WriteOutOfBounds {
+                address,
+                instruction_pointer: self.instruction_pointer,
+            });
+        }
+        let in_sync = self
+            .decoded
+            .as_ref()
+            .is_some_and(|cache| cache.generation == self.memory.generation());
+        self.memory[address as usize] = value;
+        if let (true, Some(cache)) = (in_sync, &mut self.decoded) {
+            cache.invalidate(address as usize);
+            cache.generation = self.memory.generation();
+        }
+        Ok(())
+    }
+
+    /// Executes until halt, missing input or an error, or until one of
+    /// the machine's [`Limits`] is used up. Steps and time add up over the
+    /// calls, once a limit is hit every call stops with it until the limits
+    /// are set again.
     pub fn execute(&mut self) -> ExecuteResult {
-        //self.print();
+        self.execute_until(|_| false)
+    }
+
+    /// Executes like [`Machine::execute`], also stopping with
+    /// `ExecuteResult::Continue` as soon as `stop` holds after a step.
+    pub fn execute_until(&mut self, mut stop: impl FnMut(&Self) -> bool) -> ExecuteResult {
+        if self.limits.is_unlimited() {
+            loop {
+                match self.execute_step() {
+                    ExecuteResult::Continue if stop(self) => return ExecuteResult::Continue,
+                    ExecuteResult::Continue => (),
+                    result => return result,
+                }
+            }
+        }
 
-        let mut exe_result;
-        //let mut max_loop = 1000;
+        let start = Instant::now();
+        let result = self.execute_limited(start, stop);
+        self.time_used += start.elapsed();
+        result
+    }
 
+    fn execute_limited(
+        &mut self,
+        start: Instant,
+        mut stop: impl FnMut(&Self) -> bool,
+    ) -> ExecuteResult {
+        let limits = self.limits;
+        let mut checked_time = false;
+        let mut seen = HashSet::new();
         loop {
-            ///////////// dbg
-            //print!("{}", self.dump());
-            //if max_loop == 0 {
-            //    return Result::Err("INFL".to_string());
-            //}
-            //max_loop -= 1;
-            ///////////// end dbg
-            exe_result = self.execute_step();
-            match exe_result {
-                ExecuteResult::Continue => (),
-                ExecuteResult::Panic(message) => return ExecuteResult::Panic(message),
-                ExecuteResult::Halt(result) => {
-                    //machine.print();
-                    return ExecuteResult::Halt(result);
+            if limits.max_steps.is_some_and(|max| self.steps_used >= max) {
+                return ExecuteResult::StepLimit;
+            }
+            // checked on the first step of every call and every few steps
+            if (!checked_time || self.steps_used.is_multiple_of(TIME_CHECK_INTERVAL))
+                && limits
+                    .timeout
+                    .is_some_and(|timeout| self.time_used + start.elapsed() >= timeout)
+            {
+                return ExecuteResult::TimeLimit;
+            }
+            checked_time = true;
+
+            let ip = self.instruction_pointer;
+            let result = self.execute_step();
+            if result == ExecuteResult::WaitingInput {
+                return result;
+            }
+            self.steps_used += 1;
+            if result != ExecuteResult::Continue {
+                return result;
+            }
+
+            if limits.detect_loops {
+                if matches!(self.memory.get(ip) % 100, 3 | 4) {
+                    seen.clear();
+                } else if self.instruction_pointer <= ip && !seen.insert(self.state_hash()) {
+                    return ExecuteResult::InfiniteLoop(self.instruction_pointer);
                 }
-                ExecuteResult::WaitingInput => return ExecuteResult::WaitingInput,
+            }
+            if stop(self) {
+                return ExecuteResult::Continue;
             }
         }
     }
 
-    pub fn execute_step(&mut self) -> ExecuteResult {
-        if self.memory.len() < self.instruction_pointer + 1 {
-            return ExecuteResult::Panic("Unexpected memory overflow!".to_string());
+    /// Hash of the instruction pointer, relative base and memory.
+    fn state_hash(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.instruction_pointer.hash(&mut hasher);
+        self.relative_base.hash(&mut hasher);
+        self.memory.len().hash(&mut hasher);
+        for (id, page) in self.memory.pages() {
+            id.hash(&mut hasher);
+            page.hash(&mut hasher);
         }
+        hasher.finish()
+    }
 
-        // Parse instruction
-        match Instruction::try_from(&self.memory[self.instruction_pointer..]) {
+    /// Executes one instruction, [`Limits`] only apply to [`Machine::execute`]
+    /// and [`Machine::execute_until`].
+    pub fn execute_step(&mut self) -> ExecuteResult {
+        match self.fetch_instruction() {
             Ok(instruction) => {
                 let result = self.exe_instruction(&instruction);
 
@@ -255,26 +469,129 @@ impl Machine {
 
                 result
             }
-            Err(message) => {
-                let dump = self.dump();
-                panic!("Error while parsing instructions: {message} \n{dump}")
+            Err(error) => ExecuteResult::Error(error),
+        }
+    }
+
+    /// Instruction at the instruction pointer, from the decode cache if enabled.
+    fn fetch_instruction(&mut self) -> Result<Instruction, IntcodeError> {
+        let ip = self.instruction_pointer;
+        let generation = self.memory.generation();
+        if let Some(cache) = &mut self.decoded {
+            if cache.generation != generation {
+                cache.clear(generation);
+            }
+            if let Some(instruction) = cache.get(ip) {
+                return Ok(instruction);
             }
         }
+
+        let instruction = self.current_instruction()?;
+        if let Some(cache) = &mut self.decoded {
+            cache.insert(ip, instruction);
+        }
+        Ok(instruction)
+    }
+
+    /// Decodes the instruction at the instruction pointer.
+    pub fn current_instruction(&self) -> Result<Instruction, IntcodeError> {
+        if self.instruction_pointer >= self.memory.limit() {
+            return Err(IntcodeError::ReadOutOfBounds {
+                address: self.instruction_pointer as i64,
+                instruction_pointer: self.instruction_pointer,
+            });
+        }
+
+        Instruction::try_from(&*self.memory.instruction_words(self.instruction_pointer))
+            .map_err(|error| error.at(self.instruction_pointer))
+    }
+
+    /// Installs a tracer called after every executed instruction.
+    pub fn set_tracer(&mut self, tracer: impl trace::Tracer + 'static) {
+        self.tracer = Some(Box::new(tracer));
+    }
+
+    /// Removes the tracer, dropping it flushes any buffered trace.
+    pub fn take_tracer(&mut self) -> Option<Box<dyn trace::Tracer>> {
+        self.tracer.take()
+    }
+
+    /// Installs a [`profile::Profiler`] in place of the tracer, the returned
+    /// handle reads the profile at any time.
+    pub fn start_profiling(&mut self) -> profile::Profiler {
+        let profiler = profile::Profiler::new();
+        self.set_tracer(profiler.clone());
+        profiler
+    }
+
+    /// Calls `callback` on every operand read and write of `addresses`
+    /// matching `kind`, after the accessing instruction ran.
+    pub fn watch(
+        &mut self,
+        addresses: Range<usize>,
+        kind: watch::WatchKind,
+        callback: impl FnMut(&watch::MemoryAccess) + Send + 'static,
+    ) -> watch::WatchId {
+        self.watches.add(addresses, kind, Box::new(callback))
+    }
+
+    /// Like [`Machine::watch`], collecting the accesses in the returned log.
+    pub fn watch_log(
+        &mut self,
+        addresses: Range<usize>,
+        kind: watch::WatchKind,
+    ) -> watch::WatchLog {
+        let log = watch::WatchLog::new();
+        let events = log.clone();
+        self.watch(addresses, kind, move |event| events.push(event));
+        log
+    }
+
+    /// Removes a watch, false if it was already removed.
+    pub fn unwatch(&mut self, id: watch::WatchId) -> bool {
+        self.watches.remove(id)
     }
 
     pub fn exe_instruction(&mut self, instruction: &Instruction) -> ExecuteResult {
-        match instruction {
+        if self.tracer.is_none() && self.watches.is_empty() {
+            return self.run_instruction(instruction);
+        }
+
+        let pending = trace::PendingStep::capture(self, instruction);
+        let result = self.run_instruction(instruction);
+        if matches!(result, ExecuteResult::Continue | ExecuteResult::Halt(_)) {
+            let step = pending.finish(self);
+            if let Some(tracer) = &mut self.tracer {
+                tracer.trace(&step);
+            }
+            self.watches.notify(&step);
+        }
+        result
+    }
+
+    fn run_instruction(&mut self, instruction: &Instruction) -> ExecuteResult {
+        match self.try_exe_instruction(instruction) {
+            Ok(result) => result,
+            Err(error) => ExecuteResult::Error(error),
+        }
+    }
+
+    fn try_exe_instruction(
+        &mut self,
+        instruction: &Instruction,
+    ) -> Result<ExecuteResult, IntcodeError> {
+        Ok(match instruction {
             Instruction::Add {
                 first_parameter,
                 second_parameter,
                 result,
             } => {
-                let a = self.get_parameter_value(first_parameter);
-                let b = self.get_parameter_value(second_parameter);
-                let res_addr = self.get_output_parameter_address(result);
+                let a = self.get_parameter_value(first_parameter)?;
+                let b = self.get_parameter_value(second_parameter)?;
+                let res_addr = self.get_output_parameter_address(result)?;
 
-                let result = a + b;
-                self.write_to_memory(res_addr, result);
+                let result = a.wrapping_add(b);
+                self.write_to_memory(res_addr, result)?;
 
                 self.instruction_pointer += instruction.get_size() as usize;
 
@@ -285,39 +602,36 @@ impl Machine {
                 second_parameter,
                 result,
             } => {
-                let a = self.get_parameter_value(first_parameter);
-                let b = self.get_parameter_value(second_parameter);
-                let res_addr = self.get_output_parameter_address(result);
+                let a = self.get_parameter_value(first_parameter)?;
+                let b = self.get_parameter_value(second_parameter)?;
+                let res_addr = self.get_output_parameter_address(result)?;
 
-                let result = a * b;
-                self.write_to_memory(res_addr, result);
+                let result = a.wrapping_mul(b);
+                self.write_to_memory(res_addr, result)?;
 
                 self.instruction_pointer += instruction.get_size() as usize;
 
                 ExecuteResult::Continue
             }
             Instruction::Inp { result } => {
-                let res_addr = self.get_output_parameter_address(result);
-
-                if self.input_pointer >= self.input.len() {
-                    return ExecuteResult::WaitingInput;
-                }
+                // checked before reading, so a bad address leaves the input queued
+                let res_addr = self.get_output_parameter_address(result)?;
 
-                let value = self.input[self.input_pointer];
-                self.input_pointer += 1;
+                let Some(value) = self.input.read() else {
+                    return Ok(ExecuteResult::WaitingInput);
+                };
 
-                self.write_to_memory(res_addr, value);
+                self.write_to_memory(res_addr, value)?;
 
                 self.instruction_pointer += instruction.get_size() as usize;
 
                 ExecuteResult::Continue
             }
             Instruction::Out { parameter } => {
-                let result = self.get_parameter_value(parameter);
+                let result = self.get_parameter_value(parameter)?;
                 //println!("> {result}");
 
-                self.output.push(result);
-                self.output_pointer += 1;
+                self.output.write(result);
 
                 self.instruction_pointer += instruction.get_size() as usize;
 
@@ -334,9 +648,9 @@ impl Machine {
                 first_parameter,
                 second_parameter,
             } => {
-                if 0 != self.get_parameter_value(first_parameter) {
-                    self.instruction_pointer = self.get_parameter_value(second_parameter) as usize;
-                    return ExecuteResult::Continue;
+                if 0 != self.get_parameter_value(first_parameter)? {
+                    self.instruction_pointer = self.get_parameter_value(second_parameter)? as usize;
+                    return Ok(ExecuteResult::Continue);
                 }
 
                 self.instruction_pointer += instruction.get_size() as usize;
@@ -346,9 +660,9 @@ impl Machine {
                 first_parameter,
                 second_parameter,
             } => {
-                if 0 == self.get_parameter_value(first_parameter) {
-                    self.instruction_pointer = self.get_parameter_value(second_parameter) as usize;
-                    return ExecuteResult::Continue;
+                if 0 == self.get_parameter_value(first_parameter)? {
+                    self.instruction_pointer = self.get_parameter_value(second_parameter)? as usize;
+                    return Ok(ExecuteResult::Continue);
                 }
 
                 self.instruction_pointer += instruction.get_size() as usize;
@@ -359,13 +673,13 @@ impl Machine {
                 second_parameter,
                 result,
             } => {
-                let a = self.get_parameter_value(first_parameter);
-                let b = self.get_parameter_value(second_parameter);
+                let a = self.get_parameter_value(first_parameter)?;
+                let b = self.get_parameter_value(second_parameter)?;
 
                 let value = if a < b { 1 } else { 0 };
 
-                let res_addr = self.get_output_parameter_address(result);
-                self.write_to_memory(res_addr, value);
+                let res_addr = self.get_output_parameter_address(result)?;
+                self.write_to_memory(res_addr, value)?;
 
                 self.instruction_pointer += instruction.get_size() as usize;
                 ExecuteResult::Continue
@@ -375,25 +689,25 @@ impl Machine {
                 second_parameter,
                 result,
             } => {
-                let a = self.get_parameter_value(first_parameter);
-                let b = self.get_parameter_value(second_parameter);
+                let a = self.get_parameter_value(first_parameter)?;
+                let b = self.get_parameter_value(second_parameter)?;
 
                 let value = if a == b { 1 } else { 0 };
 
-                let res_addr = self.get_output_parameter_address(result);
-                self.write_to_memory(res_addr, value);
+                let res_addr = self.get_output_parameter_address(result)?;
+                self.write_to_memory(res_addr, value)?;
 
                 self.instruction_pointer += instruction.get_size() as usize;
                 ExecuteResult::Continue
             }
             Instruction::Arb { first_parameter } => {
-                let relative_base = self.get_parameter_value(first_parameter);
-                self.relative_base += relative_base;
+                let relative_base = self.get_parameter_value(first_parameter)?;
+                self.relative_base = self.relative_base.wrapping_add(relative_base);
 
                 self.instruction_pointer += instruction.get_size() as usize;
                 ExecuteResult::Continue
             }
-        }
+        })
     }
 
     #[allow(dead_code)]
@@ -402,42 +716,39 @@ impl Machine {
         println!("Memory: {:?}", self.memory);
         println!();
     }
-
-    pub fn get_ascii_output(&self) -> String {
-        let output_chars = self
-            .output
-            .iter()
-            .map(|i| char::from_u32(*i as u32).unwrap());
-        String::from_iter(output_chars)
-    }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum ParameterMode {
     Position,
     Immediate,
     Relative,
 }
 
-impl From<i64> for ParameterMode {
-    fn from(value: i64) -> Self {
+impl TryFrom<i64> for ParameterMode {
+    type Error = IntcodeError;
+
+    fn try_from(value: i64) -> Result<Self, Self::Error> {
         match value {
-            0 => ParameterMode::Position,
-            1 => ParameterMode::Immediate,
-            2 => ParameterMode::Relative,
-            _ => panic!("Unknown parameter mode [{value}]"),
+            0 => Ok(ParameterMode::Position),
+            1 => Ok(ParameterMode::Immediate),
+            2 => Ok(ParameterMode::Relative),
+            _ => Err(IntcodeError::UnknownParameterMode {
+                mode: value,
+                instruction_pointer: 0,
+            }),
         }
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 pub struct Parameter {
     mode: ParameterMode,
     value: i64,
 }
 
 #[allow(dead_code)]
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 pub enum Instruction {
     Add {
         first_parameter: Parameter,
@@ -496,22 +807,105 @@ impl Instruction {
             Instruction::Reserved => todo!(),
         }
     }
+
+    /// Parameters the instruction reads, in operand order.
+    pub fn read_parameters(&self) -> Vec<&Parameter> {
+        match self {
+            Instruction::Add {
+                first_parameter,
+                second_parameter,
+                ..
+            }
+            | Instruction::Mul {
+                first_parameter,
+                second_parameter,
+                ..
+            }
+            | Instruction::Lst {
+                first_parameter,
+                second_parameter,
+                ..
+            }
+            | Instruction::Equ {
+                first_parameter,
+                second_parameter,
+                ..
+            }
+            | Instruction::Jit {
+                first_parameter,
+                second_parameter,
+            }
+            | Instruction::Jif {
+                first_parameter,
+                second_parameter,
+            } => vec![first_parameter, second_parameter],
+            Instruction::Out {
+                parameter: first_parameter,
+            }
+            | Instruction::Arb { first_parameter } => vec![first_parameter],
+            Instruction::Inp { .. } | Instruction::Halt | Instruction::Reserved => vec![],
+        }
+    }
+
+    /// Parameter the instruction writes to, if any.
+    pub fn write_parameter(&self) -> Option<&Parameter> {
+        match self {
+            Instruction::Add { result, .. }
+            | Instruction::Mul { result, .. }
+            | Instruction::Lst { result, .. }
+            | Instruction::Equ { result, .. }
+            | Instruction::Inp { result } => Some(result),
+            _ => None,
+        }
+    }
 }
 
 impl TryFrom<&[i64]> for Instruction {
-    type Error = String;
+    type Error = IntcodeError;
 
+    /// Decodes the instruction at the start of `memory`.
+    ///
+    /// Errors carry instruction pointer `0`, use [`IntcodeError::at`] to
+    /// relocate them to the decoded address.
     fn try_from(memory: &[i64]) -> Result<Self, Self::Error> {
-        let opcode = memory[0];
+        let Some(&opcode) = memory.first() else {
+            return Err(IntcodeError::TruncatedInstruction {
+                instruction_pointer: 0,
+            });
+        };
+        if opcode < 0 {
+            return Err(IntcodeError::UnknownOpcode {
+                opcode,
+                instruction_pointer: 0,
+            });
+        }
         let mut opcode_len = (opcode.checked_ilog10().unwrap_or(0) + 1) as i64;
 
         let instruction_code = opcode % 100;
         let mut modes_opcode = opcode / 100;
         opcode_len -= 2;
 
+        let size = match instruction_code {
+            1 | 2 | 7 | 8 => 4,
+            5 | 6 => 3,
+            3 | 4 | 9 => 2,
+            99 => 1,
+            _ => {
+                return Err(IntcodeError::UnknownOpcode {
+                    opcode,
+                    instruction_pointer: 0,
+                })
+            }
+        };
+        if memory.len() < size {
+            return Err(IntcodeError::TruncatedInstruction {
+                instruction_pointer: 0,
+            });
+        }
+
         let mut parameter_modes = Vec::new();
         while opcode_len > 0 {
-            parameter_modes.push(ParameterMode::from(modes_opcode % 10));
+            parameter_modes.push(ParameterMode::try_from(modes_opcode % 10)?);
             modes_opcode /= 10;
             opcode_len -= 1;
         }
@@ -653,15 +1047,306 @@ impl TryFrom<&[i64]> for Instruction {
                 },
             }),
             99 => Ok(Self::Halt {}),
-            _ => Err(format!("Unknown instruction opcode [{instruction_code}]!")),
+            _ => unreachable!("opcode is validated above"),
         }
     }
 }
 
-#[derive(PartialEq)]
+#[derive(Debug, PartialEq)]
 pub enum ExecuteResult {
     Continue,
     Halt(i64),
-    Panic(String),
+    Error(IntcodeError),
     WaitingInput,
+    /// Step budget of the machine's [`Limits`] used up.
+    StepLimit,
+    /// Wall-clock budget of the machine's [`Limits`] used up.
+    TimeLimit,
+    /// State repeated without IO, at the given instruction pointer.
+    InfiniteLoop(usize),
+}
+
+impl ExecuteResult {
+    /// Error for a run stopped by its [`Limits`], `instruction_pointer`
+    /// being where the machine stopped.
+    pub fn limit_error(&self, instruction_pointer: usize) -> Option<IntcodeError> {
+        match *self {
+            ExecuteResult::StepLimit => Some(IntcodeError::StepLimit {
+                instruction_pointer,
+            }),
+            ExecuteResult::TimeLimit => Some(IntcodeError::TimeLimit {
+                instruction_pointer,
+            }),
+            ExecuteResult::InfiniteLoop(instruction_pointer) => Some(IntcodeError::InfiniteLoop {
+                instruction_pointer,
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// Fault raised by the machine, always pointing at the faulting instruction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IntcodeError {
+    ReadOutOfBounds {
+        address: i64,
+        instruction_pointer: usize,
+    },
+    WriteOutOfBounds {
+        address: i64,
+        instruction_pointer: usize,
+    },
+    NegativeRelativeAddress {
+        address: i64,
+        instruction_pointer: usize,
+    },
+    ImmediateModeWrite {
+        instruction_pointer: usize,
+    },
+    UnknownOpcode {
+        opcode: i64,
+        instruction_pointer: usize,
+    },
+    UnknownParameterMode {
+        mode: i64,
+        instruction_pointer: usize,
+    },
+    TruncatedInstruction {
+        instruction_pointer: usize,
+    },
+    /// Input ran out before the program halted, raised by [`run_machine`].
+    InputExhausted {
+        instruction_pointer: usize,
+    },
+    /// Step budget used up, see [`ExecuteResult::limit_error`].
+    StepLimit {
+        instruction_pointer: usize,
+    },
+    /// Wall-clock budget used up, see [`ExecuteResult::limit_error`].
+    TimeLimit {
+        instruction_pointer: usize,
+    },
+    /// State repeated without IO, see [`ExecuteResult::limit_error`].
+    InfiniteLoop {
+        instruction_pointer: usize,
+    },
+    /// Listing given to [`run_machine`] is not a program.
+    InvalidProgram(ProgramParseError),
+    /// Input given to [`run_machine`] is not a list of values.
+    InvalidInput(ProgramParseError),
+}
+
+impl IntcodeError {
+    /// Address of the failing instruction, `None` if nothing ran.
+    pub fn instruction_pointer(&self) -> Option<usize> {
+        match self {
+            IntcodeError::ReadOutOfBounds {
+                instruction_pointer,
+                ..
+            }
+            | IntcodeError::WriteOutOfBounds {
+                instruction_pointer,
+                ..
+            }
+            | IntcodeError::NegativeRelativeAddress {
+                instruction_pointer,
+                ..
+            }
+            | IntcodeError::ImmediateModeWrite {
+                instruction_pointer,
+            }
+            | IntcodeError::UnknownOpcode {
+                instruction_pointer,
+                ..
+            }
+            | IntcodeError::UnknownParameterMode {
+                instruction_pointer,
+                ..
+            }
+            | IntcodeError::TruncatedInstruction {
+                instruction_pointer,
+            }
+            | IntcodeError::InputExhausted {
+                instruction_pointer,
+            }
+            | IntcodeError::StepLimit {
+                instruction_pointer,
+            }
+            | IntcodeError::TimeLimit {
+                instruction_pointer,
+            }
+            | IntcodeError::InfiniteLoop {
+                instruction_pointer,
+            } => Some(*instruction_pointer),
+            IntcodeError::InvalidProgram(_) | IntcodeError::InvalidInput(_) => None,
+        }
+    }
+
+    /// Moves the error to the given instruction pointer.
+    pub fn at(mut self, address: usize) -> Self {
+        match &mut self {
+            IntcodeError::ReadOutOfBounds {
+                instruction_pointer,
+                ..
+            }
+            | IntcodeError::WriteOutOfBounds {
+                instruction_pointer,
+                ..
+            }
+            | IntcodeError::NegativeRelativeAddress {
+                instruction_pointer,
+                ..
+            }
+            | IntcodeError::ImmediateModeWrite {
+                instruction_pointer,
+            }
+            | IntcodeError::UnknownOpcode {
+                instruction_pointer,
+                ..
+            }
+            | IntcodeError::UnknownParameterMode {
+                instruction_pointer,
+                ..
+            }
+            | IntcodeError::TruncatedInstruction {
+                instruction_pointer,
+            }
+            | IntcodeError::InputExhausted {
+                instruction_pointer,
+            }
+            | IntcodeError::StepLimit {
+                instruction_pointer,
+            }
+            | IntcodeError::TimeLimit {
+                instruction_pointer,
+            }
+            | IntcodeError::InfiniteLoop {
+                instruction_pointer,
+            } => *instruction_pointer = address,
+            IntcodeError::InvalidProgram(_) | IntcodeError::InvalidInput(_) => {}
+        }
+        self
+    }
+}
+
+impl Display for IntcodeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IntcodeError::ReadOutOfBounds {
+                address,
+                instruction_pointer,
+            } => write!(
+                f,
+                "Unexpected memory address for read [{address}] at IP [{instruction_pointer}]!"
+            ),
+            IntcodeError::WriteOutOfBounds {
+                address,
+                instruction_pointer,
+            } => write!(
+                f,
+                "Unexpected memory address for write [{address}] at IP [{instruction_pointer}]!"
+            ),
+            IntcodeError::NegativeRelativeAddress {
+                address,
+                instruction_pointer,
+            } => write!(
+                f,
+                "Unexpected negative relative address [{address}] at IP [{instruction_pointer}]!"
+            ),
+            IntcodeError::ImmediateModeWrite {
+                instruction_pointer,
+            } => write!(
+                f,
+                "Unexpected Immediate parameter for output instruction at IP [{instruction_pointer}]!"
+            ),
+            IntcodeError::UnknownOpcode {
+                opcode,
+                instruction_pointer,
+            } => write!(
+                f,
+                "Unknown instruction opcode [{opcode}] at IP [{instruction_pointer}]!"
+            ),
+            IntcodeError::UnknownParameterMode {
+                mode,
+                instruction_pointer,
+            } => write!(
+                f,
+                "Unknown parameter mode [{mode}] at IP [{instruction_pointer}]!"
+            ),
+            IntcodeError::TruncatedInstruction {
+                instruction_pointer,
+            } => write!(
+                f,
+                "Truncated instruction at IP [{instruction_pointer}]!"
+            ),
+            IntcodeError::InputExhausted {
+                instruction_pointer,
+            } => write!(
+                f,
+                "Program waits for more input at IP [{instruction_pointer}]!"
+            ),
+            IntcodeError::StepLimit {
+                instruction_pointer,
+            } => write!(
+                f,
+                "Step limit reached at IP [{instruction_pointer}]!"
+            ),
+            IntcodeError::TimeLimit {
+                instruction_pointer,
+            } => write!(
+                f,
+                "Time limit reached at IP [{instruction_pointer}]!"
+            ),
+            IntcodeError::InfiniteLoop {
+                instruction_pointer,
+            } => write!(
+                f,
+                "Infinite loop detected at IP [{instruction_pointer}]!"
+            ),
+            IntcodeError::InvalidProgram(error) => write!(f, "Invalid program: {error}"),
+            IntcodeError::InvalidInput(error) => write!(f, "Invalid input: {error}"),
+        }
+    }
+}
+
+impl std::error::Error for IntcodeError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn run_machine_reports_faults() {
+        assert_eq!(run_machine("104,7,99", ""), Ok((104, vec![7])));
+        assert_eq!(
+            run_machine("3,0,3,0,99", "1"),
+            Err(IntcodeError::InputExhausted {
+                instruction_pointer: 2
+            })
+        );
+        assert_eq!(
+            run_machine("1,0,0,100", ""),
+            Err(IntcodeError::UnknownOpcode {
+                opcode: 0,
+                instruction_pointer: 4
+            })
+        );
+    }
+
+    #[test]
+    fn run_machine_reports_parse_errors() {
+        let error = run_machine("104,x,99", "").unwrap_err();
+        assert_eq!(error.instruction_pointer(), None);
+        assert_eq!(
+            error.to_string(),
+            "Invalid program: Expected i64 token at index [1], got [x]!"
+        );
+        assert_eq!(
+            run_machine("3,0,99", "1,,2"),
+            Err(IntcodeError::InvalidInput(ProgramParseError {
+                index: 1,
+                token: String::new()
+            }))
+        );
+    }
 }
@@ -0,0 +1,636 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::Instant;
+
+use super::{ExecuteResult, Input, IntcodeError, Machine, Output, Program};
+
+/// Instructions a node runs before the cooperative scheduler moves on.
+const SLICE_STEPS: usize = 10_000;
+
+/// Scheduler rounds [`Network::run`] allows by default, see [`Network::set_max_rounds`].
+pub const DEFAULT_MAX_ROUNDS: usize = 1_000_000;
+
+/// Consecutive empty reads after which a polling node counts as idle.
+const IDLE_POLLS: usize = 2;
+
+/// Values per packet of [`Route::Packets`]: address, x, y.
+const PACKET_SIZE: usize = 3;
+
+pub type NodeId = usize;
+
+/// Where a node's output goes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Route {
+    /// Every value is queued on one channel.
+    Channel(String),
+    /// Every value is copied to each channel.
+    Broadcast(Vec<String>),
+    /// Values are grouped into `[address, x, y]` packets and `x, y` is queued
+    /// for the node with that address, or on the channel named after the
+    /// address if there is no such node (e.g. `"255"`).
+    Packets,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum NodeStatus {
+    Ready,
+    WaitingInput,
+    Halted(i64),
+    Failed(IntcodeError),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    /// Every node halted or failed.
+    Halted,
+    /// Nodes are left but none can make progress: all input queues are
+    /// empty and every live node waits on or keeps polling its input.
+    Idle,
+    /// The cooperative scheduler used up its rounds with nodes still busy.
+    RoundLimit,
+}
+
+/// Result of a network run.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Report {
+    pub outcome: Outcome,
+    pub halted: Vec<String>,
+    pub failed: Vec<(String, IntcodeError)>,
+}
+
+/// Intcode machines wired through named channels.
+///
+/// Every node reads from one channel and routes its output to channels or,
+/// for packet networks, to other nodes by address. Channels are created on
+/// first use and can be seeded with [`Network::send`].
+///
+/// # Examples
+/// ```
+/// use advent_of_code::intcode::*;
+/// use advent_of_code::intcode::network::*;
+///
+/// // each node adds its phase to the incoming signal
+/// let program: Program = "3,11,3,12,1,11,12,12,4,12,99,0,0".parse().unwrap();
+/// let mut network = Network::chain(&program, &["a", "b", "c"], "out");
+/// network.send("a", 1);
+/// network.send("b", 10);
+/// network.send("c", 100);
+/// network.send("a", 0);
+///
+/// let report = network.run();
+/// assert_eq!(report.outcome, Outcome::Halted);
+/// assert_eq!(network.drain("out"), vec![111]);
+/// ```
+pub struct Network {
+    bus: Arc<Bus>,
+    nodes: Vec<Node>,
+    max_rounds: usize,
+}
+
+struct Node {
+    name: String,
+    machine: Machine<NodeInput, NodeOutput>,
+    status: NodeStatus,
+    /// Instructions run by [`Network::run`], checked against the step limit.
+    steps: u64,
+}
+
+impl Default for Network {
+    fn default() -> Self {
+        Network::new()
+    }
+}
+
+impl Network {
+    pub fn new() -> Self {
+        Network {
+            bus: Arc::new(Bus {
+                state: Mutex::new(BusState::default()),
+                signal: Condvar::new(),
+            }),
+            nodes: Vec::new(),
+            max_rounds: DEFAULT_MAX_ROUNDS,
+        }
+    }
+
+    /// Nodes reading `names[i]` and writing `names[i + 1]`, the last one writes `output`.
+    pub fn chain(program: &Program, names: &[&str], output: &str) -> Self {
+        let mut network = Network::new();
+        for (index, name) in names.iter().enumerate() {
+            let next = names.get(index + 1).copied().unwrap_or(output);
+            network.add_node(name, program, name, Route::Channel(next.to_string()));
+        }
+        network
+    }
+
+    /// Chain whose last node feeds the first one, with a copy of its output on `tap`.
+    pub fn ring(program: &Program, names: &[&str], tap: &str) -> Self {
+        let mut network = Network::new();
+        for (index, name) in names.iter().enumerate() {
+            let route = match names.get(index + 1) {
+                Some(next) => Route::Channel(next.to_string()),
+                None => Route::Broadcast(vec![names[0].to_string(), tap.to_string()]),
+            };
+            network.add_node(name, program, name, route);
+        }
+        network
+    }
+
+    /// `count` nodes exchanging packets, node `i` has address `i` and is
+    /// first given its address. Reading an empty queue gives `-1`.
+    pub fn packet_switched(program: &Program, count: usize) -> Self {
+        let mut network = Network::new();
+        for address in 0..count {
+            let name = address.to_string();
+            let node = network.add_node(&name, program, &name, Route::Packets);
+            network.set_empty_input(node, -1);
+            network.send(&name, address as i64);
+        }
+        network
+    }
+
+    /// Adds a machine running `program` that reads from `input`.
+    ///
+    /// The node address used by [`Route::Packets`] is its id.
+    pub fn add_node(&mut self, name: &str, program: &Program, input: &str, route: Route) -> NodeId {
+        let id = self.nodes.len();
+        let channel = {
+            let mut state = self.bus.state.lock().unwrap();
+            let channel = state.channel(input);
+            state.addresses.insert(id as i64, channel);
+            state.inputs.push(channel);
+            state.activity.push(Activity::Busy);
+            channel
+        };
+
+        let input = NodeInput {
+            bus: self.bus.clone(),
+            node: id,
+            channel,
+            empty_input: None,
+            blocking: false,
+            polled: false,
+        };
+        let output = NodeOutput {
+            bus: self.bus.clone(),
+            node: id,
+            route,
+            packet: Vec::with_capacity(PACKET_SIZE),
+        };
+        self.nodes.push(Node {
+            name: name.to_string(),
+            machine: program.machine_with_io(input, output),
+            status: NodeStatus::Ready,
+            steps: 0,
+        });
+        id
+    }
+
+    /// Bounds [`Network::run`] to `rounds` rounds, each giving every live node
+    /// a slice of instructions.
+    pub fn set_max_rounds(&mut self, rounds: usize) {
+        self.max_rounds = rounds;
+    }
+
+    /// Makes the node read `value` instead of waiting when its queue is empty.
+    pub fn set_empty_input(&mut self, node: NodeId, value: i64) {
+        self.nodes[node].machine.input_mut().empty_input = Some(value);
+    }
+
+    pub fn node(&self, name: &str) -> Option<NodeId> {
+        self.nodes.iter().position(|n| n.name == name)
+    }
+
+    pub fn status(&self, node: NodeId) -> &NodeStatus {
+        &self.nodes[node].status
+    }
+
+    pub fn machine(&self, node: NodeId) -> &Machine<NodeInput, NodeOutput> {
+        &self.nodes[node].machine
+    }
+
+    /// Queues a value on a channel.
+    pub fn send(&mut self, channel: &str, value: i64) {
+        let mut state = self.bus.state.lock().unwrap();
+        let channel = state.channel(channel);
+        state.queues[channel].push_back(value);
+    }
+
+    /// Queues values where a packet to `address` would go.
+    pub fn send_to_address(&mut self, address: i64, values: &[i64]) {
+        let mut state = self.bus.state.lock().unwrap();
+        let channel = state.address_channel(address);
+        state.queues[channel].extend(values);
+    }
+
+    /// Removes and returns every value queued on a channel.
+    pub fn drain(&mut self, channel: &str) -> Vec<i64> {
+        let mut state = self.bus.state.lock().unwrap();
+        let channel = state.channel(channel);
+        state.queues[channel].drain(..).collect()
+    }
+
+    /// Runs the nodes round-robin on the current thread until all halt, the
+    /// network is idle or the rounds run out.
+    ///
+    /// Nodes fail once over the step limit or timeout of their machine
+    /// [`Limits`](super::Limits), counted over the whole run. Loop detection
+    /// only applies to [`Network::run_threaded`].
+    pub fn run(&mut self) -> Report {
+        self.set_blocking(false);
+        let start = Instant::now();
+        for _ in 0..self.max_rounds {
+            for (id, node) in self.nodes.iter_mut().enumerate() {
+                if is_finished(&node.status) {
+                    continue;
+                }
+                node.status = run_slice(node, start);
+                if is_finished(&node.status) {
+                    self.bus.state.lock().unwrap().activity[id] = Activity::Finished;
+                }
+            }
+            let state = self.bus.state.lock().unwrap();
+            if state.is_idle() {
+                return self.report();
+            }
+        }
+        Report {
+            outcome: Outcome::RoundLimit,
+            ..self.report()
+        }
+    }
+
+    /// Runs every node on its own thread until all halt or the network is idle.
+    ///
+    /// A node stopped by its machine [`Limits`](super::Limits), charged over
+    /// all of its `execute` calls, fails with the matching [`IntcodeError`].
+    pub fn run_threaded(&mut self) -> Report {
+        self.set_blocking(true);
+        self.bus.state.lock().unwrap().idle = false;
+
+        std::thread::scope(|scope| {
+            for (id, node) in self.nodes.iter_mut().enumerate() {
+                if is_finished(&node.status) {
+                    continue;
+                }
+                let bus = self.bus.clone();
+                scope.spawn(move || {
+                    node.status = match node.machine.execute() {
+                        ExecuteResult::Halt(value) => NodeStatus::Halted(value),
+                        ExecuteResult::Error(error) => NodeStatus::Failed(error),
+                        result => match result.limit_error(node.machine.instruction_pointer) {
+                            Some(error) => NodeStatus::Failed(error),
+                            None => NodeStatus::WaitingInput,
+                        },
+                    };
+                    if is_finished(&node.status) {
+                        let mut state = bus.state.lock().unwrap();
+                        state.activity[id] = Activity::Finished;
+                        bus.update_idle(&mut state);
+                    }
+                });
+            }
+        });
+
+        self.bus.state.lock().unwrap().idle = false;
+        self.set_blocking(false);
+        self.report()
+    }
+
+    /// Runs cooperatively, acting as a NAT on `address`: each time the network
+    /// goes idle, the last packet sent to `address` is handed to `on_wake`
+    /// and, if it returns `true`, resent to address 0.
+    pub fn run_with_nat(
+        &mut self,
+        address: i64,
+        mut on_wake: impl FnMut(i64, i64) -> bool,
+    ) -> Report {
+        loop {
+            let report = self.run();
+            if report.outcome != Outcome::Idle {
+                return report;
+            }
+
+            let packets = {
+                let mut state = self.bus.state.lock().unwrap();
+                let channel = state.address_channel(address);
+                state.queues[channel].drain(..).collect::<Vec<_>>()
+            };
+            let [.., x, y] = packets[..] else {
+                return report;
+            };
+            if !on_wake(x, y) {
+                return report;
+            }
+            self.send_to_address(0, &[x, y]);
+        }
+    }
+
+    fn set_blocking(&mut self, blocking: bool) {
+        for node in &mut self.nodes {
+            node.machine.input_mut().blocking = blocking;
+        }
+    }
+
+    fn report(&self) -> Report {
+        let halted = self
+            .nodes
+            .iter()
+            .filter(|n| matches!(n.status, NodeStatus::Halted(_)))
+            .map(|n| n.name.clone())
+            .collect();
+        let failed = self
+            .nodes
+            .iter()
+            .filter_map(|n| match &n.status {
+                NodeStatus::Failed(error) => Some((n.name.clone(), error.clone())),
+                _ => None,
+            })
+            .collect();
+        let outcome = if self.nodes.iter().all(|n| is_finished(&n.status)) {
+            Outcome::Halted
+        } else {
+            Outcome::Idle
+        };
+        Report {
+            outcome,
+            halted,
+            failed,
+        }
+    }
+}
+
+fn is_finished(status: &NodeStatus) -> bool {
+    matches!(status, NodeStatus::Halted(_) | NodeStatus::Failed(_))
+}
+
+/// Runs a node until it waits, polls an empty queue, stops or uses up its slice.
+fn run_slice(node: &mut Node, start: Instant) -> NodeStatus {
+    let machine = &mut node.machine;
+    let limits = machine.limits();
+    for _ in 0..SLICE_STEPS {
+        let result = if limits.max_steps.is_some_and(|max| node.steps >= max) {
+            ExecuteResult::StepLimit
+        } else if limits
+            .timeout
+            .is_some_and(|timeout| start.elapsed() >= timeout)
+        {
+            ExecuteResult::TimeLimit
+        } else {
+            node.steps += 1;
+            machine.execute_step()
+        };
+        match result {
+            ExecuteResult::Continue => {
+                if machine.input().polled {
+                    machine.input_mut().polled = false;
+                    break;
+                }
+            }
+            ExecuteResult::WaitingInput => return NodeStatus::WaitingInput,
+            ExecuteResult::Halt(value) => return NodeStatus::Halted(value),
+            ExecuteResult::Error(error) => return NodeStatus::Failed(error),
+            limit => {
+                let error = limit.limit_error(machine.instruction_pointer).unwrap();
+                return NodeStatus::Failed(error);
+            }
+        }
+    }
+    NodeStatus::Ready
+}
+
+struct Bus {
+    state: Mutex<BusState>,
+    signal: Condvar,
+}
+
+impl Bus {
+    fn update_idle(&self, state: &mut BusState) {
+        if state.is_idle() {
+            state.idle = true;
+        }
+        self.signal.notify_all();
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Activity {
+    Busy,
+    Waiting,
+    Polling(usize),
+    Finished,
+}
+
+#[derive(Default)]
+struct BusState {
+    queues: Vec<VecDeque<i64>>,
+    names: HashMap<String, usize>,
+    /// Channel of each packet address.
+    addresses: HashMap<i64, usize>,
+    /// Input channel and activity of each node.
+    inputs: Vec<usize>,
+    activity: Vec<Activity>,
+    /// Set once threaded nodes found the network idle.
+    idle: bool,
+}
+
+impl BusState {
+    fn channel(&mut self, name: &str) -> usize {
+        if let Some(&channel) = self.names.get(name) {
+            return channel;
+        }
+        self.queues.push(VecDeque::new());
+        self.names.insert(name.to_string(), self.queues.len() - 1);
+        self.queues.len() - 1
+    }
+
+    fn address_channel(&mut self, address: i64) -> usize {
+        match self.addresses.get(&address) {
+            Some(&channel) => channel,
+            None => self.channel(&address.to_string()),
+        }
+    }
+
+    fn is_idle(&self) -> bool {
+        self.activity
+            .iter()
+            .zip(&self.inputs)
+            .all(|(activity, &input)| {
+                *activity == Activity::Finished
+                    || (self.queues[input].is_empty()
+                        && matches!(
+                            activity,
+                            Activity::Waiting | Activity::Polling(IDLE_POLLS..)
+                        ))
+            })
+    }
+}
+
+/// Input of a network node, reading its channel.
+pub struct NodeInput {
+    bus: Arc<Bus>,
+    node: NodeId,
+    channel: usize,
+    empty_input: Option<i64>,
+    blocking: bool,
+    /// Set when the empty value was read, the scheduler then moves on.
+    polled: bool,
+}
+
+impl Input for NodeInput {
+    fn read(&mut self) -> Option<i64> {
+        let mut state = self.bus.state.lock().unwrap();
+        loop {
+            if let Some(value) = state.queues[self.channel].pop_front() {
+                state.activity[self.node] = Activity::Busy;
+                return Some(value);
+            }
+            if state.idle {
+                return None;
+            }
+
+            if let Some(value) = self.empty_input {
+                state.activity[self.node] = match state.activity[self.node] {
+                    Activity::Polling(polls) => Activity::Polling(polls + 1),
+                    _ => Activity::Polling(1),
+                };
+                if self.blocking {
+                    self.bus.update_idle(&mut state);
+                    if state.idle {
+                        return None;
+                    }
+                }
+                self.polled = true;
+                return Some(value);
+            }
+
+            state.activity[self.node] = Activity::Waiting;
+            if !self.blocking {
+                return None;
+            }
+            self.bus.update_idle(&mut state);
+            if state.idle {
+                return None;
+            }
+            state = self.bus.signal.wait(state).unwrap();
+        }
+    }
+}
+
+/// Output of a network node, routing values to channels.
+pub struct NodeOutput {
+    bus: Arc<Bus>,
+    node: NodeId,
+    route: Route,
+    packet: Vec<i64>,
+}
+
+impl Output for NodeOutput {
+    fn write(&mut self, value: i64) {
+        let mut state = self.bus.state.lock().unwrap();
+        state.activity[self.node] = Activity::Busy;
+        match &self.route {
+            Route::Channel(name) => {
+                let channel = state.channel(name);
+                state.queues[channel].push_back(value);
+            }
+            Route::Broadcast(names) => {
+                for name in names {
+                    let channel = state.channel(name);
+                    state.queues[channel].push_back(value);
+                }
+            }
+            Route::Packets => {
+                self.packet.push(value);
+                if self.packet.len() < PACKET_SIZE {
+                    return;
+                }
+                let channel = state.address_channel(self.packet[0]);
+                state.queues[channel].extend(&self.packet[1..]);
+                self.packet.clear();
+            }
+        }
+        self.bus.signal.notify_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::fixtures::{adder, echo, spin};
+    use crate::intcode::Limits;
+
+    #[test]
+    fn halts_when_every_node_halts() {
+        let mut network = Network::chain(&adder(), &["a", "b"], "out");
+        network.send("a", 1);
+        network.send("a", 2);
+        network.send("b", 10);
+
+        let report = network.run();
+        assert_eq!(report.outcome, Outcome::Halted);
+        assert_eq!(report.halted, ["a", "b"]);
+        assert_eq!(network.drain("out"), [13]);
+    }
+
+    #[test]
+    fn deadlocked_nodes_are_idle() {
+        // each node waits for the other one
+        for threaded in [false, true] {
+            let mut network = Network::ring(&echo(), &["a", "b"], "tap");
+            let report = match threaded {
+                false => network.run(),
+                true => network.run_threaded(),
+            };
+            assert_eq!(report.outcome, Outcome::Idle);
+            assert_eq!(network.status(0), &NodeStatus::WaitingInput);
+        }
+    }
+
+    #[test]
+    fn polling_nodes_are_idle() {
+        // node 0 sends (5, 6) to address 255, then polls forever
+        let program: Program = "3,100,104,255,104,5,104,6,3,100,1105,1,8".parse().unwrap();
+        let mut network = Network::packet_switched(&program, 2);
+        let report = network.run();
+        assert_eq!(report.outcome, Outcome::Idle);
+        assert_eq!(network.drain("255"), [5, 6, 5, 6]);
+
+        let mut woken = Vec::new();
+        network.run_with_nat(255, |x, y| {
+            woken.push((x, y));
+            false
+        });
+        assert!(woken.is_empty());
+    }
+
+    #[test]
+    fn limits_fail_nodes() {
+        let spin = spin();
+        let mut network = Network::new();
+        network.add_node("spin", &spin, "in", Route::Channel("out".to_string()));
+        network.set_max_rounds(3);
+        assert_eq!(network.run().outcome, Outcome::RoundLimit);
+        assert_eq!(network.status(0), &NodeStatus::Ready);
+
+        let limited = spin.with_limits(Limits {
+            max_steps: Some(100),
+            ..Limits::default()
+        });
+        let mut network = Network::new();
+        network.add_node("spin", &limited, "in", Route::Channel("out".to_string()));
+        let failed = vec![(
+            "spin".to_string(),
+            IntcodeError::StepLimit {
+                instruction_pointer: 0,
+            },
+        )];
+        assert_eq!(network.run().failed, failed);
+        assert_eq!(network.status(0), &NodeStatus::Failed(failed[0].1.clone()));
+
+        let mut network = Network::new();
+        network.add_node("spin", &limited, "in", Route::Channel("out".to_string()));
+        let report = network.run_threaded();
+        assert_eq!((report.outcome, report.failed), (Outcome::Halted, failed));
+    }
+}
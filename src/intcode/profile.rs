@@ -0,0 +1,329 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+use std::sync::{Arc, Mutex};
+
+use super::disasm::Listing;
+use super::trace::{TraceStep, Tracer};
+use super::{Instruction, Parameter, ParameterMode};
+
+/// Execution counts of a run, gathered by a [`Profiler`].
+///
+/// Calls follow the relative-base convention of compiled firmware: once the
+/// first `ARB` has moved the base off zero to set the stack up, an `ARB`
+/// moving the base up opens a frame named after its address, and the frame
+/// closes once the base drops back to where it started. Loops are taken
+/// backward jumps to immediate targets, so returns are not counted.
+#[derive(Debug, Clone, Default)]
+pub struct Profile {
+    pub steps: u64,
+    pub by_address: HashMap<usize, u64>,
+    pub by_opcode: BTreeMap<&'static str, u64>,
+    /// Taken backward jumps to immediate targets as `(target, jump address)`.
+    pub loops: HashMap<(usize, usize), u64>,
+    pub max_relative_base: i64,
+    /// One past the highest address executed or written.
+    pub memory_high_water: usize,
+
+    /// Words of each executed address, as first seen.
+    words: HashMap<usize, Vec<i64>>,
+    frames: FrameTree,
+}
+
+/// Call stacks as a tree of `(parent, entry address)`, with steps per stack.
+#[derive(Debug, Clone)]
+struct FrameTree {
+    nodes: Vec<(usize, usize)>,
+    children: HashMap<(usize, usize), usize>,
+    steps: Vec<u64>,
+    /// Current path, with the relative base each frame started at.
+    path: Vec<(usize, i64)>,
+}
+
+impl Default for FrameTree {
+    fn default() -> Self {
+        FrameTree {
+            nodes: vec![(0, 0)],
+            children: HashMap::new(),
+            steps: vec![0],
+            path: vec![],
+        }
+    }
+}
+
+impl FrameTree {
+    fn current(&self) -> usize {
+        self.path.last().map_or(0, |(node, _)| *node)
+    }
+
+    fn enter(&mut self, entry: usize, relative_base: i64) {
+        let parent = self.current();
+        let next = self.nodes.len();
+        let node = *self.children.entry((parent, entry)).or_insert(next);
+        if node == next {
+            self.nodes.push((parent, entry));
+            self.steps.push(0);
+        }
+        self.path.push((node, relative_base));
+    }
+
+    fn leave(&mut self, relative_base: i64) {
+        while self
+            .path
+            .last()
+            .is_some_and(|(_, base)| relative_base <= *base)
+        {
+            self.path.pop();
+        }
+    }
+
+    fn stack(&self, mut node: usize) -> Vec<usize> {
+        let mut stack = Vec::new();
+        while node != 0 {
+            let (parent, entry) = self.nodes[node];
+            stack.push(entry);
+            node = parent;
+        }
+        stack.reverse();
+        stack
+    }
+}
+
+impl Profile {
+    fn record(&mut self, step: &TraceStep) {
+        let ip = step.instruction_pointer;
+        self.steps += 1;
+        *self.by_address.entry(ip).or_default() += 1;
+        let instruction = step.instruction();
+        let mnemonic = instruction.map_or("?", |instruction| instruction.mnemonic());
+        *self.by_opcode.entry(mnemonic).or_default() += 1;
+        self.words.entry(ip).or_insert_with(|| step.words.clone());
+
+        let next_relative_base = step.next_relative_base();
+        self.max_relative_base = self.max_relative_base.max(next_relative_base);
+        self.memory_high_water = self.memory_high_water.max(ip + step.words.len());
+        if let Some(write) = step.write {
+            self.memory_high_water = self.memory_high_water.max(write.address + 1);
+        }
+
+        let fallthrough = ip + step.words.len();
+        let static_jump = matches!(
+            instruction,
+            Some(
+                Instruction::Jit {
+                    second_parameter: Parameter {
+                        mode: ParameterMode::Immediate,
+                        ..
+                    },
+                    ..
+                } | Instruction::Jif {
+                    second_parameter: Parameter {
+                        mode: ParameterMode::Immediate,
+                        ..
+                    },
+                    ..
+                }
+            )
+        );
+        if static_jump
+            && step.next_instruction_pointer != fallthrough
+            && step.next_instruction_pointer <= ip
+        {
+            *self
+                .loops
+                .entry((step.next_instruction_pointer, ip))
+                .or_default() += 1;
+        }
+
+        let current = self.frames.current();
+        self.frames.steps[current] += 1;
+        if let Some(Instruction::Arb { .. }) = instruction {
+            if step.relative_base != 0 && next_relative_base > step.relative_base {
+                self.frames.enter(ip, step.relative_base);
+            } else {
+                self.frames.leave(next_relative_base);
+            }
+        }
+    }
+
+    fn describe(&self, address: usize) -> String {
+        self.words
+            .get(&address)
+            .and_then(|words| Instruction::try_from(&words[..]).ok())
+            .map_or(String::new(), |instruction| instruction.to_string())
+    }
+
+    /// Ranked report of the `top` hottest addresses and loops, loop steps
+    /// count the instructions within the loop's own address range.
+    pub fn report(&self, top: usize) -> String {
+        let percent = |count: u64| 100.0 * count as f64 / self.steps.max(1) as f64;
+        let mut report = String::new();
+
+        writeln!(report, "steps: {}", self.steps).unwrap();
+        writeln!(report, "max relative base: {}", self.max_relative_base).unwrap();
+        writeln!(report, "memory high-water mark: {}", self.memory_high_water).unwrap();
+
+        writeln!(report, "\nopcodes:").unwrap();
+        let mut opcodes: Vec<(&&str, &u64)> = self.by_opcode.iter().collect();
+        opcodes.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+        for (opcode, count) in opcodes {
+            writeln!(
+                report,
+                "  {:<4} {:>12} {:>6.2}%",
+                opcode,
+                count,
+                percent(*count)
+            )
+            .unwrap();
+        }
+
+        writeln!(report, "\nhot addresses:").unwrap();
+        let mut addresses: Vec<(&usize, &u64)> = self.by_address.iter().collect();
+        addresses.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+        for (address, count) in addresses.into_iter().take(top) {
+            writeln!(
+                report,
+                "  {:04} {:>12} {:>6.2}%  {}",
+                address,
+                count,
+                percent(*count),
+                self.describe(*address)
+            )
+            .unwrap();
+        }
+
+        writeln!(report, "\nhot loops:").unwrap();
+        let mut loops: Vec<(&(usize, usize), &u64)> = self.loops.iter().collect();
+        loops.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+        for ((target, jump), iterations) in loops.into_iter().take(top) {
+            let steps: u64 = (*target..=*jump)
+                .filter_map(|a| self.by_address.get(&a))
+                .sum();
+            writeln!(
+                report,
+                "  {}..{:04} {:>12} iterations {:>12} steps {:>6.2}%",
+                Listing::label(*target),
+                jump,
+                iterations,
+                steps,
+                percent(steps)
+            )
+            .unwrap();
+        }
+        report
+    }
+
+    /// Steps per call stack in the folded format read by flamegraph tools,
+    /// one `L0000;L0123;L0456 count` line per stack.
+    pub fn folded(&self) -> String {
+        let mut lines: Vec<String> = (0..self.frames.nodes.len())
+            .filter(|&node| self.frames.steps[node] > 0)
+            .map(|node| {
+                let stack: Vec<String> = std::iter::once(0)
+                    .chain(self.frames.stack(node))
+                    .map(Listing::label)
+                    .collect();
+                format!("{} {}", stack.join(";"), self.frames.steps[node])
+            })
+            .collect();
+        lines.sort();
+        lines.join("\n") + "\n"
+    }
+}
+
+/// Tracer building a [`Profile`], clones share the same profile.
+///
+/// # Examples
+/// ```
+/// use advent_of_code::intcode::*;
+/// use advent_of_code::intcode::profile::Profiler;
+///
+/// // count down from 3
+/// let program: Program = "1001,9,-1,9,1005,9,0,99,0,3".parse().unwrap();
+/// let profiler = Profiler::new();
+/// let mut machine = program.machine();
+/// machine.set_tracer(profiler.clone());
+/// machine.execute();
+///
+/// let profile = profiler.profile();
+/// assert_eq!(profile.steps, 7);
+/// assert_eq!(profile.by_address[&0], 3);
+/// assert_eq!(profile.loops[&(0, 4)], 2);
+/// assert_eq!(profile.folded(), "L0000 7\n");
+/// ```
+#[derive(Clone, Default)]
+pub struct Profiler {
+    profile: Arc<Mutex<Profile>>,
+}
+
+impl Profiler {
+    pub fn new() -> Self {
+        Profiler::default()
+    }
+
+    pub fn profile(&self) -> Profile {
+        self.profile.lock().unwrap().clone()
+    }
+}
+
+impl Tracer for Profiler {
+    fn trace(&mut self, step: &TraceStep) {
+        self.profile.lock().unwrap().record(step);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::asm::assemble;
+
+    fn profile(source: &str) -> Profile {
+        let profiler = Profiler::new();
+        let mut machine = assemble(source).unwrap().machine();
+        machine.set_tracer(profiler.clone());
+        machine.execute();
+        profiler.profile()
+    }
+
+    #[test]
+    fn folds_call_stacks() {
+        let profile = profile(
+            "
+            ARB #100
+            CALL f
+            CALL f
+            HALT
+        f:  OUT #1
+            CALL g
+            RET
+        g:  RET
+            ",
+        );
+        // frames are named after the `ARB` of each call site
+        assert_eq!(
+            profile.folded(),
+            "L0000 8\nL0000;L0006 6\nL0000;L0006;L0027 2\nL0000;L0015 6\nL0000;L0015;L0027 2\n"
+        );
+        assert_eq!((profile.steps, profile.max_relative_base), (24, 102));
+        assert_eq!(profile.by_opcode["ARB"], 9);
+        assert!(profile.loops.is_empty());
+    }
+
+    #[test]
+    fn ranks_loops() {
+        // counts down from 3
+        let profile = profile("a: ADD [n], #-1 -> [n]\nJT [n], #a\nHALT\nn: DATA 3");
+        assert_eq!(profile.loops, HashMap::from([((0, 4), 2)]));
+        assert_eq!(profile.memory_high_water, 9);
+
+        let report = profile.report(1);
+        assert!(report.starts_with("steps: 7\n"), "{report}");
+        assert!(
+            report.contains("  0000            3  42.86%  ADD [8], #-1 -> [8]\n"),
+            "{report}"
+        );
+        assert!(
+            report.contains("hot loops:\n  L0000..0004            2 iterations            6 steps"),
+            "{report}"
+        );
+    }
+}
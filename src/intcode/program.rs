@@ -0,0 +1,243 @@
+use std::fmt::{Debug, Display};
+use std::str::FromStr;
+use std::sync::Arc;
+
+use super::{Input, Limits, Machine, Memory, Output};
+
+/// Parsed intcode image, shared between any number of machines.
+///
+/// Machines built from a program start with its [`Limits`], unlimited
+/// unless set with [`Program::with_limits`].
+///
+/// # Examples
+/// ```
+/// use advent_of_code::intcode::*;
+///
+/// let program: Program = "1,0,0,0,99\n".parse().unwrap();
+/// let mut machine = program.with_noun_verb(0, 0).machine();
+/// assert_eq!(machine.execute(), ExecuteResult::Halt(2));
+///
+/// let error = "1,x,0".parse::<Program>().unwrap_err();
+/// assert_eq!(error.index, 1);
+/// ```
+#[derive(Clone)]
+pub struct Program {
+    image: Arc<[i64]>,
+    /// Paged image whose copy-on-write pages every machine starts from.
+    memory: Memory,
+    limits: Limits,
+}
+
+impl Program {
+    pub fn new(image: Vec<i64>) -> Self {
+        Program {
+            memory: Memory::new(image.clone()),
+            image: image.into(),
+            limits: Limits::default(),
+        }
+    }
+
+    /// Copy of the program whose machines run with `limits`.
+    pub fn with_limits(&self, limits: Limits) -> Program {
+        Program {
+            limits,
+            ..self.clone()
+        }
+    }
+
+    pub fn limits(&self) -> Limits {
+        self.limits
+    }
+
+    pub fn image(&self) -> &[i64] {
+        &self.image
+    }
+
+    pub fn to_vec(&self) -> Vec<i64> {
+        self.image.to_vec()
+    }
+
+    pub fn len(&self) -> usize {
+        self.image.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.image.is_empty()
+    }
+
+    /// Copy of the program with `address` set to `value`, growing the image if needed.
+    pub fn with_patch(&self, address: usize, value: i64) -> Program {
+        let mut image = self.to_vec();
+        if address >= image.len() {
+            image.resize(address + 1, 0);
+        }
+        image[address] = value;
+        Program::new(image).with_limits(self.limits)
+    }
+
+    /// Copy of the program with noun (address 1) and verb (address 2) set.
+    pub fn with_noun_verb(&self, noun: i64, verb: i64) -> Program {
+        self.with_patch(1, noun).with_patch(2, verb)
+    }
+
+    /// Fresh machine loaded with the image.
+    pub fn machine(&self) -> Machine {
+        Machine::new(self.memory()).with_limits(self.limits)
+    }
+
+    pub fn machine_with_input(&self, input: Vec<i64>) -> Machine {
+        Machine::new_with_input(self.memory(), input).with_limits(self.limits)
+    }
+
+    pub fn machine_with_ascii_input(&self, input: &str) -> Machine {
+        Machine::new_with_ascii_input(self.memory(), input).with_limits(self.limits)
+    }
+
+    /// Fresh machine reading from `input` and writing to `output`.
+    pub fn machine_with_io<I: Input, O: Output>(&self, input: I, output: O) -> Machine<I, O> {
+        Machine::with_io(self.memory(), input, output).with_limits(self.limits)
+    }
+
+    /// Dense memory loaded with the image, sharing its pages with the
+    /// program until written.
+    pub fn memory(&self) -> Memory {
+        self.memory.clone()
+    }
+}
+
+impl Debug for Program {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Program")
+            .field("image", &self.image)
+            .field("limits", &self.limits)
+            .finish()
+    }
+}
+
+impl PartialEq for Program {
+    fn eq(&self, other: &Self) -> bool {
+        self.image == other.image && self.limits == other.limits
+    }
+}
+
+impl Eq for Program {}
+
+impl From<Vec<i64>> for Program {
+    fn from(image: Vec<i64>) -> Self {
+        Program::new(image)
+    }
+}
+
+/// Formats as the comma-separated listing accepted by [`FromStr`].
+impl Display for Program {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let words: Vec<String> = self.image.iter().map(|w| w.to_string()).collect();
+        write!(f, "{}", words.join(","))
+    }
+}
+
+impl FromStr for Program {
+    type Err = ProgramParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_values(s).map(Program::new)
+    }
+}
+
+impl std::ops::Index<usize> for Program {
+    type Output = i64;
+
+    fn index(&self, address: usize) -> &i64 {
+        &self.image[address]
+    }
+}
+
+/// Parses a comma-separated list of values, ignoring surrounding whitespace.
+pub fn parse_values(input: &str) -> Result<Vec<i64>, ProgramParseError> {
+    input
+        .trim()
+        .split(',')
+        .enumerate()
+        .map(|(index, token)| {
+            let token = token.trim();
+            token.parse().map_err(|_| ProgramParseError {
+                index,
+                token: token.to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Token of an intcode listing that is not an `i64`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProgramParseError {
+    pub index: usize,
+    pub token: String,
+}
+
+impl Display for ProgramParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Expected i64 token at index [{}], got [{}]!",
+            self.index, self.token
+        )
+    }
+}
+
+impl std::error::Error for ProgramParseError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::ExecuteResult;
+
+    #[test]
+    fn parses_listings() {
+        let program: Program = " 1, -2 ,3\n".parse().unwrap();
+        assert_eq!(program.image(), [1, -2, 3]);
+        assert_eq!(program.to_string(), "1,-2,3");
+        assert_eq!(program.to_string().parse(), Ok(program));
+    }
+
+    #[test]
+    fn reports_bad_tokens() {
+        let error = |source: &str| source.parse::<Program>().unwrap_err();
+        assert_eq!(
+            error("1,2,x"),
+            ProgramParseError {
+                index: 2,
+                token: "x".to_string()
+            }
+        );
+        assert_eq!(error("1,,2").index, 1);
+        assert_eq!(error("").token, "");
+        assert_eq!(error("9223372036854775808").index, 0);
+        assert_eq!(
+            error("1,2.5").to_string(),
+            "Expected i64 token at index [1], got [2.5]!"
+        );
+    }
+
+    #[test]
+    fn patches_copy_the_image() {
+        let program: Program = "1,0,0,0,99".parse().unwrap();
+        let patched = program.with_noun_verb(4, 4);
+        assert_eq!(program.image(), [1, 0, 0, 0, 99]);
+        assert_eq!(patched.image(), [1, 4, 4, 0, 99]);
+        assert_eq!(program.with_patch(6, 7).image(), [1, 0, 0, 0, 99, 0, 7]);
+        assert_eq!(patched.machine().execute(), ExecuteResult::Halt(198));
+    }
+
+    #[test]
+    fn machines_share_the_paged_image() {
+        let program: Program = "1,0,0,0,99".parse().unwrap();
+        let mut machine = program.machine();
+        let first_page = |memory: &Memory| memory.pages()[0].1 as *const _;
+        assert_eq!(first_page(&machine.memory), first_page(&program.memory));
+
+        assert_eq!(machine.execute(), ExecuteResult::Halt(2));
+        assert_ne!(first_page(&machine.memory), first_page(&program.memory));
+        assert_eq!(program.machine().memory.to_vec(), [1, 0, 0, 0, 99]);
+    }
+}
@@ -0,0 +1,419 @@
+use std::collections::{HashSet, VecDeque};
+use std::fmt::Display;
+use std::path::Path;
+
+use super::{Input, Machine, Memory, Output, PAGE_SIZE};
+
+/// Leading bytes of a serialized snapshot.
+const MAGIC: &[u8; 4] = b"ICSN";
+
+/// Current snapshot format version.
+///
+/// * 1: header, registers, IO queues and memory pages.
+/// * 2: same layout followed by an FNV-1a checksum of everything before it.
+const VERSION: u32 = 2;
+
+/// First version ending with a checksum.
+const CHECKSUM_VERSION: u32 = 2;
+
+const SPARSE_FLAG: u8 = 1;
+
+/// Frozen state of a [`Machine`].
+///
+/// Memory pages are shared with the machine and copied on first write,
+/// so taking a snapshot costs a reference count per page plus cloning the IO.
+/// Snapshots of machines with the default queue input and `Vec` output
+/// can be saved to disk, older format versions still load.
+///
+/// # Examples
+/// ```
+/// use advent_of_code::intcode::*;
+///
+/// let program: Program = "3,11,1,11,11,11,4,11,1105,1,0,0".parse().unwrap();
+/// let mut machine = program.machine_with_input(vec![1]);
+/// machine.execute();
+/// let snapshot = machine.snapshot();
+///
+/// machine.push_input(10);
+/// machine.execute();
+/// assert_eq!(machine.output(), &vec![2, 20]);
+///
+/// machine.restore(&snapshot);
+/// machine.push_input(100);
+/// machine.execute();
+/// assert_eq!(machine.output(), &vec![2, 200]);
+///
+/// let mut bytes = snapshot.to_bytes();
+/// let loaded = Snapshot::from_bytes(&bytes).unwrap();
+/// assert_eq!(loaded.machine().output(), &vec![2]);
+///
+/// bytes[40] ^= 1;
+/// assert!(matches!(Snapshot::from_bytes(&bytes), Err(SnapshotError::Checksum)));
+/// ```
+#[derive(Debug, Clone)]
+pub struct Snapshot<I = VecDeque<i64>, O = Vec<i64>> {
+    pub memory: Memory,
+    pub instruction_pointer: usize,
+
+    pub input: I,
+    pub output: O,
+
+    pub relative_base: i64,
+}
+
+impl<I: Input + Clone, O: Output + Clone> Snapshot<I, O> {
+    /// Fresh machine resuming from the snapshot.
+    pub fn machine(&self) -> Machine<I, O> {
+        let mut machine =
+            Machine::with_io(self.memory.clone(), self.input.clone(), self.output.clone());
+        machine.instruction_pointer = self.instruction_pointer;
+        machine.relative_base = self.relative_base;
+        machine
+    }
+}
+
+impl Snapshot {
+    /// Little-endian binary encoding, only allocated memory pages are stored.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let pages = self.memory.pages();
+
+        let mut bytes = Vec::with_capacity(64 + pages.len() * (PAGE_SIZE + 1) * 8);
+        bytes.extend_from_slice(MAGIC);
+        bytes.extend_from_slice(&VERSION.to_le_bytes());
+        bytes.push(if self.memory.is_sparse() {
+            SPARSE_FLAG
+        } else {
+            0
+        });
+
+        let mut put = |value: u64| bytes.extend_from_slice(&value.to_le_bytes());
+        put(self.memory.limit() as u64);
+        put(self.memory.len() as u64);
+        put(self.instruction_pointer as u64);
+        put(self.relative_base as u64);
+
+        put(self.input.len() as u64);
+        self.input.iter().for_each(|v| put(*v as u64));
+        put(self.output.len() as u64);
+        self.output.iter().for_each(|v| put(*v as u64));
+
+        put(pages.len() as u64);
+        for (id, page) in pages {
+            put(id as u64);
+            page.iter().for_each(|v| put(*v as u64));
+        }
+        let checksum = fnv1a(&bytes);
+        bytes.extend_from_slice(&checksum.to_le_bytes());
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Snapshot, SnapshotError> {
+        let mut reader = Reader { bytes };
+
+        if reader.take(MAGIC.len())? != MAGIC {
+            return Err(SnapshotError::BadMagic);
+        }
+        let version = u32::from_le_bytes(reader.take(4)?.try_into().unwrap());
+        if version == 0 || version > VERSION {
+            return Err(SnapshotError::UnsupportedVersion(version));
+        }
+        if version >= CHECKSUM_VERSION {
+            let body = bytes.len().checked_sub(8).ok_or(SnapshotError::Truncated)?;
+            let (body, checksum) = bytes.split_at(body);
+            if body.len() < MAGIC.len() + 4 {
+                return Err(SnapshotError::Truncated);
+            }
+            if fnv1a(body).to_le_bytes() != checksum {
+                return Err(SnapshotError::Checksum);
+            }
+            reader.bytes = &body[MAGIC.len() + 4..];
+        }
+        let sparse = reader.take(1)?[0] & SPARSE_FLAG != 0;
+
+        let limit = reader.usize()?;
+        let len = reader.usize()?;
+        let instruction_pointer = reader.usize()?;
+        let relative_base = reader.i64()?;
+        if len > limit {
+            return Err(SnapshotError::Invalid(format!(
+                "memory length [{len}] is over the limit [{limit}]"
+            )));
+        }
+        let input = reader.values()?.into();
+        let output = reader.values()?;
+
+        let page_count = reader.usize()?;
+        let mut pages = Vec::new();
+        let mut ids = HashSet::new();
+        for _ in 0..page_count {
+            let id = reader.usize()?;
+            if id.saturating_mul(PAGE_SIZE) >= limit {
+                return Err(SnapshotError::Invalid(format!(
+                    "page [{id}] is over the memory limit [{limit}]"
+                )));
+            }
+            // dense memory allocates every page below the highest one, all
+            // of them are saved
+            if !sparse && id >= page_count {
+                return Err(SnapshotError::Invalid(format!(
+                    "dense page [{id}] is past the [{page_count}] stored pages"
+                )));
+            }
+            if !ids.insert(id) {
+                return Err(SnapshotError::Invalid(format!(
+                    "page [{id}] is stored twice"
+                )));
+            }
+            let mut page = Box::new([0; PAGE_SIZE]);
+            for cell in page.iter_mut() {
+                *cell = reader.i64()?;
+            }
+            pages.push((id, page));
+        }
+
+        if !reader.bytes.is_empty() {
+            return Err(SnapshotError::Invalid("trailing bytes".to_string()));
+        }
+
+        Ok(Snapshot {
+            memory: Memory::from_pages(sparse, limit, len, pages),
+            instruction_pointer,
+            input,
+            output,
+            relative_base,
+        })
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), SnapshotError> {
+        std::fs::write(path, self.to_bytes()).map_err(SnapshotError::Io)
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Snapshot, SnapshotError> {
+        let bytes = std::fs::read(path).map_err(SnapshotError::Io)?;
+        Snapshot::from_bytes(&bytes)
+    }
+}
+
+impl Machine {
+    /// Saves the machine state, see [`Snapshot::save`].
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), SnapshotError> {
+        self.snapshot().save(path)
+    }
+
+    /// Machine resuming from a saved state.
+    pub fn load(path: impl AsRef<Path>) -> Result<Machine, SnapshotError> {
+        Ok(Snapshot::load(path)?.machine())
+    }
+}
+
+impl<I: Input + Clone, O: Output + Clone> From<&Snapshot<I, O>> for Machine<I, O> {
+    fn from(snapshot: &Snapshot<I, O>) -> Self {
+        snapshot.machine()
+    }
+}
+
+#[derive(Debug)]
+pub enum SnapshotError {
+    Io(std::io::Error),
+    BadMagic,
+    UnsupportedVersion(u32),
+    Truncated,
+    Checksum,
+    Invalid(String),
+}
+
+impl Display for SnapshotError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SnapshotError::Io(error) => write!(f, "Snapshot IO error: {error}"),
+            SnapshotError::BadMagic => write!(f, "Not an intcode snapshot!"),
+            SnapshotError::UnsupportedVersion(version) => {
+                write!(f, "Unsupported snapshot version [{version}]!")
+            }
+            SnapshotError::Truncated => write!(f, "Snapshot is truncated!"),
+            SnapshotError::Checksum => {
+                write!(f, "Snapshot checksum mismatch, the file is corrupted!")
+            }
+            SnapshotError::Invalid(message) => write!(f, "Invalid snapshot: {message}!"),
+        }
+    }
+}
+
+impl std::error::Error for SnapshotError {}
+
+/// 64-bit FNV-1a hash.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, count: usize) -> Result<&'a [u8], SnapshotError> {
+        if self.bytes.len() < count {
+            return Err(SnapshotError::Truncated);
+        }
+        let (head, tail) = self.bytes.split_at(count);
+        self.bytes = tail;
+        Ok(head)
+    }
+
+    fn i64(&mut self) -> Result<i64, SnapshotError> {
+        Ok(i64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    fn usize(&mut self) -> Result<usize, SnapshotError> {
+        let value = self.i64()? as u64;
+        usize::try_from(value).map_err(|_| SnapshotError::Invalid(format!("[{value}] is too big")))
+    }
+
+    fn values(&mut self) -> Result<Vec<i64>, SnapshotError> {
+        let count = self.usize()?;
+        if count > self.bytes.len() / 8 {
+            return Err(SnapshotError::Truncated);
+        }
+        (0..count).map(|_| self.i64()).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode::{ExecuteResult, DEFAULT_MEMORY_LIMIT};
+
+    fn snapshot() -> Snapshot {
+        let program: crate::intcode::Program = "3,11,1,11,11,11,4,11,1105,1,0,0".parse().unwrap();
+        let mut machine = program.machine_with_input(vec![1, 5]);
+        machine.execute();
+        machine.memory.set(3 * PAGE_SIZE, 7);
+        machine.snapshot()
+    }
+
+    /// Encodes a version 1 snapshot, without checksum, from header values
+    /// and `(id, first cell)` pages.
+    fn v1(sparse: bool, limit: u64, len: u64, pages: &[(u64, i64)]) -> Vec<u8> {
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend_from_slice(&1u32.to_le_bytes());
+        bytes.push(sparse as u8);
+        let mut put = |value: u64| bytes.extend_from_slice(&value.to_le_bytes());
+        [limit, len, 0, 0, 0, 0, pages.len() as u64]
+            .into_iter()
+            .for_each(&mut put);
+        for &(id, value) in pages {
+            put(id);
+            put(value as u64);
+            (1..PAGE_SIZE).for_each(|_| put(0));
+        }
+        bytes
+    }
+
+    #[test]
+    fn round_trips() {
+        let snapshot = snapshot();
+        let loaded = Snapshot::from_bytes(&snapshot.to_bytes()).unwrap();
+
+        assert_eq!(loaded.memory.to_vec(), snapshot.memory.to_vec());
+        assert_eq!(loaded.memory.limit(), snapshot.memory.limit());
+        assert_eq!(loaded.instruction_pointer, snapshot.instruction_pointer);
+        assert_eq!(
+            (loaded.input, loaded.output),
+            (snapshot.input, snapshot.output)
+        );
+    }
+
+    #[test]
+    fn rejects_corruption() {
+        let bytes = snapshot().to_bytes();
+        for index in [0, 4, 20, bytes.len() - 1] {
+            let mut corrupted = bytes.clone();
+            corrupted[index] ^= 0x10;
+            assert!(Snapshot::from_bytes(&corrupted).is_err(), "byte {index}");
+        }
+        assert!(matches!(
+            Snapshot::from_bytes(&bytes[..bytes.len() - 8]),
+            Err(SnapshotError::Checksum)
+        ));
+        assert!(matches!(
+            Snapshot::from_bytes(&bytes[..6]),
+            Err(SnapshotError::Truncated)
+        ));
+    }
+
+    #[test]
+    fn loads_version_one() {
+        let loaded = Snapshot::from_bytes(&v1(true, 1 << 40, 2, &[(0, 5)])).unwrap();
+        assert!(loaded.memory.is_sparse());
+        assert_eq!(loaded.memory.to_vec(), [5, 0]);
+    }
+
+    #[test]
+    fn rejects_hostile_headers() {
+        let invalid = |bytes: Vec<u8>| match Snapshot::from_bytes(&bytes) {
+            Err(SnapshotError::Invalid(message)) => message,
+            other => panic!("{other:?}"),
+        };
+        let limit = DEFAULT_MEMORY_LIMIT as u64;
+        assert_eq!(
+            invalid(v1(false, 100, 101, &[])),
+            "memory length [101] is over the limit [100]"
+        );
+        assert_eq!(
+            invalid(v1(true, limit, 1, &[(1 << 20, 1)])),
+            format!("page [{}] is over the memory limit [{limit}]", 1 << 20)
+        );
+        assert_eq!(
+            invalid(v1(false, 1 << 40, 1 << 40, &[(1 << 20, 1)])),
+            format!("dense page [{}] is past the [1] stored pages", 1 << 20)
+        );
+        assert_eq!(
+            invalid(v1(true, limit, 1, &[(0, 1), (0, 2)])),
+            "page [0] is stored twice"
+        );
+    }
+
+    #[test]
+    fn keeps_a_raised_limit() {
+        // writes its first input to the address in its second one
+        let program: crate::intcode::Program = "3,100,3,7,1001,100,0,0,1105,1,0".parse().unwrap();
+        let limit = 1 << 30;
+        let memory = program.memory().with_limit(limit);
+        let mut machine = Machine::new_with_input(memory, vec![7, 5 * PAGE_SIZE as i64]);
+        machine.execute();
+
+        let loaded = Snapshot::from_bytes(&machine.snapshot().to_bytes()).unwrap();
+        assert_eq!(loaded.memory.limit(), limit);
+        assert_eq!(loaded.memory.get(5 * PAGE_SIZE), 7);
+
+        let mut resumed = loaded.machine();
+        resumed.push_input(2);
+        resumed.push_input(DEFAULT_MEMORY_LIMIT as i64 + 1);
+        assert_eq!(resumed.execute(), ExecuteResult::WaitingInput);
+        assert_eq!(resumed.memory.get(DEFAULT_MEMORY_LIMIT + 1), 2);
+    }
+
+    #[test]
+    fn machine_resumes_from_file() {
+        let path =
+            std::env::temp_dir().join(format!("intcode-snapshot-{}.snap", std::process::id()));
+        let program: crate::intcode::Program = "3,11,1,11,11,11,4,11,1105,1,0,0".parse().unwrap();
+        let mut machine = program.machine_with_input(vec![1]);
+        assert_eq!(machine.execute(), ExecuteResult::WaitingInput);
+        machine.save(&path).unwrap();
+
+        let mut loaded = Machine::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.output(), &[2]);
+        for machine in [&mut machine, &mut loaded] {
+            machine.push_input(4);
+            assert_eq!(machine.execute(), ExecuteResult::WaitingInput);
+        }
+        assert_eq!(loaded.output(), machine.output());
+        assert_eq!(loaded.memory.to_vec(), machine.memory.to_vec());
+
+        assert!(matches!(Machine::load(&path), Err(SnapshotError::Io(_))));
+    }
+}
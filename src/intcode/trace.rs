@@ -0,0 +1,491 @@
+use std::fmt::Display;
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+
+use super::{Input, Instruction, Machine, Output, ParameterMode, Program};
+
+/// Receives every instruction a [`Machine`] executes.
+///
+/// Instructions that stop on missing input or fail are not traced, they
+/// leave the machine untouched.
+pub trait Tracer: Send {
+    fn trace(&mut self, step: &TraceStep);
+}
+
+/// Memory cell overwritten by an instruction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryWrite {
+    pub address: usize,
+    pub old: i64,
+    pub new: i64,
+}
+
+/// Executed instruction with its resolved operands and side effects.
+///
+/// One JSONL line per step, optional keys are left out:
+/// `{"ip":0,"rb":0,"words":[1002,4,3,4],"args":[33,3],"write":[4,33,99],"next":4}`,
+/// with `"in"` and `"out"` holding consumed input and produced output.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceStep {
+    pub instruction_pointer: usize,
+    pub relative_base: i64,
+    pub words: Vec<i64>,
+    /// Values of the read parameters, in operand order.
+    pub operands: Vec<i64>,
+    pub write: Option<MemoryWrite>,
+    pub input: Option<i64>,
+    pub output: Option<i64>,
+    pub next_instruction_pointer: usize,
+}
+
+impl TraceStep {
+    pub fn instruction(&self) -> Option<Instruction> {
+        Instruction::try_from(&self.words[..]).ok()
+    }
+
+    /// Memory cells read as operands, as `(address, value)`, leaving out
+    /// negative addresses the machine would fault on.
+    pub fn reads(&self) -> Vec<(usize, i64)> {
+        let Some(instruction) = self.instruction() else {
+            return vec![];
+        };
+        instruction
+            .read_parameters()
+            .into_iter()
+            .zip(&self.operands)
+            .filter_map(|(parameter, &value)| {
+                let address = match parameter.mode {
+                    ParameterMode::Position => parameter.value,
+                    ParameterMode::Relative => self.relative_base.wrapping_add(parameter.value),
+                    ParameterMode::Immediate => return None,
+                };
+                usize::try_from(address)
+                    .ok()
+                    .map(|address| (address, value))
+            })
+            .collect()
+    }
+
+    /// Relative base after the step.
+    pub fn next_relative_base(&self) -> i64 {
+        match self.instruction() {
+            Some(Instruction::Arb { .. }) => self.relative_base.wrapping_add(self.operands[0]),
+            _ => self.relative_base,
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        let list = |values: &[i64]| {
+            values
+                .iter()
+                .map(|v| v.to_string())
+                .collect::<Vec<_>>()
+                .join(",")
+        };
+
+        let mut json = format!(
+            "{{\"ip\":{},\"rb\":{},\"words\":[{}],\"args\":[{}]",
+            self.instruction_pointer,
+            self.relative_base,
+            list(&self.words),
+            list(&self.operands)
+        );
+        if let Some(write) = self.write {
+            json += &format!(",\"write\":[{},{},{}]", write.address, write.old, write.new);
+        }
+        if let Some(value) = self.input {
+            json += &format!(",\"in\":{value}");
+        }
+        if let Some(value) = self.output {
+            json += &format!(",\"out\":{value}");
+        }
+        json += &format!(",\"next\":{}}}", self.next_instruction_pointer);
+        json
+    }
+
+    /// Parses a line written by [`TraceStep::to_json`].
+    ///
+    /// Numbers are read as `i64` directly, a generic JSON parser would
+    /// round values above 2^53.
+    pub fn from_json(line: &str) -> Result<TraceStep, String> {
+        let body = line
+            .trim()
+            .strip_prefix('{')
+            .and_then(|l| l.strip_suffix('}'))
+            .ok_or("expected a JSON object")?;
+
+        let mut step = TraceStep {
+            instruction_pointer: 0,
+            relative_base: 0,
+            words: vec![],
+            operands: vec![],
+            write: None,
+            input: None,
+            output: None,
+            next_instruction_pointer: 0,
+        };
+        let (mut has_ip, mut has_next) = (false, false);
+
+        for field in split_fields(body) {
+            let (key, value) = field
+                .split_once(':')
+                .ok_or_else(|| format!("expected `key:value`, got [{field}]"))?;
+            let key = key.trim().trim_matches('"');
+            let value = value.trim();
+            match key {
+                "ip" => {
+                    step.instruction_pointer = parse_number(value)?;
+                    has_ip = true;
+                }
+                "rb" => step.relative_base = parse_number(value)?,
+                "words" => step.words = parse_list(value)?,
+                "args" => step.operands = parse_list(value)?,
+                "write" => {
+                    let &[address, old, new] = &parse_list(value)?[..] else {
+                        return Err(format!("expected [address,old,new], got {value}"));
+                    };
+                    step.write = Some(MemoryWrite {
+                        address: usize::try_from(address).map_err(|e| e.to_string())?,
+                        old,
+                        new,
+                    });
+                }
+                "in" => step.input = Some(parse_number(value)?),
+                "out" => step.output = Some(parse_number(value)?),
+                "next" => {
+                    step.next_instruction_pointer = parse_number(value)?;
+                    has_next = true;
+                }
+                _ => return Err(format!("unknown key [{key}]")),
+            }
+        }
+
+        if !has_ip || !has_next || step.words.is_empty() {
+            return Err("expected `ip`, `words` and `next` keys".to_string());
+        }
+        Ok(step)
+    }
+}
+
+/// Formats as `0004: MUL [4], #3 -> [4]  args 33,3  [4] 33 -> 99`.
+impl Display for TraceStep {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.instruction() {
+            Some(instruction) => write!(f, "{:04}: {}", self.instruction_pointer, instruction)?,
+            None => write!(f, "{:04}: {:?}", self.instruction_pointer, self.words)?,
+        }
+        if !self.operands.is_empty() {
+            let operands: Vec<String> = self.operands.iter().map(|v| v.to_string()).collect();
+            write!(f, "  args {}", operands.join(","))?;
+        }
+        if let Some(write) = self.write {
+            write!(f, "  [{}] {} -> {}", write.address, write.old, write.new)?;
+        }
+        if let Some(value) = self.input {
+            write!(f, "  in {value}")?;
+        }
+        if let Some(value) = self.output {
+            write!(f, "  out {value}")?;
+        }
+        if self.relative_base != 0 {
+            write!(f, "  rb {}", self.relative_base)?;
+        }
+        Ok(())
+    }
+}
+
+/// Malformed line of a JSONL trace.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceParseError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl Display for TraceParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Trace error at line [{}]: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for TraceParseError {}
+
+/// Parses a JSONL trace, skipping blank lines.
+pub fn read_trace(source: &str) -> Result<Vec<TraceStep>, TraceParseError> {
+    source
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(index, line)| {
+            TraceStep::from_json(line).map_err(|message| TraceParseError {
+                line: index + 1,
+                message,
+            })
+        })
+        .collect()
+}
+
+/// Writes each step as a JSONL line.
+///
+/// Wrap files in a `BufWriter`, it is flushed when the machine drops the tracer.
+pub struct JsonlTracer<W: Write + Send> {
+    writer: W,
+}
+
+impl<W: Write + Send> JsonlTracer<W> {
+    pub fn new(writer: W) -> Self {
+        JsonlTracer { writer }
+    }
+}
+
+impl<W: Write + Send> Tracer for JsonlTracer<W> {
+    fn trace(&mut self, step: &TraceStep) {
+        writeln!(self.writer, "{}", step.to_json())
+            .unwrap_or_else(|error| panic!("Unexpected trace write error: {error}"));
+    }
+}
+
+/// In-memory trace, clones share the same log.
+///
+/// Keep a clone before handing it to [`Machine::set_tracer`] to read
+/// the steps back.
+#[derive(Clone, Default)]
+pub struct TraceLog {
+    steps: Arc<Mutex<Vec<TraceStep>>>,
+}
+
+impl TraceLog {
+    pub fn new() -> Self {
+        TraceLog::default()
+    }
+
+    pub fn steps(&self) -> Vec<TraceStep> {
+        self.steps.lock().unwrap().clone()
+    }
+
+    pub fn len(&self) -> usize {
+        self.steps.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl Tracer for TraceLog {
+    fn trace(&mut self, step: &TraceStep) {
+        self.steps.lock().unwrap().push(step.clone());
+    }
+}
+
+/// State captured before an instruction runs, completed into a [`TraceStep`].
+pub(super) struct PendingStep {
+    step: TraceStep,
+}
+
+impl PendingStep {
+    pub(super) fn capture<I: Input, O: Output>(
+        machine: &Machine<I, O>,
+        instruction: &Instruction,
+    ) -> Self {
+        let ip = machine.instruction_pointer;
+        let size = instruction.get_size() as usize;
+        let words = machine.memory.instruction_words(ip)[..size].to_vec();
+        let operands = instruction
+            .read_parameters()
+            .into_iter()
+            .filter_map(|parameter| machine.get_parameter_value(parameter).ok())
+            .collect();
+        let write = instruction
+            .write_parameter()
+            .and_then(|parameter| machine.get_output_parameter_address(parameter).ok())
+            .map(|address| MemoryWrite {
+                address: address as usize,
+                old: machine.memory.get(address as usize),
+                new: 0,
+            });
+
+        let step = TraceStep {
+            instruction_pointer: ip,
+            relative_base: machine.relative_base,
+            words,
+            operands,
+            write,
+            input: None,
+            output: None,
+            next_instruction_pointer: 0,
+        };
+        PendingStep { step }
+    }
+
+    /// Fills in the effects once the instruction has run.
+    pub(super) fn finish<I: Input, O: Output>(mut self, machine: &Machine<I, O>) -> TraceStep {
+        if let Some(write) = &mut self.step.write {
+            write.new = machine.memory.get(write.address);
+        }
+        match self.step.instruction() {
+            Some(Instruction::Inp { .. }) => self.step.input = self.step.write.map(|w| w.new),
+            Some(Instruction::Out { .. }) => self.step.output = self.step.operands.first().copied(),
+            _ => (),
+        }
+        self.step.next_instruction_pointer = machine.instruction_pointer;
+        self.step
+    }
+}
+
+/// Applies a traced step to a machine without executing it.
+///
+/// Consumed input is not read from the machine input, it is already
+/// part of the recorded memory write.
+pub fn apply<I: Input, O: Output>(machine: &mut Machine<I, O>, step: &TraceStep) {
+    if let Some(write) = step.write {
+        machine.memory.set(write.address, write.new);
+    }
+    if let Some(value) = step.output {
+        machine.output_mut().write(value);
+    }
+    machine.relative_base = step.next_relative_base();
+    machine.instruction_pointer = step.next_instruction_pointer;
+}
+
+/// Rebuilds the machine state after the first `count` steps of a trace
+/// recorded from a fresh machine running `program`.
+///
+/// # Examples
+/// ```
+/// use advent_of_code::intcode::*;
+/// use advent_of_code::intcode::trace::*;
+///
+/// let program: Program = "3,9,1002,9,3,9,4,9,99,0".parse().unwrap();
+/// let log = TraceLog::new();
+/// let mut machine = program.machine_with_input(vec![14]);
+/// machine.set_tracer(log.clone());
+/// machine.execute();
+///
+/// let steps = log.steps();
+/// assert_eq!(steps.len(), 4);
+/// assert_eq!(steps[1].to_string(), "0002: MUL [9], #3 -> [9]  args 14,3  [9] 14 -> 42");
+///
+/// let replayed = replay(&program, &steps, 2);
+/// assert_eq!(replayed.instruction_pointer, 6);
+/// assert_eq!(replayed.memory[9], 42);
+///
+/// let parsed = read_trace(&steps.iter().map(|s| s.to_json() + "\n").collect::<String>());
+/// assert_eq!(parsed.unwrap(), steps);
+/// ```
+pub fn replay(program: &Program, steps: &[TraceStep], count: usize) -> Machine {
+    let mut machine = program.machine();
+    for step in &steps[..count.min(steps.len())] {
+        apply(&mut machine, step);
+    }
+    machine
+}
+
+/// Index of the first step where two traces differ, `None` if identical.
+///
+/// A trace ending early diverges at its length.
+pub fn first_divergence(a: &[TraceStep], b: &[TraceStep]) -> Option<usize> {
+    a.iter()
+        .zip(b)
+        .position(|(a, b)| a != b)
+        .or_else(|| (a.len() != b.len()).then(|| a.len().min(b.len())))
+}
+
+/// Splits `a,[b,c],d` at top-level commas.
+fn split_fields(body: &str) -> Vec<&str> {
+    let mut fields = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    for (index, c) in body.char_indices() {
+        match c {
+            '[' => depth += 1,
+            ']' => depth -= 1,
+            ',' if depth == 0 => {
+                fields.push(&body[start..index]);
+                start = index + 1;
+            }
+            _ => (),
+        }
+    }
+    if !body[start..].trim().is_empty() {
+        fields.push(&body[start..]);
+    }
+    fields
+}
+
+fn parse_number<T: std::str::FromStr>(text: &str) -> Result<T, String> {
+    text.trim()
+        .parse()
+        .map_err(|_| format!("expected a number, got [{}]", text.trim()))
+}
+
+fn parse_list(text: &str) -> Result<Vec<i64>, String> {
+    let inner = text
+        .trim()
+        .strip_prefix('[')
+        .and_then(|t| t.strip_suffix(']'))
+        .ok_or_else(|| format!("expected a list, got [{text}]"))?;
+    if inner.trim().is_empty() {
+        return Ok(vec![]);
+    }
+    inner.split(',').map(parse_number).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn traced(program: &Program, input: Vec<i64>) -> Vec<TraceStep> {
+        let log = TraceLog::new();
+        let mut machine = program.machine_with_input(input);
+        machine.set_tracer(log.clone());
+        machine.execute();
+        log.steps()
+    }
+
+    #[test]
+    fn jsonl_round_trip() {
+        let program: Program = "3,9,1002,9,3,9,4,9,99,0".parse().unwrap();
+        let steps = traced(&program, vec![14]);
+        assert_eq!(
+            steps[0].to_json(),
+            r#"{"ip":0,"rb":0,"words":[3,9],"args":[],"write":[9,0,14],"in":14,"next":2}"#
+        );
+        let jsonl: String = steps.iter().map(|s| s.to_json() + "\n").collect();
+        assert_eq!(read_trace(&jsonl).unwrap(), steps);
+        assert_eq!(read_trace("{\"ip\":x}").unwrap_err().line, 1);
+    }
+
+    #[test]
+    fn replays_wrapping_relative_base() {
+        // ARB i64::MAX twice wraps the relative base to -2, then reads [rb+11]
+        let max = i64::MAX;
+        let program: Program = format!("109,{max},109,{max},204,11,99,0,0,42")
+            .parse()
+            .unwrap();
+        let steps = traced(&program, vec![]);
+        assert_eq!(steps[1].next_relative_base(), -2);
+        assert_eq!(steps[2].reads(), [(9, 42)]);
+
+        let replayed = replay(&program, &steps, steps.len());
+        assert_eq!(replayed.relative_base, -2);
+        assert_eq!(replayed.output(), &[42]);
+        assert_eq!(first_divergence(&steps, &steps[..2]), Some(2));
+    }
+
+    #[test]
+    fn skips_negative_read_addresses() {
+        // OUT [rb+1] and ADD [-1], [rb+7] with rb at -5, as in a hand-edited trace
+        let step = |words: Vec<i64>, operands: Vec<i64>| TraceStep {
+            instruction_pointer: 0,
+            relative_base: -5,
+            words,
+            operands,
+            write: None,
+            input: None,
+            output: None,
+            next_instruction_pointer: 2,
+        };
+        assert_eq!(step(vec![204, 1], vec![7]).reads(), []);
+        assert_eq!(step(vec![2001, -1, 7, 0], vec![1, 2]).reads(), [(2, 2)]);
+    }
+}
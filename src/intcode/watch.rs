@@ -0,0 +1,289 @@
+use std::collections::BTreeMap;
+use std::ops::Range;
+use std::sync::{Arc, Mutex};
+
+use super::trace::TraceStep;
+use super::{Memory, Snapshot, PAGE_SIZE};
+
+/// Accesses a watch reports.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WatchKind {
+    Read,
+    Write,
+    ReadWrite,
+}
+
+impl WatchKind {
+    fn matches(self, access: &Access) -> bool {
+        matches!(
+            (self, access),
+            (WatchKind::ReadWrite, _)
+                | (WatchKind::Read, Access::Read(_))
+                | (WatchKind::Write, Access::Write { .. })
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    Read(i64),
+    Write { old: i64, new: i64 },
+}
+
+/// Operand read or write of a watched cell.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryAccess {
+    /// Address of the accessing instruction.
+    pub instruction_pointer: usize,
+    pub address: usize,
+    pub access: Access,
+}
+
+/// Handle to remove a watch with [`Machine::unwatch`](super::Machine::unwatch).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct WatchId(usize);
+
+type Callback = Box<dyn FnMut(&MemoryAccess) + Send>;
+
+struct Watch {
+    id: WatchId,
+    addresses: Range<usize>,
+    kind: WatchKind,
+    callback: Callback,
+}
+
+/// Watches registered on a machine.
+#[derive(Default)]
+pub(super) struct Watches {
+    watches: Vec<Watch>,
+    next_id: usize,
+}
+
+impl Watches {
+    pub(super) fn is_empty(&self) -> bool {
+        self.watches.is_empty()
+    }
+
+    pub(super) fn add(
+        &mut self,
+        addresses: Range<usize>,
+        kind: WatchKind,
+        callback: Callback,
+    ) -> WatchId {
+        let id = WatchId(self.next_id);
+        self.next_id += 1;
+        self.watches.push(Watch {
+            id,
+            addresses,
+            kind,
+            callback,
+        });
+        id
+    }
+
+    pub(super) fn remove(&mut self, id: WatchId) -> bool {
+        let count = self.watches.len();
+        self.watches.retain(|watch| watch.id != id);
+        self.watches.len() < count
+    }
+
+    /// Calls the watches concerned by the accesses of an executed step,
+    /// reads before the write.
+    pub(super) fn notify(&mut self, step: &TraceStep) {
+        let reads = step
+            .reads()
+            .into_iter()
+            .map(|(address, value)| (address, Access::Read(value)));
+        let write = step.write.map(|write| {
+            (
+                write.address,
+                Access::Write {
+                    old: write.old,
+                    new: write.new,
+                },
+            )
+        });
+
+        for (address, access) in reads.chain(write) {
+            let event = MemoryAccess {
+                instruction_pointer: step.instruction_pointer,
+                address,
+                access,
+            };
+            for watch in &mut self.watches {
+                if watch.addresses.contains(&address) && watch.kind.matches(&access) {
+                    (watch.callback)(&event);
+                }
+            }
+        }
+    }
+}
+
+/// Accesses recorded by [`Machine::watch_log`](super::Machine::watch_log),
+/// clones share the same list.
+#[derive(Clone, Default)]
+pub struct WatchLog {
+    events: Arc<Mutex<Vec<MemoryAccess>>>,
+}
+
+impl WatchLog {
+    pub fn new() -> Self {
+        WatchLog::default()
+    }
+
+    pub fn events(&self) -> Vec<MemoryAccess> {
+        self.events.lock().unwrap().clone()
+    }
+
+    /// Recorded accesses, emptying the log.
+    pub fn take(&self) -> Vec<MemoryAccess> {
+        std::mem::take(&mut self.events.lock().unwrap())
+    }
+
+    pub fn len(&self) -> usize {
+        self.events.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub(super) fn push(&self, event: &MemoryAccess) {
+        self.events.lock().unwrap().push(*event);
+    }
+}
+
+/// Memory cell holding different values in two states.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CellChange {
+    pub address: usize,
+    pub before: i64,
+    pub after: i64,
+}
+
+/// Cells changed between two snapshots, ordered by address.
+///
+/// Pages still shared by both snapshots are skipped without comparing them.
+///
+/// # Examples
+/// ```
+/// use advent_of_code::intcode::*;
+/// use advent_of_code::intcode::watch::*;
+///
+/// // reads a paddle move, adds it to the paddle position in [13]
+/// let program: Program = "3,12,1,12,13,13,4,13,1105,1,0,0,0,20".parse().unwrap();
+/// let mut machine = program.machine();
+/// let reads = machine.watch_log(13..14, WatchKind::Read);
+///
+/// machine.execute();
+/// let before = machine.snapshot();
+/// machine.push_input(-1);
+/// machine.execute();
+/// let after = machine.snapshot();
+///
+/// assert_eq!(
+///     diff(&before, &after),
+///     vec![
+///         CellChange { address: 12, before: 0, after: -1 },
+///         CellChange { address: 13, before: 20, after: 19 },
+///     ]
+/// );
+/// assert_eq!(reads.events().iter().map(|e| e.instruction_pointer).collect::<Vec<_>>(), [2, 6]);
+/// ```
+pub fn diff<I, O>(before: &Snapshot<I, O>, after: &Snapshot<I, O>) -> Vec<CellChange> {
+    diff_memory(&before.memory, &after.memory)
+}
+
+/// Cells holding different values in two memories, ordered by address.
+pub fn diff_memory(before: &Memory, after: &Memory) -> Vec<CellChange> {
+    const ZERO_PAGE: [i64; PAGE_SIZE] = [0; PAGE_SIZE];
+
+    let mut pages: BTreeMap<usize, (&[i64; PAGE_SIZE], &[i64; PAGE_SIZE])> = BTreeMap::new();
+    for (id, page) in before.pages() {
+        pages.insert(id, (page, &ZERO_PAGE));
+    }
+    for (id, page) in after.pages() {
+        pages.entry(id).or_insert((&ZERO_PAGE, page)).1 = page;
+    }
+
+    let mut changes = Vec::new();
+    for (id, (old, new)) in pages {
+        if std::ptr::eq(old, new) {
+            continue;
+        }
+        for (offset, (&before, &after)) in old.iter().zip(new).enumerate() {
+            if before != after {
+                changes.push(CellChange {
+                    address: id * PAGE_SIZE + offset,
+                    before,
+                    after,
+                });
+            }
+        }
+    }
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc::channel;
+
+    use super::*;
+    use crate::intcode::{Machine, Program};
+
+    #[test]
+    fn reports_matching_accesses() {
+        // [9] = [9] + [10], reading both cells and writing 9
+        let program: Program = "1,9,10,9,99,0,0,0,0,4,5".parse().unwrap();
+        let mut machine = program.machine();
+        let writes = machine.watch_log(9..10, WatchKind::Write);
+        let reads = machine.watch_log(9..11, WatchKind::Read);
+        let all = machine.watch_log(10..11, WatchKind::ReadWrite);
+        let (sender, receiver) = channel();
+        let id = machine.watch(0..100, WatchKind::ReadWrite, move |e| {
+            sender.send(e.address).unwrap()
+        });
+        assert!(machine.unwatch(id));
+        assert!(!machine.unwatch(id));
+        machine.execute();
+
+        let write = MemoryAccess {
+            instruction_pointer: 0,
+            address: 9,
+            access: Access::Write { old: 4, new: 9 },
+        };
+        assert_eq!(writes.take(), [write]);
+        assert!(writes.is_empty());
+        let read_values: Vec<Access> = reads.events().iter().map(|e| e.access).collect();
+        assert_eq!(read_values, [Access::Read(4), Access::Read(5)]);
+        assert_eq!(all.len(), 1);
+        assert!(receiver.try_recv().is_err());
+    }
+
+    #[test]
+    fn diffs_sparse_and_dense_memories() {
+        let mut machine = Machine::new(Memory::sparse(vec![1, 2]).with_limit(1 << 30));
+        let before = machine.snapshot();
+        machine.memory[1] = 3;
+        machine.memory[5 * PAGE_SIZE] = 7;
+        let after = machine.snapshot();
+
+        let change = |address, before, after| CellChange {
+            address,
+            before,
+            after,
+        };
+        assert_eq!(
+            diff(&before, &after),
+            [change(1, 2, 3), change(5 * PAGE_SIZE, 0, 7)]
+        );
+        assert_eq!(
+            diff(&after, &before),
+            [change(1, 3, 2), change(5 * PAGE_SIZE, 7, 0)]
+        );
+        assert!(diff(&after, &after).is_empty());
+
+        let dense = Memory::new(vec![1, 3]);
+        assert_eq!(diff_memory(&before.memory, &dense), [change(1, 2, 3)]);
+    }
+}
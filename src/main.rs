@@ -1,4 +1,4 @@
-use advent_of_code::template::commands::{all, download, read, scaffold, solve, time};
+use advent_of_code::template::commands::{all, disasm, download, read, scaffold, solve, time};
 use args::{parse, AppArguments};
 
 #[cfg(feature = "today")]
@@ -36,6 +36,12 @@ mod args {
             day: Option<Day>,
             store: bool,
         },
+        Disasm {
+            day: Day,
+            analysis: bool,
+            dot: bool,
+            decompile: bool,
+        },
         #[cfg(feature = "today")]
         Today,
     }
@@ -57,6 +63,12 @@ mod args {
                     store,
                 }
             }
+            Some("disasm") => AppArguments::Disasm {
+                day: args.free_from_str()?,
+                analysis: args.contains("--analysis"),
+                dot: args.contains("--dot"),
+                decompile: args.contains("--decompile"),
+            },
             Some("download") => AppArguments::Download {
                 day: args.free_from_str()?,
             },
@@ -104,6 +116,12 @@ fn main() {
         Ok(args) => match args {
             AppArguments::All { release } => all::handle(release),
             AppArguments::Time { day, all, store } => time::handle(day, all, store),
+            AppArguments::Disasm {
+                day,
+                analysis,
+                dot,
+                decompile,
+            } => disasm::handle(day, analysis, dot, decompile),
             AppArguments::Download { day } => download::handle(day),
             AppArguments::Read { day } => read::handle(day),
             AppArguments::Scaffold {
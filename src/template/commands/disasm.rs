@@ -0,0 +1,28 @@
+use std::process;
+
+use crate::intcode::analysis::analyze;
+use crate::intcode::decompile;
+use crate::intcode::disasm::disassemble;
+use crate::intcode::Program;
+use crate::template::{read_file, Day};
+
+pub fn handle(day: Day, analysis: bool, dot: bool, decompile: bool) {
+    let input = read_file("inputs", day);
+    let program: Program = match input.parse() {
+        Ok(program) => program,
+        Err(e) => {
+            eprintln!("failed to parse intcode program for day {day}: {e}");
+            process::exit(1);
+        }
+    };
+
+    if dot {
+        print!("{}", analyze(&program).to_dot());
+    } else if decompile {
+        print!("{}", decompile::decompile(&program));
+    } else if analysis {
+        print!("{}", analyze(&program));
+    } else {
+        print!("{}", disassemble(&program));
+    }
+}